@@ -0,0 +1,19 @@
+// Bands of the CelShading struct, sorted by threshold. Light below the lowest
+// threshold is none at all
+uniform float celThresholds[8];
+uniform float celLevels[8];
+uniform int celBandCount;
+
+float celBand(float intensity)
+{
+    float level = 0.0;
+    for(int i = 0; i < celBandCount; i++)
+    {
+        if(intensity > celThresholds[i])
+        {
+            level = celLevels[i];
+        }
+    }
+
+    return level;
+}
@@ -0,0 +1,71 @@
+// Lights of the Lighting struct, directions point towards the light and the
+// last directional slot is for the headlight
+uniform vec3 ambientLight;
+
+uniform vec3 lightDirections[5];
+uniform vec3 lightColours[5];
+uniform int directionalCount;
+
+uniform vec3 pointPositions[4];
+uniform vec3 pointColours[4];
+uniform float pointRanges[4];
+uniform int pointCount;
+
+// Blinn-Phong, a strength of zero turns it off
+uniform float specularStrength;
+uniform float shininess;
+uniform vec3 eyePos;
+
+#include "common/shadow.glsl"
+
+#ifdef CEL_SHADING
+#include "common/cel.glsl"
+#endif
+
+// With CEL_SHADING the diffuse light falls into bands and the highlight is
+// either there or not
+float diffuseSpecular(vec3 surface_normal, vec3 to_light, vec3 to_eye)
+{
+    float diffuse = max(dot(surface_normal, to_light), 0.0);
+#ifdef CEL_SHADING
+    diffuse = celBand(diffuse);
+#endif
+    if(diffuse <= 0.0 || specularStrength <= 0.0)
+    {
+        return diffuse;
+    }
+
+    vec3 halfway = normalize(to_light + to_eye);
+    float specular = pow(max(dot(surface_normal, halfway), 0.0), shininess);
+#ifdef CEL_SHADING
+    specular = step(0.5, specular);
+#endif
+    return diffuse + specularStrength * specular;
+}
+
+// What the surface colour is multiplied by
+vec3 lightColour(vec3 surface_normal, vec3 surface_pos)
+{
+#ifdef UNLIT
+    return vec3(1.0);
+#else
+    vec3 to_eye = normalize(eyePos - surface_pos);
+    vec3 light = ambientLight;
+
+    // Only the first directional light casts shadows
+    for(int i = 0; i < directionalCount; i++)
+    {
+        float shadow = i == 0 ? shadowLight(surface_pos) : 1.0;
+        light += shadow * diffuseSpecular(surface_normal, lightDirections[i], to_eye) * lightColours[i];
+    }
+
+    for(int i = 0; i < pointCount; i++)
+    {
+        vec3 offset = pointPositions[i] - surface_pos;
+        float falloff = max(1.0 - length(offset) / pointRanges[i], 0.0);
+        light += diffuseSpecular(surface_normal, normalize(offset), to_eye) * falloff * falloff * pointColours[i];
+    }
+
+    return light;
+#endif
+}
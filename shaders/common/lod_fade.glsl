@@ -0,0 +1,23 @@
+// Screen-door cross-fade between levels of detail and debug tint of the level
+uniform float lodFade;
+uniform int lodFadeIn;
+uniform vec4 lodTint;
+
+const float bayer[16] = float[](0.0, 8.0, 2.0, 10.0,
+                                12.0, 4.0, 14.0, 6.0,
+                                3.0, 11.0, 1.0, 9.0,
+                                15.0, 7.0, 13.0, 5.0);
+
+// Whether the fragment is left out of the level fading in or out
+bool lodDiscarded()
+{
+    int dither_index = (int(gl_FragCoord.y) % 4) * 4 + int(gl_FragCoord.x) % 4;
+    float threshold = (bayer[dither_index] + 0.5) / 16.0;
+    return (lodFadeIn == 0 && threshold < lodFade) || (lodFadeIn != 0 && threshold >= lodFade);
+}
+
+// Debug overlay, zero alpha leaves the colour alone
+vec4 lodTinted(vec4 colour)
+{
+    return mix(colour, vec4(lodTint.rgb, colour.a), lodTint.a);
+}
@@ -0,0 +1,16 @@
+// Morph targets blended on the GPU, two texels per vertex and target holding
+// the position and normal deltas
+uniform samplerBuffer morphDeltas;
+uniform int morphCount;
+uniform int morphVerts;
+uniform float morphWeights[8];
+
+void applyMorphs(inout vec3 pos, inout vec3 morph_normal)
+{
+    for(int target = 0; target < morphCount; target++)
+    {
+        int texel = 2 * (target * morphVerts + int(model_vertex + 0.5));
+        pos += morphWeights[target] * texelFetch(morphDeltas, texel).xyz;
+        morph_normal += morphWeights[target] * texelFetch(morphDeltas, texel + 1).xyz;
+    }
+}
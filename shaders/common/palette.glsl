@@ -0,0 +1,16 @@
+// Colours of the model, faces index into it
+uniform vec4 palette[32];
+uniform int paletteSize;
+
+// Highlighted vertices are yellow, vertices with a true colour override the
+// palette
+vec4 vertexColour()
+{
+    if(highlightFactor > 0.0)
+    {
+        return vec4(0.7, 0.7, 0.0, 1.0);
+    }
+
+    int entry = clamp(int(palette_index + 0.5), 0, max(paletteSize - 1, 0));
+    return mix(palette[entry], vertex_colour, colour_weight);
+}
@@ -0,0 +1,7 @@
+// Variants: FLAT_SHADING gives every face the normal of its provoking vertex
+// instead of interpolating, UNLIT skips the light altogether
+#ifdef FLAT_SHADING
+#define NORMAL_QUALIFIER flat
+#else
+#define NORMAL_QUALIFIER smooth
+#endif
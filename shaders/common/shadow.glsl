@@ -0,0 +1,39 @@
+// Depth map of the first directional light, a shadow lets none of its light
+// through
+uniform sampler2D shadowMap;
+uniform int shadowsEnabled;
+uniform mat4 lightMat;
+uniform float shadowBias;
+uniform int shadowPcfRadius;
+
+// Share of the light that reaches the surface, averaged over the texels around
+// it so the edges are soft
+float shadowLight(vec3 surface_pos)
+{
+    if(shadowsEnabled == 0)
+    {
+        return 1.0;
+    }
+
+    vec4 light_pos = lightMat * vec4(surface_pos, 1.0);
+    vec3 map_pos = light_pos.xyz / light_pos.w * 0.5 + 0.5;
+    if(map_pos.z > 1.0)
+    {
+        return 1.0;
+    }
+
+    vec2 texel = 1.0 / vec2(textureSize(shadowMap, 0));
+    float lit = 0.0;
+    int samples = 0;
+    for(int x = -shadowPcfRadius; x <= shadowPcfRadius; x++)
+    {
+        for(int y = -shadowPcfRadius; y <= shadowPcfRadius; y++)
+        {
+            float depth = texture(shadowMap, map_pos.xy + vec2(x, y) * texel).r;
+            lit += map_pos.z - shadowBias > depth ? 0.0 : 1.0;
+            samples++;
+        }
+    }
+
+    return lit / float(samples);
+}
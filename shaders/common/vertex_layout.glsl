@@ -0,0 +1,9 @@
+// Attributes of every model vertex, in the order the editor packs them
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in float highlightFactor;
+layout (location = 2) in vec3 normal;
+layout (location = 3) in float palette_index;
+layout (location = 4) in vec4 vertex_colour;
+layout (location = 5) in float colour_weight;
+layout (location = 6) in vec2 uv;
+layout (location = 7) in float model_vertex;
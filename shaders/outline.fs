@@ -0,0 +1,9 @@
+#version 330 core
+
+out vec4 fragColour;
+
+uniform vec4 outlineColour;
+
+void main() {
+    fragColour = outlineColour;
+}
@@ -0,0 +1,27 @@
+#version 330 core
+
+#include "common/vertex_layout.glsl"
+#include "common/morph.glsl"
+
+uniform mat4 transMat;
+
+// Width in pixels of the target being drawn to
+uniform float outlineWidth;
+uniform vec2 targetSize;
+
+void main() {
+    vec3 pos = aPos;
+    vec3 morph_normal = normal;
+    applyMorphs(pos, morph_normal);
+
+    // Pushed out along the normal as seen on screen, the offset is scaled by
+    // w so it stays the same number of pixels after the perspective divide
+    vec4 clip_pos = transMat * vec4(pos, 1.0);
+    vec2 screen_normal = (transMat * vec4(morph_normal, 0.0)).xy;
+    if(length(screen_normal) > 0.0)
+    {
+        clip_pos.xy += normalize(screen_normal) * outlineWidth * 2.0 / targetSize * clip_pos.w;
+    }
+
+    gl_Position = clip_pos;
+}
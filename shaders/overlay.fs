@@ -0,0 +1,15 @@
+#version 330
+
+// Interpolated values from the vertex shaders
+in vec2 UV;
+
+// Ouput data
+out vec4 colour;
+
+// Flat colour over the whole viewport
+uniform vec4 overlayColour;
+
+void main()
+{
+    colour = overlayColour;
+}
@@ -0,0 +1,16 @@
+#version 330
+
+// Interpolated values from the vertex shaders
+in vec2 UV;
+
+// Ouput data
+out vec4 colour;
+
+// Depth texture of the shadow map, the depth comes in the red channel
+uniform sampler2D textureSampler;
+
+void main()
+{
+    float depth = texture(textureSampler, UV).r;
+    colour = vec4(depth, depth, depth, 1.0);
+}
@@ -0,0 +1,15 @@
+#version 330 core
+
+#include "common/vertex_layout.glsl"
+#include "common/morph.glsl"
+
+uniform mat4 lightMat;
+uniform mat4 modelMat;
+
+void main() {
+    vec3 pos = aPos;
+    vec3 morph_normal = normal;
+    applyMorphs(pos, morph_normal);
+
+    gl_Position = lightMat * modelMat * vec4(pos, 1.0);
+}
@@ -1,23 +1,28 @@
 #version 330 core
 
+#include "common/shading.glsl"
+#include "common/lighting.glsl"
+#include "common/lod_fade.glsl"
+
 in vec4 colour;
-in vec3 vert_normal;
+NORMAL_QUALIFIER in vec3 vert_normal;
+in vec3 frag_pos;
 
 out vec4 fragColour;
 
-void main() {
-    float intensity = max(dot(vert_normal, normalize(vec3(1.0, 0.0, 1.0))), 0);
+// Share of the colour let through to what is behind, for x-ray
+uniform float transparency;
 
-    /*if(intensity < 0.1)
+void main() {
+    if(lodDiscarded())
     {
-        intensity = 0.1;
+        discard;
     }
-    else
-    {
-        intensity = 0.9;
-    }*/
 
-    intensity += 0.1;
+    vec3 normal = normalize(vert_normal);
+    fragColour = vec4(lightColour(normal, frag_pos) * colour.rgb, colour.a);
+
+    fragColour = lodTinted(fragColour);
 
-    fragColour = intensity * colour;
-}
\ No newline at end of file
+    fragColour.a *= 1.0 - transparency;
+}
@@ -1,45 +1,28 @@
 #version 330 core
 
-layout (location = 0) in vec3 aPos;
-layout (location = 1) in float highlightFactor;
-layout (location = 2) in vec3 normal;
-layout (location = 3) in float palette_index;
+#include "common/vertex_layout.glsl"
+#include "common/shading.glsl"
 
 out vec4 colour;
-out vec3 vert_normal;
+NORMAL_QUALIFIER out vec3 vert_normal;
+out vec3 frag_pos;
 
 uniform mat4 transMat;
+uniform mat4 modelMat;
+uniform mat3 normalMat;
+
+#include "common/palette.glsl"
+#include "common/morph.glsl"
 
 void main() {
-    gl_Position = transMat * vec4(aPos.x, aPos.y, aPos.z, 1.0);
-
-    vec3 vert_colour = vec3(0.9, 0.9, 0.9);
-
-    vert_normal = normalize(normal);
-
-    if(highlightFactor > 0.0)
-    {
-        vert_colour = vec3(0.7, 0.7, 0.0);
-    }
-    else
-    {
-        if(palette_index == 0.0)
-        {
-            vert_colour = vec3(0.4, 0.4, 0.4);
-        }
-        if(palette_index == 1.0)
-        {
-            vert_colour = vec3(0.7, 0.3, 0.3);
-        }
-        if(palette_index == 2.0)
-        {
-            vert_colour = vec3(0.3, 0.3, 0.7);
-        }
-        if(palette_index == 3.0)
-        {
-            vert_colour = vec3(0.3, 0.7, 0.3);
-        }
-    }
-
-    colour = vec4(vert_colour, 1.0);
+    vec3 pos = aPos;
+    vec3 morph_normal = normal;
+    applyMorphs(pos, morph_normal);
+
+    gl_Position = transMat * vec4(pos, 1.0);
+    frag_pos = (modelMat * vec4(pos, 1.0)).xyz;
+
+    vert_normal = normalize(normalMat * morph_normal);
+
+    colour = vertexColour();
 }
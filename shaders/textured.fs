@@ -0,0 +1,32 @@
+#version 330 core
+
+#include "common/shading.glsl"
+#include "common/lighting.glsl"
+#include "common/lod_fade.glsl"
+
+in vec4 colour;
+NORMAL_QUALIFIER in vec3 vert_normal;
+in vec3 frag_pos;
+in vec2 tex_coords;
+
+out vec4 fragColour;
+
+// Share of the colour let through to what is behind, for x-ray
+uniform float transparency;
+
+uniform sampler2D tex;
+
+void main() {
+    if(lodDiscarded())
+    {
+        discard;
+    }
+
+    // The texture modulates the palette or vertex colour
+    vec3 normal = normalize(vert_normal);
+    fragColour = vec4(lightColour(normal, frag_pos), 1.0) * colour * texture(tex, tex_coords);
+
+    fragColour = lodTinted(fragColour);
+
+    fragColour.a *= 1.0 - transparency;
+}
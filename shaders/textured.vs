@@ -0,0 +1,30 @@
+#version 330 core
+
+#include "common/vertex_layout.glsl"
+#include "common/shading.glsl"
+
+out vec4 colour;
+NORMAL_QUALIFIER out vec3 vert_normal;
+out vec3 frag_pos;
+out vec2 tex_coords;
+
+uniform mat4 transMat;
+uniform mat4 modelMat;
+uniform mat3 normalMat;
+
+#include "common/palette.glsl"
+#include "common/morph.glsl"
+
+void main() {
+    vec3 pos = aPos;
+    vec3 morph_normal = normal;
+    applyMorphs(pos, morph_normal);
+
+    gl_Position = transMat * vec4(pos, 1.0);
+    frag_pos = (modelMat * vec4(pos, 1.0)).xyz;
+
+    vert_normal = normalize(normalMat * morph_normal);
+    tex_coords = uv;
+
+    colour = vertexColour();
+}
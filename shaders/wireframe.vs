@@ -1,16 +1,13 @@
 #version 330 core
 
-layout (location = 0) in vec3 vert_pos;
-layout (location = 1) in float highlightFactor;
-layout (location = 2) in vec3 normal;
-layout (location = 3) in float palette_index;
+#include "common/vertex_layout.glsl"
 
 out vec4 colour;
 
 uniform mat4 transMat;
 
 void main() {
-    gl_Position = transMat * vec4(vert_pos.x, vert_pos.y, vert_pos.z, 1.0);
+    gl_Position = transMat * vec4(aPos, 1.0);
 
     if(highlightFactor > 0.0)
     {
@@ -1,9 +1,49 @@
 mod shader;
+mod shader_source;
 mod camera;
 mod window;
 mod model;
+mod selection;
+mod group;
+pub mod primitives;
+mod loft;
+mod scene;
+mod palette;
+mod gradient;
+mod texture;
+mod uv_projection;
+mod morph;
+mod animation;
+mod lod;
+mod collision;
+mod diff;
+mod software;
+mod screenshot;
+mod backend;
+mod display_mode;
+mod lighting;
+mod cel_shading;
+mod shadow;
+mod viewport_layout;
 
-pub use shader::Shader;
+pub use shader::{Shader, ShaderError};
 pub use camera::Camera;
 pub use window::*;
 pub use model::*;
+pub use selection::*;
+pub use primitives::PrimitiveSettings;
+pub use loft::*;
+pub use scene::*;
+pub use palette::*;
+pub use gradient::*;
+pub use texture::*;
+pub use uv_projection::*;
+pub use animation::*;
+pub use lod::*;
+pub use software::*;
+pub use screenshot::*;
+pub use display_mode::*;
+pub use lighting::*;
+pub use cel_shading::*;
+pub use shadow::*;
+pub use viewport_layout::*;
@@ -0,0 +1,343 @@
+use cgmath::{Matrix4, Vector3, Rad};
+use cgmath::prelude::*;
+
+use std::fs::File;
+use std::io::{Write, BufReader, BufRead};
+
+
+// ---- RIGID PART OF A MODEL ----
+// Parts move the vertices of the group with the same name, rotating them
+// around the axis through the pivot
+pub struct Part {
+    pub name: String,
+    pub pivot: Vector3<f32>,
+    pub axis: Vector3<f32>
+}
+
+// How the values go from one key to the next. Bezier uses handles a sixth of
+// the way towards the neighbouring keys so the motion eases through them
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Interpolation {
+    Linear,
+    Bezier
+}
+
+// Angle in radians around the axis of the part, translation in model space
+#[derive(Clone, Copy)]
+pub struct PartKey {
+    pub time: f32,
+    pub angle: f32,
+    pub translation: Vector3<f32>,
+    pub interpolation: Interpolation
+}
+
+pub struct PartTrack {
+    part: String,
+    keys: Vec<PartKey>
+}
+
+pub struct Clip {
+    pub name: String,
+    pub looping: bool,
+    tracks: Vec<PartTrack>
+}
+
+// Playback time of a clip, advanced with the frame delta time
+pub struct AnimationClock {
+    pub time: f32,
+    pub speed: f32,
+    pub playing: bool
+}
+
+
+impl Part {
+    pub fn new(name: &str, pivot: Vector3<f32>, axis: Vector3<f32>) -> Part {
+        Part {
+            name: String::from(name),
+            pivot,
+            axis
+        }
+    }
+
+    // Rotate around the pivot then move
+    pub fn matrix(&self, angle: f32, translation: Vector3<f32>) -> Matrix4<f32> {
+        let axis = if self.axis.magnitude() > 0.0 { self.axis.normalize() } else { Vector3::unit_z() };
+
+        Matrix4::from_translation(translation + self.pivot)
+      * Matrix4::from_axis_angle(axis, Rad(angle))
+      * Matrix4::from_translation(-self.pivot)
+    }
+
+    // `Part,px,py,pz,ax,ay,az,name`, the name goes last so it can contain commas
+    pub(super) fn to_line(&self) -> String {
+        format!("Part,{},{},{},{},{},{},{}", self.pivot.x, self.pivot.y, self.pivot.z,
+                                             self.axis.x, self.axis.y, self.axis.z,
+                                             self.name)
+    }
+
+    pub(super) fn from_line(line: &str) -> Option<Part> {
+        let str_vec: Vec<&str> = line.splitn(8, ',').collect();
+        if str_vec.len() != 8 || str_vec[0] != "Part" {
+            return None;
+        }
+
+        let values: Vec<f32> = str_vec[1..7].iter()
+            .map(|value| value.parse::<f32>().unwrap_or(0.0))
+            .collect();
+
+        Some(Part::new(str_vec[7],
+                       Vector3::new(values[0], values[1], values[2]),
+                       Vector3::new(values[3], values[4], values[5])))
+    }
+}
+
+
+impl PartTrack {
+    pub fn part(&self) -> &str {
+        &self.part
+    }
+
+    pub fn keys(&self) -> &Vec<PartKey> {
+        &self.keys
+    }
+
+    // Keys stay sorted by time, one at the same time is replaced
+    pub fn add_key(&mut self, key: PartKey) {
+        match self.keys.iter().position(|other| other.time >= key.time) {
+            Some(key_index) if self.keys[key_index].time == key.time => self.keys[key_index] = key,
+            Some(key_index) => self.keys.insert(key_index, key),
+            None => self.keys.push(key)
+        }
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.keys.last().map(|key| key.time).unwrap_or(0.0)
+    }
+
+    // Angle and translation at the given time, held outside the keys
+    pub fn sample(&self, time: f32) -> (f32, Vector3<f32>) {
+        if self.keys.is_empty() {
+            return (0.0, Vector3::zero());
+        }
+
+        let next_index = match self.keys.iter().position(|key| key.time > time) {
+            Some(0) => return (self.keys[0].angle, self.keys[0].translation),
+            Some(key_index) => key_index,
+            None => {
+                let last = self.keys[self.keys.len() - 1];
+                return (last.angle, last.translation);
+            }
+        };
+
+        let prev = self.keys[next_index - 1];
+        let next = self.keys[next_index];
+        let fraction = (time - prev.time) / (next.time - prev.time);
+
+        match prev.interpolation {
+            Interpolation::Linear => {
+                (prev.angle + (next.angle - prev.angle) * fraction,
+                 prev.translation.lerp(next.translation, fraction))
+            },
+            Interpolation::Bezier => {
+                let before = self.keys[if next_index > 1 { next_index - 2 } else { next_index - 1 }];
+                let after = self.keys[(next_index + 1).min(self.keys.len() - 1)];
+
+                let angle = bezier(prev.angle,
+                                   prev.angle + (next.angle - before.angle) / 6.0,
+                                   next.angle - (after.angle - prev.angle) / 6.0,
+                                   next.angle,
+                                   fraction);
+                let translation = Vector3::new(
+                    bezier(prev.translation.x,
+                           prev.translation.x + (next.translation.x - before.translation.x) / 6.0,
+                           next.translation.x - (after.translation.x - prev.translation.x) / 6.0,
+                           next.translation.x,
+                           fraction),
+                    bezier(prev.translation.y,
+                           prev.translation.y + (next.translation.y - before.translation.y) / 6.0,
+                           next.translation.y - (after.translation.y - prev.translation.y) / 6.0,
+                           next.translation.y,
+                           fraction),
+                    bezier(prev.translation.z,
+                           prev.translation.z + (next.translation.z - before.translation.z) / 6.0,
+                           next.translation.z - (after.translation.z - prev.translation.z) / 6.0,
+                           next.translation.z,
+                           fraction));
+
+                (angle, translation)
+            }
+        }
+    }
+}
+
+
+impl Clip {
+    pub fn new(name: &str) -> Clip {
+        Clip {
+            name: String::from(name),
+            looping: true,
+            tracks: Vec::new()
+        }
+    }
+
+    pub fn tracks(&self) -> &Vec<PartTrack> {
+        &self.tracks
+    }
+
+    pub fn track(&self, part_name: &str) -> Option<&PartTrack> {
+        self.tracks.iter().find(|track| track.part == part_name)
+    }
+
+    // One track per part, created on the first key
+    pub fn add_key(&mut self, part_name: &str, key: PartKey) {
+        let track_index = match self.tracks.iter().position(|track| track.part == part_name) {
+            Some(track_index) => track_index,
+            None => {
+                self.tracks.push(PartTrack {
+                    part: String::from(part_name),
+                    keys: Vec::new()
+                });
+                self.tracks.len() - 1
+            }
+        };
+
+        self.tracks[track_index].add_key(key);
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.tracks.iter().fold(0.0, |duration, track| duration.max(track.duration()))
+    }
+
+    // -------------------------------------------------------------------------
+    // WRITE TO FILE
+    // -------------------------------------------------------------------------
+    // A header with the name, then a Track line per part followed by its keys
+    // as time,angle,tx,ty,tz,interpolation
+    pub fn write_to_file(&self, path_to_file: &str) {
+        let mut file = File::create(path_to_file).unwrap();
+
+        writeln!(file, "Clip,{},{}", self.looping as u8, self.name).expect("Could not write to file");
+        for track in self.tracks.iter() {
+            writeln!(file, "Track,{}", track.part).expect("Could not write to file");
+            for key in track.keys.iter() {
+                let interpolation = match key.interpolation {
+                    Interpolation::Linear => "L",
+                    Interpolation::Bezier => "B"
+                };
+                writeln!(file, "{},{},{},{},{},{}", key.time,
+                                                   key.angle,
+                                                   key.translation.x,
+                                                   key.translation.y,
+                                                   key.translation.z,
+                                                   interpolation).expect("Could not write to file");
+            }
+        }
+    }
+
+    // -------------------------------------------------------------------------
+    // LOAD FROM FILE
+    // -------------------------------------------------------------------------
+    pub fn load_from_file(path_to_file: &str) -> Option<Clip> {
+        match File::open(path_to_file) {
+            Ok(file) => {
+                let mut clip = Clip::new("");
+
+                let file = BufReader::new(file);
+                for line in file.lines() {
+                    let actual_line = line.unwrap();
+                    let str_vec: Vec<&str> = actual_line.split(',').collect();
+
+                    match str_vec[0] {
+                        "Clip" => {
+                            let str_vec: Vec<&str> = actual_line.splitn(3, ',').collect();
+                            if str_vec.len() == 3 {
+                                clip.looping = str_vec[1] != "0";
+                                clip.name = String::from(str_vec[2]);
+                            }
+                        },
+                        "Track" => {
+                            clip.tracks.push(PartTrack {
+                                part: String::from(&actual_line["Track,".len()..]),
+                                keys: Vec::new()
+                            });
+                        },
+                        _ => {
+                            if str_vec.len() != 6 || clip.tracks.is_empty() {
+                                println!("Wrong clip line: {}", actual_line);
+                                continue;
+                            }
+
+                            let values: Vec<f32> = str_vec[0..5].iter()
+                                .map(|value| value.parse::<f32>().unwrap_or(0.0))
+                                .collect();
+                            let key = PartKey {
+                                time: values[0],
+                                angle: values[1],
+                                translation: Vector3::new(values[2], values[3], values[4]),
+                                interpolation: if str_vec[5] == "B" {
+                                    Interpolation::Bezier
+                                } else {
+                                    Interpolation::Linear
+                                }
+                            };
+
+                            let last_track = clip.tracks.len() - 1;
+                            clip.tracks[last_track].add_key(key);
+                        }
+                    }
+                }
+
+                Some(clip)
+            },
+            Err(_e) => {
+                println!("Could not open {}", path_to_file);
+                None
+            }
+        }
+    }
+}
+
+
+impl AnimationClock {
+    pub fn new() -> AnimationClock {
+        AnimationClock {
+            time: 0.0,
+            speed: 1.0,
+            playing: false
+        }
+    }
+
+    // Looping clips wrap around, the others stop at the end
+    pub fn advance(&mut self, delta_time: f32, clip: &Clip) {
+        if !self.playing {
+            return;
+        }
+
+        self.time += delta_time * self.speed;
+
+        let duration = clip.duration();
+        if self.time > duration {
+            if clip.looping && duration > 0.0 {
+                self.time %= duration;
+            } else {
+                self.time = duration;
+                self.playing = false;
+            }
+        }
+    }
+}
+
+
+// -----------------------------------------------------------------------------
+// MISC FUNCTIONS
+// -----------------------------------------------------------------------------
+// Clips of a model go next to it, models/ship.mdl gets models/ship.<clip>.clip
+pub fn clip_path(model_path: &str, clip_name: &str) -> String {
+    let stem = model_path.trim_end_matches(".mdl");
+    format!("{}.{}.clip", stem, clip_name)
+}
+
+fn bezier(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let s = 1.0 - t;
+    s * s * s * p0 + 3.0 * s * s * t * p1 + 3.0 * s * t * t * p2 + t * t * t * p3
+}
@@ -0,0 +1,847 @@
+extern crate gl;
+use self::gl::types::*;
+
+use cgmath::{Matrix3, Matrix4, Vector2, Vector3, Vector4};
+use cgmath::prelude::*;
+
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::mem;
+use std::os::raw::c_void;
+use std::ptr;
+#[cfg(test)]
+use std::rc::Rc;
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Primitive {
+    Triangles,
+    Lines,
+    Points
+}
+
+// Float attribute of an interleaved vertex, the offset is in floats
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct VertexAttrib {
+    pub location: u32,
+    pub size: i32,
+    pub offset: usize
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Uniform {
+    Int(i32),
+    Float(f32),
+    Vec2(Vector2<f32>),
+    Vec3(Vector3<f32>),
+    Vec4(Vector4<f32>),
+    Matrix3(Matrix3<f32>),
+    Matrix(Matrix4<f32>),
+    FloatArray(Vec<f32>),
+    Vec3Array(Vec<Vector3<f32>>),
+    ColourArray(Vec<(f32, f32, f32, f32)>)
+}
+
+// Log of the stage of a program that failed
+#[derive(Clone, PartialEq, Debug)]
+pub enum ProgramError {
+    Vertex(String),
+    Fragment(String),
+    Link(String)
+}
+
+// Off-screen target. Colour ones have a colour texture and a depth and
+// stencil renderbuffer, depth ones only a depth texture. Missing ones are zero
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Framebuffer {
+    pub id: u32,
+    pub colour_texture: u32,
+    pub depth_texture: u32,
+    pub renderbuffer: u32,
+    pub size: (u32, u32)
+}
+
+// ---- EVERYTHING THE EDITOR ASKS OF THE GPU ----
+// Objects are named by u32 handles like in GL, zero is never a valid one. The
+// framebuffer with id zero is the window
+pub trait RenderBackend {
+    // Buffers and vertex arrays
+    fn create_vertex_array(&mut self) -> u32;
+    fn create_buffer(&mut self) -> u32;
+    fn delete_vertex_array(&mut self, vao: u32);
+    fn delete_buffer(&mut self, buffer: u32);
+    fn buffer_vertices(&mut self, vao: u32, buffer: u32, data: &[f32], stride: u32, attribs: &[VertexAttrib]);
+    fn buffer_indices(&mut self, vao: u32, buffer: u32, data: &[i32]);
+
+    // Programs and their uniforms, uniform locations are -1 when not found
+    fn create_program(&mut self, vertex_source: &str, fragment_source: &str) -> Result<u32, ProgramError>;
+    fn delete_program(&mut self, program: u32);
+    fn use_program(&mut self, program: u32);
+    fn uniform_location(&mut self, program: u32, name: &str) -> i32;
+    // Names and locations of what a linked program uses, arrays go by the
+    // name of the array
+    fn active_uniforms(&mut self, program: u32) -> Vec<(String, i32)>;
+    fn active_attributes(&mut self, program: u32) -> Vec<(String, i32)>;
+    fn set_uniform(&mut self, location: i32, value: &Uniform);
+
+    // Framebuffers and textures
+    fn create_framebuffer(&mut self, size: (u32, u32)) -> Framebuffer;
+    fn create_depth_framebuffer(&mut self, size: (u32, u32)) -> Framebuffer;
+    fn delete_framebuffer(&mut self, framebuffer: &Framebuffer);
+    fn bind_framebuffer(&mut self, framebuffer: u32, size: (u32, u32));
+    // Part of the bound framebuffer drawn to, from the bottom left corner.
+    // Binding a framebuffer resets it to the whole of it
+    fn set_viewport(&mut self, origin: (u32, u32), size: (u32, u32));
+    fn clear(&mut self, colour: (f32, f32, f32, f32));
+    fn bind_texture(&mut self, unit: u32, texture: u32);
+
+    // Fixed function state. Polygon offset is (factor, units) and only moves
+    // faces, blending is the usual alpha blending. Back faces are culled
+    // unless front culling is on
+    fn set_polygon_offset(&mut self, offset: Option<(f32, f32)>);
+    fn set_colour_write(&mut self, enabled: bool);
+    fn set_depth_write(&mut self, enabled: bool);
+    fn set_blending(&mut self, enabled: bool);
+    fn set_front_culling(&mut self, enabled: bool);
+    fn set_depth_test(&mut self, enabled: bool);
+
+    // Draw calls, first and count are in elements of the index buffer for
+    // draw_elements and in vertices for draw_arrays
+    fn draw_elements(&mut self, vao: u32, primitive: Primitive, first: usize, count: usize);
+    fn draw_arrays(&mut self, vao: u32, primitive: Primitive, first: usize, count: usize);
+}
+
+
+// -----------------------------------------------------------------------------
+// CURRENT BACKEND
+// -----------------------------------------------------------------------------
+// GL by default. GL itself is global to the thread so the backend is as well
+thread_local! {
+    static BACKEND: RefCell<Box<dyn RenderBackend>> = RefCell::new(Box::new(GlBackend));
+}
+
+pub fn with_backend<R, F: FnOnce(&mut dyn RenderBackend) -> R>(f: F) -> R {
+    BACKEND.with(|backend| f(backend.borrow_mut().as_mut()))
+}
+
+// Returns the backend that was in use
+#[cfg(test)]
+pub fn set_backend(backend: Box<dyn RenderBackend>) -> Box<dyn RenderBackend> {
+    BACKEND.with(|current| mem::replace(&mut *current.borrow_mut(), backend))
+}
+
+
+// -----------------------------------------------------------------------------
+// OPENGL
+// -----------------------------------------------------------------------------
+pub struct GlBackend;
+
+impl RenderBackend for GlBackend {
+    fn create_vertex_array(&mut self) -> u32 {
+        let mut vao = 0;
+        unsafe { gl::GenVertexArrays(1, &mut vao); }
+        vao
+    }
+
+    fn create_buffer(&mut self) -> u32 {
+        let mut buffer = 0;
+        unsafe { gl::GenBuffers(1, &mut buffer); }
+        buffer
+    }
+
+    fn delete_vertex_array(&mut self, vao: u32) {
+        unsafe { gl::DeleteVertexArrays(1, &vao as *const GLuint); }
+    }
+
+    fn delete_buffer(&mut self, buffer: u32) {
+        unsafe { gl::DeleteBuffers(1, &buffer as *const GLuint); }
+    }
+
+    fn buffer_vertices(&mut self, vao: u32, buffer: u32, data: &[f32], stride: u32, attribs: &[VertexAttrib]) {
+        if data.is_empty() {
+            return;
+        }
+
+        unsafe {
+            gl::BindVertexArray(vao);
+
+            gl::BindBuffer(gl::ARRAY_BUFFER, buffer);
+            gl::BufferData
+               (gl::ARRAY_BUFFER,
+               mem::size_of_val(data) as GLsizeiptr,
+                &data[0] as *const f32 as *const c_void,
+                gl::DYNAMIC_DRAW);
+
+            for attrib in attribs.iter() {
+                gl::VertexAttribPointer(attrib.location, attrib.size, gl::FLOAT, gl::FALSE,
+                    stride as i32 * mem::size_of::<GLfloat>() as GLsizei,
+                   (attrib.offset * mem::size_of::<GLfloat>()) as *const c_void);
+                gl::EnableVertexAttribArray(attrib.location);
+            }
+
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+
+            gl::BindVertexArray(0);
+        }
+    }
+
+    // The element buffer binding is part of the vertex array
+    fn buffer_indices(&mut self, vao: u32, buffer: u32, data: &[i32]) {
+        if data.is_empty() {
+            return;
+        }
+
+        unsafe {
+            gl::BindVertexArray(vao);
+
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, buffer);
+            gl::BufferData(gl::ELEMENT_ARRAY_BUFFER,
+               mem::size_of_val(data) as GLsizeiptr,
+                &data[0] as *const i32 as *const c_void,
+                gl::DYNAMIC_DRAW);
+
+            gl::BindVertexArray(0);
+        }
+    }
+
+    fn create_program(&mut self, vertex_source: &str, fragment_source: &str) -> Result<u32, ProgramError> {
+        unsafe {
+            let vertex_shader = compile_shader(gl::VERTEX_SHADER, vertex_source)
+                .map_err(ProgramError::Vertex)?;
+            let fragment_shader = match compile_shader(gl::FRAGMENT_SHADER, fragment_source) {
+                Ok(fragment_shader) => fragment_shader,
+                Err(log) => {
+                    gl::DeleteShader(vertex_shader);
+                    return Err(ProgramError::Fragment(log));
+                }
+            };
+
+            let program = gl::CreateProgram();
+            gl::AttachShader(program, vertex_shader);
+            gl::AttachShader(program, fragment_shader);
+            gl::LinkProgram(program);
+
+            gl::DeleteShader(vertex_shader);
+            gl::DeleteShader(fragment_shader);
+
+            let mut success = gl::FALSE as GLint;
+            gl::GetProgramiv(program, gl::LINK_STATUS, &mut success);
+            if success != gl::TRUE as GLint {
+                let mut log_length = 0;
+                gl::GetProgramiv(program, gl::INFO_LOG_LENGTH, &mut log_length);
+                let mut info_log = vec![0u8; log_length.max(1) as usize];
+                let mut length = 0;
+                gl::GetProgramInfoLog(program, log_length, &mut length, info_log.as_mut_ptr() as *mut GLchar);
+                gl::DeleteProgram(program);
+                return Err(ProgramError::Link(String::from_utf8_lossy(&info_log[..length as usize]).into_owned()));
+            }
+
+            Ok(program)
+        }
+    }
+
+    fn delete_program(&mut self, program: u32) {
+        unsafe { gl::DeleteProgram(program); }
+    }
+
+    fn use_program(&mut self, program: u32) {
+        unsafe { gl::UseProgram(program); }
+    }
+
+    fn uniform_location(&mut self, program: u32, name: &str) -> i32 {
+        let name = CString::new(name).unwrap();
+        unsafe { gl::GetUniformLocation(program, name.as_ptr()) }
+    }
+
+    fn active_uniforms(&mut self, program: u32) -> Vec<(String, i32)> {
+        let mut uniforms = Vec::new();
+        unsafe {
+            let mut count = 0;
+            gl::GetProgramiv(program, gl::ACTIVE_UNIFORMS, &mut count);
+            for index in 0..count as u32 {
+                let name = active_name(program, index, gl::GetActiveUniform);
+                let location = self.uniform_location(program, &name);
+                uniforms.push((array_name(&name), location));
+            }
+        }
+        uniforms
+    }
+
+    fn active_attributes(&mut self, program: u32) -> Vec<(String, i32)> {
+        let mut attributes = Vec::new();
+        unsafe {
+            let mut count = 0;
+            gl::GetProgramiv(program, gl::ACTIVE_ATTRIBUTES, &mut count);
+            for index in 0..count as u32 {
+                let name = active_name(program, index, gl::GetActiveAttrib);
+                let c_name = CString::new(name.as_str()).unwrap();
+                let location = gl::GetAttribLocation(program, c_name.as_ptr());
+                attributes.push((name, location));
+            }
+        }
+        attributes
+    }
+
+    fn set_uniform(&mut self, location: i32, value: &Uniform) {
+        unsafe {
+            match value {
+                Uniform::Int(value) => gl::Uniform1i(location, *value),
+                Uniform::Float(value) => gl::Uniform1f(location, *value),
+                Uniform::Vec2(vector) => gl::Uniform2f(location, vector.x, vector.y),
+                Uniform::Vec3(vector) => gl::Uniform3f(location, vector.x, vector.y, vector.z),
+                Uniform::Vec4(vector) => gl::Uniform4f(location, vector.x, vector.y, vector.z, vector.w),
+                Uniform::Matrix3(matrix) => gl::UniformMatrix3fv(location, 1, gl::FALSE, matrix.as_ptr()),
+                Uniform::Matrix(matrix) => gl::UniformMatrix4fv(location, 1, gl::FALSE, matrix.as_ptr()),
+                Uniform::FloatArray(values) => {
+                    gl::Uniform1fv(location, values.len() as i32, values.as_ptr())
+                },
+                Uniform::Vec3Array(vectors) => {
+                    let mut values = Vec::<f32>::with_capacity(3 * vectors.len());
+                    for vector in vectors.iter() {
+                        values.extend_from_slice(&[vector.x, vector.y, vector.z]);
+                    }
+                    gl::Uniform3fv(location, vectors.len() as i32, values.as_ptr());
+                },
+                Uniform::ColourArray(colours) => {
+                    let mut values = Vec::<f32>::with_capacity(4 * colours.len());
+                    for colour in colours.iter() {
+                        values.extend_from_slice(&[colour.0, colour.1, colour.2, colour.3]);
+                    }
+                    gl::Uniform4fv(location, colours.len() as i32, values.as_ptr());
+                }
+            }
+        }
+    }
+
+    fn create_framebuffer(&mut self, size: (u32, u32)) -> Framebuffer {
+        let mut framebuffer = Framebuffer {
+            id: 0,
+            colour_texture: 0,
+            depth_texture: 0,
+            renderbuffer: 0,
+            size
+        };
+
+        unsafe {
+            gl::GenFramebuffers(1, &mut framebuffer.id);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer.id);
+
+            gl::GenTextures(1, &mut framebuffer.colour_texture);
+            gl::BindTexture(gl::TEXTURE_2D, framebuffer.colour_texture);
+            gl::TexImage2D
+               (gl::TEXTURE_2D, 0, gl::RGB as i32,
+                size.0 as i32, size.1 as i32, 0,
+                gl::RGB, gl::UNSIGNED_BYTE, ptr::null());
+            gl::TexParameteri
+               (gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri
+               (gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+
+            gl::FramebufferTexture2D
+               (gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D, framebuffer.colour_texture, 0);
+
+            gl::GenRenderbuffers(1, &mut framebuffer.renderbuffer);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, framebuffer.renderbuffer);
+            gl::RenderbufferStorage
+               (gl::RENDERBUFFER, gl::DEPTH24_STENCIL8,
+                size.0 as i32, size.1 as i32);
+            gl::FramebufferRenderbuffer
+               (gl::FRAMEBUFFER, gl::DEPTH_STENCIL_ATTACHMENT,
+                gl::RENDERBUFFER, framebuffer.renderbuffer);
+            if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) !=
+               gl::FRAMEBUFFER_COMPLETE {
+                println!("[ERR] Framebuffer is not complete!");
+            }
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+
+        framebuffer
+    }
+
+    // Depth is read as the red channel, everything outside the texture is as
+    // far as can be
+    fn create_depth_framebuffer(&mut self, size: (u32, u32)) -> Framebuffer {
+        let mut framebuffer = Framebuffer {
+            id: 0,
+            colour_texture: 0,
+            depth_texture: 0,
+            renderbuffer: 0,
+            size
+        };
+
+        unsafe {
+            gl::GenFramebuffers(1, &mut framebuffer.id);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer.id);
+
+            gl::GenTextures(1, &mut framebuffer.depth_texture);
+            gl::BindTexture(gl::TEXTURE_2D, framebuffer.depth_texture);
+            gl::TexImage2D
+               (gl::TEXTURE_2D, 0, gl::DEPTH_COMPONENT24 as i32,
+                size.0 as i32, size.1 as i32, 0,
+                gl::DEPTH_COMPONENT, gl::FLOAT, ptr::null());
+            gl::TexParameteri
+               (gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri
+               (gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri
+               (gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_BORDER as i32);
+            gl::TexParameteri
+               (gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_BORDER as i32);
+            let border: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+            gl::TexParameterfv
+               (gl::TEXTURE_2D, gl::TEXTURE_BORDER_COLOR, border.as_ptr());
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+
+            gl::FramebufferTexture2D
+               (gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT,
+                gl::TEXTURE_2D, framebuffer.depth_texture, 0);
+            gl::DrawBuffer(gl::NONE);
+            gl::ReadBuffer(gl::NONE);
+            if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) !=
+               gl::FRAMEBUFFER_COMPLETE {
+                println!("[ERR] Framebuffer is not complete!");
+            }
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+
+        framebuffer
+    }
+
+    fn delete_framebuffer(&mut self, framebuffer: &Framebuffer) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &framebuffer.id as *const GLuint);
+            gl::DeleteTextures(1, &framebuffer.colour_texture as *const GLuint);
+            gl::DeleteTextures(1, &framebuffer.depth_texture as *const GLuint);
+            gl::DeleteRenderbuffers(1, &framebuffer.renderbuffer as *const GLuint);
+        }
+    }
+
+    fn bind_framebuffer(&mut self, framebuffer: u32, size: (u32, u32)) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+            gl::Viewport(0, 0, size.0 as i32, size.1 as i32);
+        }
+    }
+
+    fn set_viewport(&mut self, origin: (u32, u32), size: (u32, u32)) {
+        unsafe { gl::Viewport(origin.0 as i32, origin.1 as i32, size.0 as i32, size.1 as i32); }
+    }
+
+    fn clear(&mut self, colour: (f32, f32, f32, f32)) {
+        unsafe {
+            gl::ClearColor(colour.0, colour.1, colour.2, colour.3);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+    }
+
+    // Texture zero unbinds
+    fn bind_texture(&mut self, unit: u32, texture: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+        }
+    }
+
+    fn set_polygon_offset(&mut self, offset: Option<(f32, f32)>) {
+        unsafe {
+            match offset {
+                Some((factor, units)) => {
+                    gl::Enable(gl::POLYGON_OFFSET_FILL);
+                    gl::PolygonOffset(factor, units);
+                },
+                None => gl::Disable(gl::POLYGON_OFFSET_FILL)
+            }
+        }
+    }
+
+    fn set_colour_write(&mut self, enabled: bool) {
+        let mask = if enabled { gl::TRUE } else { gl::FALSE };
+        unsafe { gl::ColorMask(mask, mask, mask, mask); }
+    }
+
+    fn set_depth_write(&mut self, enabled: bool) {
+        unsafe { gl::DepthMask(if enabled { gl::TRUE } else { gl::FALSE }); }
+    }
+
+    fn set_blending(&mut self, enabled: bool) {
+        unsafe {
+            if enabled {
+                gl::Enable(gl::BLEND);
+                gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            } else {
+                gl::Disable(gl::BLEND);
+            }
+        }
+    }
+
+    fn set_front_culling(&mut self, enabled: bool) {
+        unsafe { gl::CullFace(if enabled { gl::FRONT } else { gl::BACK }); }
+    }
+
+    fn set_depth_test(&mut self, enabled: bool) {
+        unsafe {
+            if enabled {
+                gl::Enable(gl::DEPTH_TEST);
+            } else {
+                gl::Disable(gl::DEPTH_TEST);
+            }
+        }
+    }
+
+    fn draw_elements(&mut self, vao: u32, primitive: Primitive, first: usize, count: usize) {
+        unsafe {
+            gl::BindVertexArray(vao);
+            gl::DrawElements
+               (gl_primitive(primitive),
+                count as i32, gl::UNSIGNED_INT,
+               (first * mem::size_of::<GLuint>()) as *const c_void);
+            gl::BindVertexArray(0);
+        }
+    }
+
+    fn draw_arrays(&mut self, vao: u32, primitive: Primitive, first: usize, count: usize) {
+        unsafe {
+            gl::BindVertexArray(vao);
+            gl::DrawArrays(gl_primitive(primitive), first as i32, count as i32);
+            gl::BindVertexArray(0);
+        }
+    }
+}
+
+
+// -----------------------------------------------------------------------------
+// RECORDING
+// -----------------------------------------------------------------------------
+// One entry per call with copies of the data, uniforms are logged by name.
+// Only the tests draw through it
+#[cfg(test)]
+#[derive(Clone, PartialEq, Debug)]
+pub enum BackendCall {
+    CreateVertexArray(u32),
+    CreateBuffer(u32),
+    DeleteVertexArray(u32),
+    DeleteBuffer(u32),
+    BufferVertices { vao: u32, buffer: u32, data: Vec<f32>, stride: u32, attribs: Vec<VertexAttrib> },
+    BufferIndices { vao: u32, buffer: u32, data: Vec<i32> },
+    CreateProgram(u32),
+    DeleteProgram(u32),
+    UseProgram(u32),
+    SetUniform { program: u32, name: String, value: Uniform },
+    CreateFramebuffer(Framebuffer),
+    DeleteFramebuffer(Framebuffer),
+    BindFramebuffer(u32, (u32, u32)),
+    SetViewport((u32, u32), (u32, u32)),
+    Clear((f32, f32, f32, f32)),
+    BindTexture(u32, u32),
+    SetPolygonOffset(Option<(f32, f32)>),
+    SetColourWrite(bool),
+    SetDepthWrite(bool),
+    SetBlending(bool),
+    SetFrontCulling(bool),
+    SetDepthTest(bool),
+    DrawElements { vao: u32, primitive: Primitive, first: usize, count: usize },
+    DrawArrays { vao: u32, primitive: Primitive, first: usize, count: usize }
+}
+
+// Needs no GPU. The log is shared so it can still be read once the backend
+// has been handed over with set_backend
+#[cfg(test)]
+pub struct RecordingBackend {
+    calls: Rc<RefCell<Vec<BackendCall>>>,
+    next_handle: u32,
+    uniforms: Vec<(u32, String)>,
+    programs: Vec<RecordedProgram>
+}
+
+// What the sources of a program declare, read from the text instead of a
+// compiler so that nothing is ever optimised out
+#[cfg(test)]
+struct RecordedProgram {
+    id: u32,
+    uniforms: Vec<String>,
+    attributes: Vec<(String, i32)>
+}
+
+#[cfg(test)]
+impl RecordingBackend {
+    pub fn new() -> RecordingBackend {
+        RecordingBackend {
+            calls: Rc::new(RefCell::new(Vec::new())),
+            next_handle: 1,
+            uniforms: Vec::new(),
+            programs: Vec::new()
+        }
+    }
+
+    pub fn calls(&self) -> Rc<RefCell<Vec<BackendCall>>> {
+        self.calls.clone()
+    }
+
+    fn record(&mut self, call: BackendCall) {
+        self.calls.borrow_mut().push(call);
+    }
+
+    fn new_handle(&mut self) -> u32 {
+        self.next_handle += 1;
+        self.next_handle - 1
+    }
+}
+
+#[cfg(test)]
+impl RenderBackend for RecordingBackend {
+    fn create_vertex_array(&mut self) -> u32 {
+        let vao = self.new_handle();
+        self.record(BackendCall::CreateVertexArray(vao));
+        vao
+    }
+
+    fn create_buffer(&mut self) -> u32 {
+        let buffer = self.new_handle();
+        self.record(BackendCall::CreateBuffer(buffer));
+        buffer
+    }
+
+    fn delete_vertex_array(&mut self, vao: u32) {
+        self.record(BackendCall::DeleteVertexArray(vao));
+    }
+
+    fn delete_buffer(&mut self, buffer: u32) {
+        self.record(BackendCall::DeleteBuffer(buffer));
+    }
+
+    fn buffer_vertices(&mut self, vao: u32, buffer: u32, data: &[f32], stride: u32, attribs: &[VertexAttrib]) {
+        self.record(BackendCall::BufferVertices {
+            vao,
+            buffer,
+            data: data.to_vec(),
+            stride,
+            attribs: attribs.to_vec()
+        });
+    }
+
+    fn buffer_indices(&mut self, vao: u32, buffer: u32, data: &[i32]) {
+        self.record(BackendCall::BufferIndices { vao, buffer, data: data.to_vec() });
+    }
+
+    // Sources are not checked
+    fn create_program(&mut self, vertex_source: &str, fragment_source: &str) -> Result<u32, ProgramError> {
+        let program = self.new_handle();
+        self.programs.push(RecordedProgram {
+            id: program,
+            uniforms: declared_uniforms(vertex_source).into_iter()
+                .chain(declared_uniforms(fragment_source))
+                .collect(),
+            attributes: declared_attributes(vertex_source)
+        });
+        self.record(BackendCall::CreateProgram(program));
+        Ok(program)
+    }
+
+    fn delete_program(&mut self, program: u32) {
+        self.record(BackendCall::DeleteProgram(program));
+    }
+
+    fn use_program(&mut self, program: u32) {
+        self.record(BackendCall::UseProgram(program));
+    }
+
+    // Every name asked for gets a location so nothing is missing
+    fn uniform_location(&mut self, program: u32, name: &str) -> i32 {
+        match self.uniforms.iter().position(|uniform| uniform.0 == program && uniform.1 == name) {
+            Some(location) => location as i32,
+            None => {
+                self.uniforms.push((program, String::from(name)));
+                self.uniforms.len() as i32 - 1
+            }
+        }
+    }
+
+    fn active_uniforms(&mut self, program: u32) -> Vec<(String, i32)> {
+        let names = match self.programs.iter().find(|recorded| recorded.id == program) {
+            Some(recorded) => recorded.uniforms.clone(),
+            None => Vec::new()
+        };
+        names.into_iter()
+            .map(|name| {
+                let location = self.uniform_location(program, &name);
+                (name, location)
+            })
+            .collect()
+    }
+
+    fn active_attributes(&mut self, program: u32) -> Vec<(String, i32)> {
+        match self.programs.iter().find(|recorded| recorded.id == program) {
+            Some(recorded) => recorded.attributes.clone(),
+            None => Vec::new()
+        }
+    }
+
+    fn set_uniform(&mut self, location: i32, value: &Uniform) {
+        let (program, name) = match self.uniforms.get(location as usize) {
+            Some(uniform) => uniform.clone(),
+            None => (0, String::new())
+        };
+        self.record(BackendCall::SetUniform { program, name, value: value.clone() });
+    }
+
+    fn create_framebuffer(&mut self, size: (u32, u32)) -> Framebuffer {
+        let framebuffer = Framebuffer {
+            id: self.new_handle(),
+            colour_texture: self.new_handle(),
+            depth_texture: 0,
+            renderbuffer: self.new_handle(),
+            size
+        };
+        self.record(BackendCall::CreateFramebuffer(framebuffer));
+        framebuffer
+    }
+
+    fn create_depth_framebuffer(&mut self, size: (u32, u32)) -> Framebuffer {
+        let framebuffer = Framebuffer {
+            id: self.new_handle(),
+            colour_texture: 0,
+            depth_texture: self.new_handle(),
+            renderbuffer: 0,
+            size
+        };
+        self.record(BackendCall::CreateFramebuffer(framebuffer));
+        framebuffer
+    }
+
+    fn delete_framebuffer(&mut self, framebuffer: &Framebuffer) {
+        self.record(BackendCall::DeleteFramebuffer(*framebuffer));
+    }
+
+    fn bind_framebuffer(&mut self, framebuffer: u32, size: (u32, u32)) {
+        self.record(BackendCall::BindFramebuffer(framebuffer, size));
+    }
+
+    fn set_viewport(&mut self, origin: (u32, u32), size: (u32, u32)) {
+        self.record(BackendCall::SetViewport(origin, size));
+    }
+
+    fn clear(&mut self, colour: (f32, f32, f32, f32)) {
+        self.record(BackendCall::Clear(colour));
+    }
+
+    fn bind_texture(&mut self, unit: u32, texture: u32) {
+        self.record(BackendCall::BindTexture(unit, texture));
+    }
+
+    fn set_polygon_offset(&mut self, offset: Option<(f32, f32)>) {
+        self.record(BackendCall::SetPolygonOffset(offset));
+    }
+
+    fn set_colour_write(&mut self, enabled: bool) {
+        self.record(BackendCall::SetColourWrite(enabled));
+    }
+
+    fn set_depth_write(&mut self, enabled: bool) {
+        self.record(BackendCall::SetDepthWrite(enabled));
+    }
+
+    fn set_blending(&mut self, enabled: bool) {
+        self.record(BackendCall::SetBlending(enabled));
+    }
+
+    fn set_front_culling(&mut self, enabled: bool) {
+        self.record(BackendCall::SetFrontCulling(enabled));
+    }
+
+    fn set_depth_test(&mut self, enabled: bool) {
+        self.record(BackendCall::SetDepthTest(enabled));
+    }
+
+    fn draw_elements(&mut self, vao: u32, primitive: Primitive, first: usize, count: usize) {
+        self.record(BackendCall::DrawElements { vao, primitive, first, count });
+    }
+
+    fn draw_arrays(&mut self, vao: u32, primitive: Primitive, first: usize, count: usize) {
+        self.record(BackendCall::DrawArrays { vao, primitive, first, count });
+    }
+}
+
+
+// -----------------------------------------------------------------------------
+// MISC FUNCTIONS
+// -----------------------------------------------------------------------------
+fn gl_primitive(primitive: Primitive) -> GLenum {
+    match primitive {
+        Primitive::Triangles => gl::TRIANGLES,
+        Primitive::Lines => gl::LINES,
+        Primitive::Points => gl::POINTS
+    }
+}
+
+// `palette[0]` is reported for the whole array
+fn array_name(name: &str) -> String {
+    String::from(name.trim_end_matches("[0]"))
+}
+
+unsafe fn active_name(program: u32, index: u32,
+                      get_active: unsafe fn(GLuint, GLuint, GLsizei, *mut GLsizei, *mut GLint, *mut GLenum, *mut GLchar)) -> String {
+    let mut name = vec![0u8; 256];
+    let mut length = 0;
+    let mut size = 0;
+    let mut kind = 0;
+    get_active(program, index, name.len() as i32, &mut length, &mut size, &mut kind, name.as_mut_ptr() as *mut GLchar);
+    String::from_utf8_lossy(&name[..length as usize]).into_owned()
+}
+
+// `uniform type name;` and `uniform type name[size];` lines
+#[cfg(test)]
+fn declared_uniforms(source: &str) -> Vec<String> {
+    source.lines()
+        .filter_map(|line| {
+            let words: Vec<&str> = line.trim().trim_end_matches(';').split_whitespace().collect();
+            if words.len() == 3 && words[0] == "uniform" {
+                Some(String::from(words[2].split('[').next().unwrap()))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+// `layout (location = n) in type name;` lines
+#[cfg(test)]
+fn declared_attributes(source: &str) -> Vec<(String, i32)> {
+    source.lines()
+        .filter_map(|line| {
+            let line = line.trim().trim_end_matches(';');
+            if !line.starts_with("layout") {
+                return None;
+            }
+            let location = line.split('=').nth(1)?.split(')').next()?.trim().parse::<i32>().ok()?;
+            let words: Vec<&str> = line.split(')').nth(1)?.split_whitespace().collect();
+            if words.len() == 3 && words[0] == "in" {
+                Some((String::from(words[2]), location))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+unsafe fn compile_shader(kind: GLenum, source: &str) -> Result<u32, String> {
+    let shader = gl::CreateShader(kind);
+    let c_str = CString::new(source.as_bytes()).unwrap();
+    gl::ShaderSource(shader, 1, &c_str.as_ptr(), ptr::null());
+    gl::CompileShader(shader);
+
+    let mut success = gl::FALSE as GLint;
+    gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut success);
+    if success != gl::TRUE as GLint {
+        let mut log_length = 0;
+        gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut log_length);
+        let mut info_log = vec![0u8; log_length.max(1) as usize];
+        let mut length = 0;
+        gl::GetShaderInfoLog(shader, log_length, &mut length, info_log.as_mut_ptr() as *mut GLchar);
+        gl::DeleteShader(shader);
+        return Err(String::from_utf8_lossy(&info_log[..length as usize]).into_owned());
+    }
+
+    Ok(shader)
+}
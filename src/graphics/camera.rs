@@ -1,26 +1,21 @@
 extern crate glfw;
 
-extern crate gl;
-use gl::types::*;
-
-use std::ptr;
-use std::mem;
-use std::os::raw::c_void;
-
 use cgmath::{Matrix4, vec3, Deg, Rad, perspective, Point3, Vector3, Vector4};
 use cgmath::prelude::*;
 
 use super::window::*;
+use super::screenshot::read_texture;
+use super::backend::{with_backend, Framebuffer, Primitive, VertexAttrib};
 
 pub struct Camera {
     pos_glob: Point3<f32>,
     att_glob: Vector3<f32>,
     vel_loc: Vector3<f32>,
     rot_glob: Vector3<f32>,
+    fov_deg: f32,
     size: (u32, u32),
     vao: u32,
-    framebuffer: u32,
-    texture_color_buffer: u32,
+    framebuffer: Framebuffer,
     proj_mat: Matrix4<f32>,
     view_mat: Matrix4<f32>,
     pub total_mat: Matrix4<f32>,
@@ -31,103 +26,61 @@ const CAM_ROTATION: f32 = 0.7;
 
 impl Camera {
     pub fn create(fov_deg: f32, render_size: (u32, u32), pos_glob: Point3<f32>, att_glob: Vector3<f32>) -> Camera {
+        // Screen quad and the framebuffer it shows
+        let vao = create_screen_quad();
+        let framebuffer = with_backend(|backend| backend.create_framebuffer(render_size));
+
         // Create empty object
         let mut cam = Camera {
             pos_glob: pos_glob,
             att_glob: att_glob,
             vel_loc: Vector3::zero(),
             rot_glob: Vector3::zero(),
+            fov_deg,
             size: render_size,
-            vao: 0,
-            framebuffer: 0,
-            texture_color_buffer: 0,
+            vao,
+            framebuffer,
             proj_mat: Matrix4::identity(),
             view_mat: Matrix4::identity(),
             total_mat: Matrix4::identity()
         };
 
         // Calculate projection and view matrices
-        cam.proj_mat = cam.proj_mat * perspective(Deg(fov_deg), render_size.0 as f32 / render_size.1 as f32, 0.1, 100.0);
+        let (proj_mat, view_mat) = proj_view_mats(fov_deg, render_size, pos_glob, att_glob);
+        cam.proj_mat = proj_mat;
+        cam.view_mat = view_mat;
 
-        let att_mat: Matrix4<f32> =
-            Matrix4::<f32>::from_angle_z(Rad(att_glob.z))
-          * Matrix4::<f32>::from_angle_y(Rad(att_glob.y))
-          * Matrix4::<f32>::from_angle_x(Rad(att_glob.x));
-        let look_dir = att_mat * Vector4::new(1.0, 0.0, 0.0, 0.0);
-        let cam_target = Point3::new(pos_glob.x + look_dir.x, pos_glob.y + look_dir.y, pos_glob.z + look_dir.z);
+        cam.total_mat = cam.proj_mat * cam.view_mat;
 
-        cam.view_mat = Matrix4::look_at(pos_glob, cam_target, vec3(0.0, 0.0, 1.0));
+        cam
+    }
 
-        cam.total_mat = cam.proj_mat * cam.view_mat;
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
 
-        // Generate VAO for the screen quad
-        unsafe {
-            let mut vbo = 0;
-
-            let vertices: [f32; 24] = [
-                -1.0,  1.0,  0.0, 1.0,
-                -1.0, -1.0,  0.0, 0.0,
-                 1.0, -1.0,  1.0, 0.0,
-    
-                -1.0,  1.0,  0.0, 1.0,
-                 1.0, -1.0,  1.0, 0.0,
-                 1.0,  1.0,  1.0, 1.0
-            ];
-
-            gl::GenVertexArrays(1, &mut cam.vao);
-            gl::GenBuffers(1, &mut vbo);
-
-            gl::BindVertexArray(cam.vao);
-            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-            gl::BufferData
-               (gl::ARRAY_BUFFER,
-                (vertices.len() * mem::size_of::<GLfloat>()) as GLsizeiptr,
-                &vertices[0] as *const f32 as *const c_void,
-                gl::STATIC_DRAW);
-
-            gl::EnableVertexAttribArray(0);
-            let stride = 4 * mem::size_of::<GLfloat>() as GLsizei;
-            gl::VertexAttribPointer
-               (0, 4, gl::FLOAT, gl::FALSE,
-                stride, ptr::null());
-
-            // Generate framebuffer object
-            gl::GenFramebuffers(1, &mut cam.framebuffer);
-            gl::BindFramebuffer(gl::FRAMEBUFFER, cam.framebuffer);
-            
-            gl::GenTextures(1, &mut cam.texture_color_buffer);
-            gl::BindTexture(gl::TEXTURE_2D, cam.texture_color_buffer);
-            gl::TexImage2D
-               (gl::TEXTURE_2D, 0, gl::RGB as i32,
-                render_size.0 as i32, render_size.1 as i32, 0,
-                gl::RGB, gl::UNSIGNED_BYTE, ptr::null());
-            gl::TexParameteri
-               (gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
-            gl::TexParameteri
-               (gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
-            gl::BindTexture(gl::TEXTURE_2D, 0);
-
-            gl::FramebufferTexture2D
-               (gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0,
-                gl::TEXTURE_2D, cam.texture_color_buffer, 0);
-            
-            let mut rbo = 0;
-            gl::GenRenderbuffers(1, &mut rbo);
-            gl::BindRenderbuffer(gl::RENDERBUFFER, rbo);
-            gl::RenderbufferStorage
-               (gl::RENDERBUFFER, gl::DEPTH24_STENCIL8,
-                render_size.0 as i32, render_size.1 as i32);
-            gl::FramebufferRenderbuffer
-               (gl::FRAMEBUFFER, gl::DEPTH_STENCIL_ATTACHMENT,
-                gl::RENDERBUFFER, rbo);
-            if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) !=
-               gl::FRAMEBUFFER_COMPLETE {
-                println!("[ERR] Framebuffer is not complete!");
-            }
-            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+    // New framebuffer and projection for another render size, nothing is done
+    // if the size is the same
+    pub fn resize(&mut self, render_size: (u32, u32)) {
+        if render_size == self.size {
+            return;
         }
 
-        cam
+        with_backend(|backend| {
+            backend.delete_framebuffer(&self.framebuffer);
+            self.framebuffer = backend.create_framebuffer(render_size);
+        });
+
+        self.size = render_size;
+        self.proj_mat = proj_view_mats(self.fov_deg, render_size, self.pos_glob, self.att_glob).0;
+        self.total_mat = self.proj_mat * self.view_mat;
+    }
+
+    // Total matrix of a camera that was never created, for rendering without
+    // a GL context
+    pub fn headless_total_mat(fov_deg: f32, render_size: (u32, u32), pos_glob: Point3<f32>, att_glob: Vector3<f32>) -> Matrix4<f32> {
+        let (proj_mat, view_mat) = proj_view_mats(fov_deg, render_size, pos_glob, att_glob);
+        proj_mat * view_mat
     }
 
     pub fn process_input(&mut self, window: &Window) {
@@ -232,26 +185,183 @@ impl Camera {
         self.total_mat = self.proj_mat * self.view_mat;
     }
 
-    pub fn activate(&self) {
-        unsafe {
-            gl::Viewport(0, 0, self.size.0 as i32, self.size.1 as i32);
-            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+    pub fn position(&self) -> Point3<f32> {
+        self.pos_glob
+    }
+
+    // Unit vector the camera looks along
+    pub fn look_direction(&self) -> Vector3<f32> {
+        look_direction(self.att_glob)
+    }
 
-            gl::ClearColor(0.1, 0.1, 0.1, 0.0);
-            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+    // Diameter in pixels of the render target covered by a sphere, used to
+    // pick levels of detail. Spheres around the camera cover everything
+    pub fn projected_diameter(&self, centre_glob: Vector3<f32>, radius: f32) -> f32 {
+        let depth = -(self.view_mat * centre_glob.extend(1.0)).z;
+        if depth <= radius {
+            return f32::MAX;
         }
+
+        radius * self.proj_mat[1][1] * self.size.1 as f32 / depth
+    }
+
+    pub fn activate(&self) {
+        with_backend(|backend| {
+            backend.bind_framebuffer(self.framebuffer.id, self.size);
+            backend.clear((0.1, 0.1, 0.1, 0.0));
+        });
+    }
+
+    // What the camera drew last, at its own resolution
+    pub fn read_colour_buffer(&self) -> image::RgbaImage {
+        read_texture(self.framebuffer.colour_texture, self.size)
     }
 
     pub fn render(&self) {
-        unsafe {
-            gl::BindVertexArray(self.vao);
-            gl::ActiveTexture(gl::TEXTURE0);
-            gl::BindTexture(gl::TEXTURE_2D, self.texture_color_buffer);
+        with_backend(|backend| {
+            backend.bind_texture(0, self.framebuffer.colour_texture);
+            backend.draw_arrays(self.vao, Primitive::Triangles, 0, 6);
+            backend.bind_texture(0, 0);
+        });
+    }
+}
+
+
+// -----------------------------------------------------------------------------
+// MISC FUNCTIONS
+// -----------------------------------------------------------------------------
+// Two triangles covering the viewport, as (x, y, u, v) for camera.vs
+pub(super) fn create_screen_quad() -> u32 {
+    let vertices: [f32; 24] = [
+        -1.0,  1.0,  0.0, 1.0,
+        -1.0, -1.0,  0.0, 0.0,
+         1.0, -1.0,  1.0, 0.0,
+
+        -1.0,  1.0,  0.0, 1.0,
+         1.0, -1.0,  1.0, 0.0,
+         1.0,  1.0,  1.0, 1.0
+    ];
+
+    with_backend(|backend| {
+        let vao = backend.create_vertex_array();
+        let vbo = backend.create_buffer();
+        backend.buffer_vertices(vao, vbo, &vertices, 4,
+                                &[VertexAttrib { location: 0, size: 4, offset: 0 }]);
+        vao
+    })
+}
+
+fn proj_view_mats(fov_deg: f32, render_size: (u32, u32), pos_glob: Point3<f32>, att_glob: Vector3<f32>) -> (Matrix4<f32>, Matrix4<f32>) {
+    let proj_mat = perspective(Deg(fov_deg), render_size.0 as f32 / render_size.1 as f32, 0.1, 100.0);
+
+    let cam_target = pos_glob + look_direction(att_glob);
+
+    let view_mat = Matrix4::look_at(pos_glob, cam_target, vec3(0.0, 0.0, 1.0));
+
+    (proj_mat, view_mat)
+}
+
+// Attitude angles applied Z * Y * X to the X axis
+fn look_direction(att_glob: Vector3<f32>) -> Vector3<f32> {
+    let att_mat: Matrix4<f32> =
+        Matrix4::<f32>::from_angle_z(Rad(att_glob.z))
+      * Matrix4::<f32>::from_angle_y(Rad(att_glob.y))
+      * Matrix4::<f32>::from_angle_x(Rad(att_glob.x));
+
+    (att_mat * Vector4::new(1.0, 0.0, 0.0, 0.0)).truncate()
+}
+
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::backend::{set_backend, BackendCall, RecordingBackend};
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn record() -> Rc<RefCell<Vec<BackendCall>>> {
+        let backend = RecordingBackend::new();
+        let calls = backend.calls();
+        set_backend(Box::new(backend));
+        calls
+    }
 
-            gl::DrawArrays(gl::TRIANGLES, 0, 6);
+    fn create() -> Camera {
+        Camera::create(60.0, (320, 180), Point3::new(-2.0, 0.0, 0.0), Vector3::zero())
+    }
+
+    #[test]
+    fn create_makes_the_quad_and_the_framebuffer() {
+        let calls = record();
+        let cam = create();
+
+        let calls = calls.borrow();
+        assert_eq!(calls.len(), 4);
+        assert_eq!(calls[0], BackendCall::CreateVertexArray(cam.vao));
+        match &calls[2] {
+            BackendCall::BufferVertices { vao, data, stride, .. } => {
+                assert_eq!(*vao, cam.vao);
+                assert_eq!(data.len(), 24);
+                assert_eq!(*stride, 4);
+            },
+            call => panic!("Expected the quad, got {:?}", call)
+        }
+        assert_eq!(calls[3], BackendCall::CreateFramebuffer(cam.framebuffer));
+        assert_eq!(cam.framebuffer.size, (320, 180));
+        assert_ne!(cam.framebuffer.colour_texture, 0);
+        assert_ne!(cam.framebuffer.renderbuffer, 0);
+    }
+
+    #[test]
+    fn activate_and_render_use_the_framebuffer() {
+        let calls = record();
+        let cam = create();
+        calls.borrow_mut().clear();
+
+        cam.activate();
+        cam.render();
+
+        assert_eq!(*calls.borrow(), vec![
+            BackendCall::BindFramebuffer(cam.framebuffer.id, (320, 180)),
+            BackendCall::Clear((0.1, 0.1, 0.1, 0.0)),
+            BackendCall::BindTexture(0, cam.framebuffer.colour_texture),
+            BackendCall::DrawArrays { vao: cam.vao, primitive: Primitive::Triangles, first: 0, count: 6 },
+            BackendCall::BindTexture(0, 0)
+        ]);
+    }
 
-            gl::BindTexture(gl::TEXTURE_2D, 0);
-            gl::BindVertexArray(0);
+    #[test]
+    fn resize_replaces_the_framebuffer() {
+        let calls = record();
+        let mut cam = create();
+        let old_framebuffer = cam.framebuffer;
+        let old_mat = cam.total_mat;
+        calls.borrow_mut().clear();
+
+        cam.resize((320, 180));
+        assert!(calls.borrow().is_empty());
+
+        cam.resize((160, 180));
+        {
+            let calls = calls.borrow();
+            assert_eq!(calls.len(), 2);
+            // The whole old one goes, renderbuffer included
+            assert_eq!(calls[0], BackendCall::DeleteFramebuffer(old_framebuffer));
+            assert_eq!(calls[1], BackendCall::CreateFramebuffer(cam.framebuffer));
         }
+        assert_eq!(cam.framebuffer.size, (160, 180));
+        assert_ne!(cam.framebuffer.id, old_framebuffer.id);
+        assert_ne!(cam.framebuffer.renderbuffer, old_framebuffer.renderbuffer);
+        assert_eq!(cam.size(), (160, 180));
+        assert_ne!(cam.total_mat, old_mat);
+
+        // Draws go to the new one
+        calls.borrow_mut().clear();
+        cam.activate();
+        assert_eq!(calls.borrow()[0], BackendCall::BindFramebuffer(cam.framebuffer.id, (160, 180)));
     }
 }
@@ -0,0 +1,114 @@
+use cgmath::Vector2;
+
+use super::shader::Shader;
+use super::backend::with_backend;
+
+
+// ---- SIZE OF THE UNIFORM ARRAYS IN THE SHADERS ----
+pub const MAX_CEL_BANDS: usize = 8;
+
+// Light past the threshold is flattened to the level. Below the lowest
+// threshold a surface gets no light at all
+#[derive(Clone, Copy)]
+pub struct CelBand {
+    pub threshold: f32,
+    pub level: f32
+}
+
+// Bands go from the lowest threshold up. Outlines are the back faces pushed
+// out along their normals by the width in pixels, the inverted hull
+pub struct CelShading {
+    bands: Vec<CelBand>,
+    pub outlines: bool,
+    pub outline_width: f32,
+    pub outline_colour: (f32, f32, f32, f32)
+}
+
+// ---- TRON LOOK ----
+// Few bands and glowing cyan edges
+const DEFAULT_BAND_COUNT: usize = 3;
+const DEFAULT_OUTLINE_WIDTH: f32 = 2.0;
+const DEFAULT_OUTLINE_COLOUR: (f32, f32, f32, f32) = (0.0, 0.9, 1.0, 1.0);
+
+
+impl CelShading {
+    pub fn create_default() -> CelShading {
+        let mut cel_shading = CelShading {
+            bands: Vec::new(),
+            outlines: true,
+            outline_width: DEFAULT_OUTLINE_WIDTH,
+            outline_colour: DEFAULT_OUTLINE_COLOUR
+        };
+        cel_shading.set_even_bands(DEFAULT_BAND_COUNT);
+
+        cel_shading
+    }
+
+    pub fn bands(&self) -> &Vec<CelBand> {
+        &self.bands
+    }
+
+    // Bands past the shader array size are refused, they are kept sorted
+    pub fn add_band(&mut self, threshold: f32, level: f32) -> Option<usize> {
+        if self.bands.len() >= MAX_CEL_BANDS {
+            println!("Too many cel bands, the band was not added");
+            return None;
+        }
+
+        let band_index = self.bands.iter()
+            .position(|band| band.threshold > threshold)
+            .unwrap_or(self.bands.len());
+        self.bands.insert(band_index, CelBand { threshold, level });
+
+        Some(band_index)
+    }
+
+    pub fn clear_bands(&mut self) {
+        self.bands = Vec::new();
+    }
+
+    // Thresholds split the light evenly and each band is lit like the top of
+    // its range
+    pub fn set_even_bands(&mut self, band_count: usize) {
+        let band_count = band_count.clamp(1, MAX_CEL_BANDS);
+
+        self.clear_bands();
+        for band_index in 0..band_count {
+            self.add_band(band_index as f32 / band_count as f32,
+                          (band_index + 1) as f32 / band_count as f32);
+        }
+    }
+
+    // -------------------------------------------------------------------------
+    // PASS TO SHADER
+    // -------------------------------------------------------------------------
+    // For the shaders built with CEL_SHADING defined
+    pub fn pass_to_shader(&self, shader: &Shader) {
+        let thresholds: Vec<f32> = self.bands.iter().map(|band| band.threshold).collect();
+        let levels: Vec<f32> = self.bands.iter().map(|band| band.level).collect();
+
+        shader.pass_float_array("celThresholds", &thresholds);
+        shader.pass_float_array("celLevels", &levels);
+        shader.pass_int("celBandCount", self.bands.len() as i32);
+    }
+
+    // The width is in pixels of the target being drawn to
+    pub fn pass_outline_to_shader(&self, shader: &Shader, target_size: (u32, u32)) {
+        shader.pass_float("outlineWidth", self.outline_width);
+        shader.pass_vec2("targetSize", Vector2::new(target_size.0 as f32, target_size.1 as f32));
+        shader.pass_colour("outlineColour", self.outline_colour);
+    }
+
+    // -------------------------------------------------------------------------
+    // STATE AROUND THE OUTLINES
+    // -------------------------------------------------------------------------
+    // Only the back of the hull is drawn so it shows around the model and not
+    // over it
+    pub fn begin_outline(&self) {
+        with_backend(|backend| backend.set_front_culling(true));
+    }
+
+    pub fn end_outline(&self) {
+        with_backend(|backend| backend.set_front_culling(false));
+    }
+}
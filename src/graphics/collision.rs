@@ -0,0 +1,795 @@
+use cgmath::{Matrix4, Vector3};
+use cgmath::prelude::*;
+
+use std::fs::File;
+use std::io::{BufReader, BufRead};
+
+
+// ---- CONSTANTS FOR THE TREE ----
+const MAX_LEAF_TRIANGLES: usize = 4;
+const EPSILON: f32 = 1e-6;
+
+// Box of a node, leaves own a range of the triangles and inner nodes have
+// their two children right after in the list and at `right`
+struct AabbNode {
+    min: Vector3<f32>,
+    max: Vector3<f32>,
+    right: usize,
+    start: usize,
+    count: usize
+}
+
+// Triangles in model space, reordered so every leaf covers a contiguous range.
+// The original face index is kept for reporting hits
+pub struct CollisionMesh {
+    triangles: Vec<[Vector3<f32>; 3]>,
+    face_indices: Vec<usize>,
+    nodes: Vec<AabbNode>
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RayHit {
+    pub distance: f32,
+    pub point: Vector3<f32>,
+    pub normal: Vector3<f32>,
+    pub face: usize
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ClosestPoint {
+    pub point: Vector3<f32>,
+    pub distance: f32,
+    pub face: usize
+}
+
+
+impl AabbNode {
+    fn is_leaf(&self) -> bool {
+        self.count > 0
+    }
+}
+
+
+impl CollisionMesh {
+    // -------------------------------------------------------------------------
+    // CREATION
+    // -------------------------------------------------------------------------
+    pub fn new(triangles: Vec<[Vector3<f32>; 3]>) -> CollisionMesh {
+        let centroids: Vec<Vector3<f32>> = triangles.iter()
+            .map(|triangle| (triangle[0] + triangle[1] + triangle[2]) / 3.0)
+            .collect();
+        let mut order: Vec<usize> = (0..triangles.len()).collect();
+        let mut nodes = Vec::<AabbNode>::new();
+
+        if !triangles.is_empty() {
+            build_node(&triangles, &centroids, &mut order, 0, &mut nodes);
+        }
+
+        CollisionMesh {
+            triangles: order.iter().map(|index| triangles[*index]).collect(),
+            face_indices: order,
+            nodes
+        }
+    }
+
+    // Reads only the vertices and faces of a .mdl, so collision meshes can be
+    // loaded without a GL context
+    pub fn load_from_file(path_to_file: &str) -> Option<CollisionMesh> {
+        match File::open(path_to_file) {
+            Ok(file) => {
+                let mut positions = Vec::<Vector3<f32>>::new();
+                let mut triangles = Vec::<[Vector3<f32>; 3]>::new();
+
+                let file = BufReader::new(file);
+                let mut mode = 0;
+                for line in file.lines() {
+                    let actual_line = line.unwrap();
+                    match actual_line.as_str() {
+                        "Lines" => { mode = 1; continue; },
+                        "Faces" => { mode = 2; continue; },
+                        _ => ()
+                    }
+
+                    let str_vec: Vec<&str> = actual_line.split(',').collect();
+                    match mode {
+                        0 => {
+                            let coords: Vec<f32> = str_vec.iter().take(3)
+                                .filter_map(|value| value.parse::<f32>().ok())
+                                .collect();
+                            if coords.len() == 3 {
+                                positions.push(Vector3::new(coords[0], coords[1], coords[2]));
+                            }
+                        },
+                        2 => {
+                            // Any other section ends the faces
+                            let verts: Vec<usize> = str_vec.iter().take(3)
+                                .filter_map(|value| value.parse::<usize>().ok())
+                                .collect();
+                            if verts.len() != 3 {
+                                break;
+                            }
+                            if verts.iter().all(|vert| *vert < positions.len()) {
+                                triangles.push([positions[verts[0]],
+                                                positions[verts[1]],
+                                                positions[verts[2]]]);
+                            }
+                        },
+                        _ => ()
+                    }
+                }
+
+                Some(CollisionMesh::new(triangles))
+            },
+            Err(_e) => {
+                println!("Could not open {}", path_to_file);
+                None
+            }
+        }
+    }
+
+    pub fn n_triangles(&self) -> usize {
+        self.triangles.len()
+    }
+
+        pub fn bounds(&self) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        self.nodes.first().map(|node| (node.min, node.max))
+    }
+
+    // -------------------------------------------------------------------------
+    // RAY CAST
+    // -------------------------------------------------------------------------
+    // First hit along the ray up to the maximum distance, both sides of the
+    // faces are hit and the normal faces the ray
+    pub fn ray_cast(&self, origin: Vector3<f32>, dir: Vector3<f32>, max_distance: f32) -> Option<RayHit> {
+        if dir.magnitude2() == 0.0 {
+            return None;
+        }
+        let dir = dir.normalize();
+
+        let mut best: Option<RayHit> = None;
+        self.traverse(&mut best, |best, min, max| {
+            let limit = best.map(|hit| hit.distance).unwrap_or(max_distance);
+            ray_box(origin, dir, min, max, 0.0).is_some_and(|distance| distance <= limit)
+        }, |best, triangle, face| {
+            let limit = best.map(|hit| hit.distance).unwrap_or(max_distance);
+            if let Some(distance) = ray_triangle(origin, dir, triangle) {
+                if distance <= limit {
+                    let mut normal = triangle_normal(triangle);
+                    if normal.dot(dir) > 0.0 {
+                        normal = -normal;
+                    }
+                    *best = Some(RayHit {
+                        distance,
+                        point: origin + dir * distance,
+                        normal,
+                        face
+                    });
+                }
+            }
+        });
+
+        best
+    }
+
+    // -------------------------------------------------------------------------
+    // SPHERE CAST
+    // -------------------------------------------------------------------------
+    // The sphere moves from the origin along the direction. The point is where
+    // it touches the mesh and the normal pushes the sphere away from it
+        pub fn sphere_cast(&self,
+                       origin: Vector3<f32>,
+                       dir: Vector3<f32>,
+                       radius: f32,
+                       max_distance: f32) -> Option<RayHit> {
+        if dir.magnitude2() == 0.0 {
+            return None;
+        }
+        let dir = dir.normalize();
+        let margin = Vector3::new(radius, radius, radius);
+
+        let mut best: Option<RayHit> = None;
+        self.traverse(&mut best, |best, min, max| {
+            let limit = best.map(|hit| hit.distance).unwrap_or(max_distance);
+            ray_box(origin, dir, min - margin, max + margin, 0.0)
+                .is_some_and(|distance| distance <= limit)
+        }, |best, triangle, face| {
+            let limit = best.map(|hit| hit.distance).unwrap_or(max_distance);
+            if let Some((distance, point)) = sphere_triangle(origin, dir, radius, triangle) {
+                if distance <= limit {
+                    let centre = origin + dir * distance;
+                    let mut normal = centre - point;
+                    normal = if normal.magnitude2() > 0.0 {
+                        normal.normalize()
+                    } else {
+                        -dir
+                    };
+                    *best = Some(RayHit {
+                        distance,
+                        point,
+                        normal,
+                        face
+                    });
+                }
+            }
+        });
+
+        best
+    }
+
+    // -------------------------------------------------------------------------
+    // CLOSEST POINT
+    // -------------------------------------------------------------------------
+        pub fn closest_point(&self, point: Vector3<f32>) -> Option<ClosestPoint> {
+        let mut best: Option<ClosestPoint> = None;
+        self.traverse(&mut best, |best, min, max| {
+            match best {
+                Some(closest) => box_distance2(point, min, max) <= closest.distance * closest.distance,
+                None => true
+            }
+        }, |best, triangle, face| {
+            let candidate = closest_on_triangle(point, triangle);
+            let distance = (candidate - point).magnitude();
+            if best.is_none_or(|closest| distance < closest.distance) {
+                *best = Some(ClosestPoint {
+                    point: candidate,
+                    distance,
+                    face
+                });
+            }
+        });
+
+        best
+    }
+
+    // -------------------------------------------------------------------------
+    // OVERLAP BETWEEN TWO TRANSFORMED MESHES
+    // -------------------------------------------------------------------------
+    // Pairs of faces, this mesh first, that intersect once both meshes are
+    // placed with their model matrices
+    pub fn overlapping_faces(&self,
+                             model_mat: &Matrix4<f32>,
+                             other: &CollisionMesh,
+                             other_mat: &Matrix4<f32>) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::<(usize, usize)>::new();
+        if self.nodes.is_empty() || other.nodes.is_empty() {
+            return pairs;
+        }
+
+        // Work in the space of this mesh
+        let to_local = match model_mat.invert() {
+            Some(inverse) => inverse * other_mat,
+            None => return pairs
+        };
+        let other_triangles: Vec<[Vector3<f32>; 3]> = other.triangles.iter()
+            .map(|triangle| [transform_point(&to_local, triangle[0]),
+                             transform_point(&to_local, triangle[1]),
+                             transform_point(&to_local, triangle[2])])
+            .collect();
+
+        let mut stack = vec![(0usize, 0usize)];
+        while let Some((node_index, other_index)) = stack.pop() {
+            let node = &self.nodes[node_index];
+            let other_node = &other.nodes[other_index];
+            let (other_min, other_max) = transform_box(&to_local, other_node.min, other_node.max);
+            if !boxes_overlap(node.min, node.max, other_min, other_max) {
+                continue;
+            }
+
+            match (node.is_leaf(), other_node.is_leaf()) {
+                (true, true) => {
+                    for index in node.start..node.start + node.count {
+                        let other_range = other_node.start..other_node.start + other_node.count;
+                        for (other_triangle, other_face) in other_triangles[other_range.clone()].iter()
+                            .zip(other.face_indices[other_range].iter()) {
+                            if triangles_overlap(self.triangles[index], *other_triangle) {
+                                pairs.push((self.face_indices[index], *other_face));
+                            }
+                        }
+                    }
+                },
+                // Descend into the bigger node first
+                (false, true) => {
+                    stack.push((node_index + 1, other_index));
+                    stack.push((node.right, other_index));
+                },
+                (true, false) => {
+                    stack.push((node_index, other_index + 1));
+                    stack.push((node_index, other_node.right));
+                },
+                (false, false) => {
+                    if (node.max - node.min).magnitude2() >= (other_max - other_min).magnitude2() {
+                        stack.push((node_index + 1, other_index));
+                        stack.push((node.right, other_index));
+                    } else {
+                        stack.push((node_index, other_index + 1));
+                        stack.push((node_index, other_node.right));
+                    }
+                }
+            }
+        }
+
+        pairs
+    }
+
+        pub fn overlaps(&self,
+                    model_mat: &Matrix4<f32>,
+                    other: &CollisionMesh,
+                    other_mat: &Matrix4<f32>) -> bool {
+        !self.overlapping_faces(model_mat, other, other_mat).is_empty()
+    }
+
+    // -------------------------------------------------------------------------
+    // TREE TRAVERSAL
+    // -------------------------------------------------------------------------
+    // Visits the triangles of every leaf whose box passes the test, the state
+    // is shared by both so the test can depend on what was found so far
+    fn traverse<S, B, T>(&self, state: &mut S, visit_box: B, mut visit_triangle: T)
+      where B: Fn(&S, Vector3<f32>, Vector3<f32>) -> bool,
+            T: FnMut(&mut S, [Vector3<f32>; 3], usize) {
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let mut stack = vec![0usize];
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if !visit_box(state, node.min, node.max) {
+                continue;
+            }
+
+            if node.is_leaf() {
+                for index in node.start..node.start + node.count {
+                    visit_triangle(state, self.triangles[index], self.face_indices[index]);
+                }
+            } else {
+                stack.push(node.right);
+                stack.push(node_index + 1);
+            }
+        }
+    }
+}
+
+
+// -----------------------------------------------------------------------------
+// TREE BUILDING
+// -----------------------------------------------------------------------------
+// Splits at the median centroid along the longest axis of the node
+fn build_node(triangles: &[[Vector3<f32>; 3]],
+              centroids: &[Vector3<f32>],
+              order: &mut [usize],
+              start: usize,
+              nodes: &mut Vec<AabbNode>) -> usize {
+    let mut min = triangles[order[0]][0];
+    let mut max = min;
+    for index in order.iter() {
+        for corner in triangles[*index].iter() {
+            min = component_min(min, *corner);
+            max = component_max(max, *corner);
+        }
+    }
+
+    let node_index = nodes.len();
+    nodes.push(AabbNode {
+        min,
+        max,
+        right: 0,
+        start,
+        count: order.len()
+    });
+
+    if order.len() <= MAX_LEAF_TRIANGLES {
+        return node_index;
+    }
+
+    let size = max - min;
+    let axis = if size.x >= size.y && size.x >= size.z {
+        0
+    } else if size.y >= size.z {
+        1
+    } else {
+        2
+    };
+    order.sort_by(|a, b| centroids[*a][axis].partial_cmp(&centroids[*b][axis])
+                         .unwrap_or(std::cmp::Ordering::Equal));
+
+    let half = order.len() / 2;
+    let (left, right) = order.split_at_mut(half);
+    build_node(triangles, centroids, left, start, nodes);
+    let right_index = build_node(triangles, centroids, right, start + half, nodes);
+
+    nodes[node_index].right = right_index;
+    nodes[node_index].count = 0;
+
+    node_index
+}
+
+
+// -----------------------------------------------------------------------------
+// MISC FUNCTIONS
+// -----------------------------------------------------------------------------
+fn component_min(a: Vector3<f32>, b: Vector3<f32>) -> Vector3<f32> {
+    Vector3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z))
+}
+
+fn component_max(a: Vector3<f32>, b: Vector3<f32>) -> Vector3<f32> {
+    Vector3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z))
+}
+
+fn transform_point(matrix: &Matrix4<f32>, point: Vector3<f32>) -> Vector3<f32> {
+    (matrix * point.extend(1.0)).truncate()
+}
+
+// Box around the eight transformed corners
+fn transform_box(matrix: &Matrix4<f32>,
+                 min: Vector3<f32>,
+                 max: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+    let mut new_min = transform_point(matrix, min);
+    let mut new_max = new_min;
+    for corner in 1..8 {
+        let point = Vector3::new(if corner & 1 == 0 { min.x } else { max.x },
+                                 if corner & 2 == 0 { min.y } else { max.y },
+                                 if corner & 4 == 0 { min.z } else { max.z });
+        let point = transform_point(matrix, point);
+        new_min = component_min(new_min, point);
+        new_max = component_max(new_max, point);
+    }
+    (new_min, new_max)
+}
+
+fn boxes_overlap(min_a: Vector3<f32>, max_a: Vector3<f32>,
+                 min_b: Vector3<f32>, max_b: Vector3<f32>) -> bool {
+    min_a.x <= max_b.x && max_a.x >= min_b.x &&
+    min_a.y <= max_b.y && max_a.y >= min_b.y &&
+    min_a.z <= max_b.z && max_a.z >= min_b.z
+}
+
+fn box_distance2(point: Vector3<f32>, min: Vector3<f32>, max: Vector3<f32>) -> f32 {
+    let closest = component_max(min, component_min(point, max));
+    (closest - point).magnitude2()
+}
+
+fn triangle_normal(triangle: [Vector3<f32>; 3]) -> Vector3<f32> {
+    let normal = (triangle[1] - triangle[0]).cross(triangle[2] - triangle[0]);
+    if normal.magnitude2() > 0.0 { normal.normalize() } else { normal }
+}
+
+// Distance along the ray to where it enters the box, zero when it starts inside
+fn ray_box(origin: Vector3<f32>,
+           dir: Vector3<f32>,
+           min: Vector3<f32>,
+           max: Vector3<f32>,
+           t_min: f32) -> Option<f32> {
+    let mut t_enter = t_min;
+    let mut t_exit = f32::MAX;
+
+    for axis in 0..3 {
+        if dir[axis].abs() < EPSILON {
+            if origin[axis] < min[axis] || origin[axis] > max[axis] {
+                return None;
+            }
+        } else {
+            let t_a = (min[axis] - origin[axis]) / dir[axis];
+            let t_b = (max[axis] - origin[axis]) / dir[axis];
+            t_enter = t_enter.max(t_a.min(t_b));
+            t_exit = t_exit.min(t_a.max(t_b));
+            if t_enter > t_exit {
+                return None;
+            }
+        }
+    }
+
+    Some(t_enter)
+}
+
+// Moller-Trumbore, both sides
+fn ray_triangle(origin: Vector3<f32>, dir: Vector3<f32>, triangle: [Vector3<f32>; 3]) -> Option<f32> {
+    let edge_1 = triangle[1] - triangle[0];
+    let edge_2 = triangle[2] - triangle[0];
+    let p = dir.cross(edge_2);
+    let det = edge_1.dot(p);
+    if det.abs() < EPSILON {
+        return None;
+    }
+
+    let inv_det = 1.0 / det;
+    let s = origin - triangle[0];
+    let u = s.dot(p) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = s.cross(edge_1);
+    let v = dir.dot(q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = edge_2.dot(q) * inv_det;
+    if t >= 0.0 { Some(t) } else { None }
+}
+
+// First contact of a moving sphere against the face, its edges and corners.
+// Returns the distance travelled and the contact point on the triangle
+fn sphere_triangle(origin: Vector3<f32>,
+                   dir: Vector3<f32>,
+                   radius: f32,
+                   triangle: [Vector3<f32>; 3]) -> Option<(f32, Vector3<f32>)> {
+    // Already touching
+    let closest = closest_on_triangle(origin, triangle);
+    if (closest - origin).magnitude2() <= radius * radius {
+        return Some((0.0, closest));
+    }
+
+    let mut best: Option<(f32, Vector3<f32>)> = None;
+    let mut keep = |distance: f32, point: Vector3<f32>| {
+        if distance >= 0.0 && best.is_none_or(|(best_distance, _)| distance < best_distance) {
+            best = Some((distance, point));
+        }
+    };
+
+    // Face, on whichever side the sphere comes from
+    let normal = triangle_normal(triangle);
+    if normal.magnitude2() > 0.0 {
+        let height = (origin - triangle[0]).dot(normal);
+        let normal = if height < 0.0 { -normal } else { normal };
+        let height = height.abs();
+        let approach = -dir.dot(normal);
+        if approach > EPSILON {
+            let distance = (height - radius) / approach;
+            let contact = origin + dir * distance - normal * radius;
+            if (closest_on_triangle(contact, triangle) - contact).magnitude2() < EPSILON {
+                keep(distance, contact);
+            }
+        }
+    }
+
+    // Edges as cylinders
+    for corner in 0..3 {
+        let start = triangle[corner];
+        let edge = triangle[(corner + 1) % 3] - start;
+        let offset = origin - start;
+
+        let edge_len2 = edge.dot(edge);
+        let offset_edge = offset.dot(edge);
+        let dir_edge = dir.dot(edge);
+
+        let a = edge_len2 * dir.dot(dir) - dir_edge * dir_edge;
+        let b = edge_len2 * offset.dot(dir) - offset_edge * dir_edge;
+        let c = edge_len2 * (offset.dot(offset) - radius * radius) - offset_edge * offset_edge;
+        if a.abs() < EPSILON {
+            continue;
+        }
+
+        let disc = b * b - a * c;
+        if disc < 0.0 {
+            continue;
+        }
+
+        let distance = (-b - disc.sqrt()) / a;
+        let along = (offset_edge + distance * dir_edge) / edge_len2;
+        if (0.0..=1.0).contains(&along) {
+            keep(distance, start + edge * along);
+        }
+    }
+
+    // Corners as spheres
+    for corner in triangle.iter() {
+        let offset = origin - corner;
+        let b = offset.dot(dir);
+        let c = offset.dot(offset) - radius * radius;
+        let disc = b * b - c;
+        if disc >= 0.0 {
+            keep(-b - disc.sqrt(), *corner);
+        }
+    }
+
+    best
+}
+
+// From Real-Time Collision Detection, by regions of the triangle
+fn closest_on_triangle(point: Vector3<f32>, triangle: [Vector3<f32>; 3]) -> Vector3<f32> {
+    let (a, b, c) = (triangle[0], triangle[1], triangle[2]);
+    let ab = b - a;
+    let ac = c - a;
+
+    let ap = point - a;
+    let d1 = ab.dot(ap);
+    let d2 = ac.dot(ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+
+    let bp = point - b;
+    let d3 = ab.dot(bp);
+    let d4 = ac.dot(bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return a + ab * (d1 / (d1 - d3));
+    }
+
+    let cp = point - c;
+    let d5 = ab.dot(cp);
+    let d6 = ac.dot(cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return a + ac * (d2 / (d2 - d6));
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+
+    let denom = 1.0 / (va + vb + vc);
+    a + ab * (vb * denom) + ac * (vc * denom)
+}
+
+// Separating axis test over both normals, the edge cross products and, for
+// coplanar triangles, the in-plane edge normals
+fn triangles_overlap(tri_a: [Vector3<f32>; 3], tri_b: [Vector3<f32>; 3]) -> bool {
+    let edges_a = [tri_a[1] - tri_a[0], tri_a[2] - tri_a[1], tri_a[0] - tri_a[2]];
+    let edges_b = [tri_b[1] - tri_b[0], tri_b[2] - tri_b[1], tri_b[0] - tri_b[2]];
+    let normal_a = edges_a[0].cross(edges_a[1]);
+    let normal_b = edges_b[0].cross(edges_b[1]);
+
+    let mut axes = vec![normal_a, normal_b];
+    for edge_a in edges_a.iter() {
+        for edge_b in edges_b.iter() {
+            axes.push(edge_a.cross(*edge_b));
+        }
+    }
+    for edge in edges_a.iter() {
+        axes.push(normal_a.cross(*edge));
+    }
+    for edge in edges_b.iter() {
+        axes.push(normal_b.cross(*edge));
+    }
+
+    for axis in axes.iter() {
+        if axis.magnitude2() < EPSILON * EPSILON {
+            continue;
+        }
+
+        let project = |triangle: &[Vector3<f32>; 3]| {
+            let values = [axis.dot(triangle[0]), axis.dot(triangle[1]), axis.dot(triangle[2])];
+            (values[0].min(values[1]).min(values[2]), values[0].max(values[1]).max(values[2]))
+        };
+        let (min_a, max_a) = project(&tri_a);
+        let (min_b, max_b) = project(&tri_b);
+        if max_a < min_b || max_b < min_a {
+            return false;
+        }
+    }
+
+    true
+}
+
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOLERANCE: f32 = 1e-4;
+
+    // Squares of side one on the XY plane from the origin to (size, size),
+    // two triangles each so there are enough for a few levels of the tree
+    fn floor(size: usize) -> CollisionMesh {
+        let mut triangles = Vec::new();
+        for y in 0..size {
+            for x in 0..size {
+                let corner = Vector3::new(x as f32, y as f32, 0.0);
+                let (step_x, step_y) = (Vector3::unit_x(), Vector3::unit_y());
+                triangles.push([corner, corner + step_x, corner + step_x + step_y]);
+                triangles.push([corner, corner + step_x + step_y, corner + step_y]);
+            }
+        }
+        CollisionMesh::new(triangles)
+    }
+
+    fn assert_close(a: Vector3<f32>, b: Vector3<f32>) {
+        assert!((a - b).magnitude() < TOLERANCE, "{:?} is not {:?}", a, b);
+    }
+
+    #[test]
+    fn bounds_cover_every_triangle() {
+        let (min, max) = floor(4).bounds().unwrap();
+        assert_close(min, Vector3::new(0.0, 0.0, 0.0));
+        assert_close(max, Vector3::new(4.0, 4.0, 0.0));
+        assert!(CollisionMesh::new(Vec::new()).bounds().is_none());
+    }
+
+    #[test]
+    fn ray_hits_either_side_with_the_normal_facing_it() {
+        let mesh = floor(4);
+
+        let hit = mesh.ray_cast(Vector3::new(1.25, 2.75, 5.0), Vector3::new(0.0, 0.0, -2.0), f32::MAX).unwrap();
+        assert!((hit.distance - 5.0).abs() < TOLERANCE);
+        assert_close(hit.point, Vector3::new(1.25, 2.75, 0.0));
+        assert_close(hit.normal, Vector3::unit_z());
+        assert_eq!(hit.face, 2 * (2 * 4 + 1) + 1);
+
+        let hit = mesh.ray_cast(Vector3::new(1.75, 2.25, -1.0), Vector3::unit_z(), f32::MAX).unwrap();
+        assert_close(hit.normal, -Vector3::unit_z());
+        assert_eq!(hit.face, 2 * (2 * 4 + 1));
+
+        assert!(mesh.ray_cast(Vector3::new(1.5, 1.5, 5.0), -Vector3::unit_z(), 4.0).is_none());
+        assert!(mesh.ray_cast(Vector3::new(5.0, 1.5, 5.0), -Vector3::unit_z(), f32::MAX).is_none());
+        assert!(mesh.ray_cast(Vector3::new(1.5, 1.5, 5.0), Vector3::unit_z(), f32::MAX).is_none());
+    }
+
+    #[test]
+    fn sphere_stops_on_faces_and_edges() {
+        let mesh = floor(4);
+
+        // Falling on the face
+        let hit = mesh.sphere_cast(Vector3::new(1.5, 2.5, 5.0), -Vector3::unit_z(), 0.5, f32::MAX).unwrap();
+        assert!((hit.distance - 4.5).abs() < TOLERANCE);
+        assert_close(hit.point, Vector3::new(1.5, 2.5, 0.0));
+        assert_close(hit.normal, Vector3::unit_z());
+
+        // Rolling into the edge at x = 0 from slightly above the plane
+        let hit = mesh.sphere_cast(Vector3::new(-1.0, 2.5, 0.3), Vector3::unit_x(), 0.5, f32::MAX).unwrap();
+        assert!((hit.distance - 0.6).abs() < TOLERANCE);
+        assert_close(hit.point, Vector3::new(0.0, 2.5, 0.0));
+        assert_close(hit.normal, Vector3::new(-0.8, 0.0, 0.6));
+
+        // Already touching, or passing over
+        let hit = mesh.sphere_cast(Vector3::new(2.0, 2.0, 0.3), Vector3::unit_x(), 0.5, f32::MAX).unwrap();
+        assert_eq!(hit.distance, 0.0);
+        assert!(mesh.sphere_cast(Vector3::new(-1.0, 2.5, 0.6), Vector3::unit_x(), 0.5, f32::MAX).is_none());
+    }
+
+    #[test]
+    fn closest_point_clamps_to_the_mesh() {
+        let mesh = floor(4);
+
+        let closest = mesh.closest_point(Vector3::new(2.25, 1.75, 3.0)).unwrap();
+        assert_close(closest.point, Vector3::new(2.25, 1.75, 0.0));
+        assert!((closest.distance - 3.0).abs() < TOLERANCE);
+        assert_eq!(closest.face / 2, 4 + 2);
+
+        let closest = mesh.closest_point(Vector3::new(5.0, 2.5, 1.0)).unwrap();
+        assert_close(closest.point, Vector3::new(4.0, 2.5, 0.0));
+        assert!((closest.distance - 2.0f32.sqrt()).abs() < TOLERANCE);
+
+        assert!(CollisionMesh::new(Vec::new()).closest_point(Vector3::zero()).is_none());
+    }
+
+    #[test]
+    fn overlap_uses_both_model_matrices() {
+        let mesh = floor(4);
+        let wall = CollisionMesh::new(vec![[Vector3::new(0.0, 0.0, -1.0),
+                                            Vector3::new(0.0, 0.0, 1.0),
+                                            Vector3::new(0.0, 1.0, 1.0)]]);
+        let identity = Matrix4::identity();
+
+        // Standing in the quad at (2, 1), across the diagonal its triangles share
+        let through = Matrix4::from_translation(Vector3::new(2.5, 1.2, 0.0));
+        let mut faces = mesh.overlapping_faces(&identity, &wall, &through);
+        faces.sort();
+        assert_eq!(faces, vec![(2 * (4 + 2), 0), (2 * (4 + 2) + 1, 0)]);
+        assert!(mesh.overlaps(&identity, &wall, &through));
+
+        // Moving the floor away, or the wall out past its edge
+        let lowered = Matrix4::from_translation(Vector3::new(0.0, 0.0, -2.0));
+        assert!(!mesh.overlaps(&lowered, &wall, &through));
+        let outside = Matrix4::from_translation(Vector3::new(4.5, 1.2, 0.0));
+        assert!(!mesh.overlaps(&identity, &wall, &outside));
+
+        // A copy of the floor lying on it
+        assert!(mesh.overlaps(&identity, &mesh, &identity));
+        let above = Matrix4::from_translation(Vector3::new(0.0, 0.0, 0.5));
+        assert!(!mesh.overlaps(&identity, &mesh, &above));
+    }
+}
@@ -0,0 +1,414 @@
+use cgmath::Vector3;
+use cgmath::prelude::*;
+
+use super::model::Model;
+
+
+// ---- VERTEX FOUND AGAIN THROUGH ITS NEIGHBOURS ----
+// Indices are in the numbering of each model
+#[derive(Clone, Copy)]
+pub struct VertMove {
+    pub old: usize,
+    pub new: usize,
+    pub offset: Vector3<f32>
+}
+
+// ---- CHANGES GOING FROM ONE MODEL TO ANOTHER ----
+// Vertices are matched by position first, so renumbering alone is no change.
+// The ones left over are paired up through the vertices they share lines and
+// faces with, which makes them moved instead of removed and added again.
+// Added things use the indices of the new model, removed ones of the old model
+pub struct ModelDiff {
+    vert_map: Vec<Option<usize>>,
+    pub added_verts: Vec<usize>,
+    pub removed_verts: Vec<usize>,
+    pub moved_verts: Vec<VertMove>,
+    pub recoloured_verts: Vec<(usize, usize)>,
+    pub added_lines: Vec<usize>,
+    pub removed_lines: Vec<usize>,
+    pub added_faces: Vec<usize>,
+    pub removed_faces: Vec<usize>,
+    pub flipped_faces: Vec<(usize, usize)>,
+    pub recoloured_faces: Vec<(usize, usize)>,
+    pub added_colours: Vec<String>,
+    pub removed_colours: Vec<String>,
+    pub changed_colours: Vec<String>
+}
+
+
+impl ModelDiff {
+    pub fn between(old: &Model, new: &Model, tolerance: f32) -> ModelDiff {
+        let mut diff = ModelDiff {
+            vert_map: vec![None; old.n_verts()],
+            added_verts: Vec::new(),
+            removed_verts: Vec::new(),
+            moved_verts: Vec::new(),
+            recoloured_verts: Vec::new(),
+            added_lines: Vec::new(),
+            removed_lines: Vec::new(),
+            added_faces: Vec::new(),
+            removed_faces: Vec::new(),
+            flipped_faces: Vec::new(),
+            recoloured_faces: Vec::new(),
+            added_colours: Vec::new(),
+            removed_colours: Vec::new(),
+            changed_colours: Vec::new()
+        };
+
+        diff.match_verts(old, new, tolerance);
+        diff.match_moved_verts(old, new);
+        diff.compare_verts(old, new);
+        diff.compare_lines(old, new);
+        diff.compare_faces(old, new);
+        diff.compare_palettes(old, new);
+
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added_verts.is_empty() && self.removed_verts.is_empty() &&
+        self.moved_verts.is_empty() && self.recoloured_verts.is_empty() &&
+        self.added_lines.is_empty() && self.removed_lines.is_empty() &&
+        self.added_faces.is_empty() && self.removed_faces.is_empty() &&
+        self.flipped_faces.is_empty() && self.recoloured_faces.is_empty() &&
+        self.added_colours.is_empty() && self.removed_colours.is_empty() &&
+        self.changed_colours.is_empty()
+    }
+
+    // -------------------------------------------------------------------------
+    // VERTEX MATCHING
+    // -------------------------------------------------------------------------
+    // Closest pairs first so two vertices close to each other don't steal the
+    // match of the other one
+    fn match_verts(&mut self, old: &Model, new: &Model, tolerance: f32) {
+        let mut pairs = Vec::<(f32, usize, usize)>::new();
+        for old_index in 0..old.n_verts() {
+            for new_index in 0..new.n_verts() {
+                let distance = (new.vert_pos(new_index) - old.vert_pos(old_index)).magnitude();
+                if distance <= tolerance {
+                    pairs.push((distance, old_index, new_index));
+                }
+            }
+        }
+        pairs.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+        let mut new_taken = vec![false; new.n_verts()];
+        for (_distance, old_index, new_index) in pairs {
+            if self.vert_map[old_index].is_none() && !new_taken[new_index] {
+                self.vert_map[old_index] = Some(new_index);
+                new_taken[new_index] = true;
+            }
+        }
+    }
+
+    // A left over old vertex and a left over new one are the same vertex when
+    // they share matched neighbours. Each round can match more neighbours so
+    // this goes on until nothing changes
+    fn match_moved_verts(&mut self, old: &Model, new: &Model) {
+        let old_neighbours = neighbours(old);
+        let new_neighbours = neighbours(new);
+
+        loop {
+            let mut new_taken = vec![false; new.n_verts()];
+            for new_index in self.vert_map.iter().filter_map(|new_index| *new_index) {
+                new_taken[new_index] = true;
+            }
+
+            // Shared neighbours, then distance to break ties
+            let mut best: Option<(usize, f32, usize, usize)> = None;
+            for (old_index, vert_neighbours) in old_neighbours.iter().enumerate() {
+                if self.vert_map[old_index].is_some() {
+                    continue;
+                }
+
+                let mapped: Vec<usize> = vert_neighbours.iter()
+                    .filter_map(|neighbour| self.vert_map[*neighbour])
+                    .collect();
+                if mapped.is_empty() {
+                    continue;
+                }
+
+                for new_index in 0..new.n_verts() {
+                    if new_taken[new_index] {
+                        continue;
+                    }
+
+                    let shared = mapped.iter()
+                        .filter(|neighbour| new_neighbours[new_index].contains(neighbour))
+                        .count();
+                    if shared == 0 {
+                        continue;
+                    }
+
+                    let distance = (new.vert_pos(new_index) - old.vert_pos(old_index)).magnitude();
+                    let better = match best {
+                        Some((best_shared, best_distance, _, _)) => {
+                            shared > best_shared || (shared == best_shared && distance < best_distance)
+                        },
+                        None => true
+                    };
+                    if better {
+                        best = Some((shared, distance, old_index, new_index));
+                    }
+                }
+            }
+
+            match best {
+                Some((_shared, _distance, old_index, new_index)) => {
+                    self.vert_map[old_index] = Some(new_index);
+                    self.moved_verts.push(VertMove {
+                        old: old_index,
+                        new: new_index,
+                        offset: new.vert_pos(new_index) - old.vert_pos(old_index)
+                    });
+                },
+                None => break
+            }
+        }
+
+        self.moved_verts.sort_by_key(|vert_move| vert_move.old);
+    }
+
+    fn compare_verts(&mut self, old: &Model, new: &Model) {
+        let mut new_taken = vec![false; new.n_verts()];
+        for old_index in 0..old.n_verts() {
+            match self.vert_map[old_index] {
+                Some(new_index) => {
+                    new_taken[new_index] = true;
+                    if old.vert_colour(old_index) != new.vert_colour(new_index) {
+                        self.recoloured_verts.push((old_index, new_index));
+                    }
+                },
+                None => self.removed_verts.push(old_index)
+            }
+        }
+
+        self.added_verts = (0..new.n_verts()).filter(|new_index| !new_taken[*new_index]).collect();
+    }
+
+    // -------------------------------------------------------------------------
+    // LINES AND FACES
+    // -------------------------------------------------------------------------
+    // Lines have no direction
+    fn compare_lines(&mut self, old: &Model, new: &Model) {
+        let new_keys: Vec<(usize, usize)> = (0..new.n_lines())
+            .map(|line_index| line_key(new.line_verts(line_index)))
+            .collect();
+        let mut new_found = vec![false; new.n_lines()];
+
+        for line_index in 0..old.n_lines() {
+            let (a, b) = old.line_verts(line_index);
+            let found = match (self.vert_map[a], self.vert_map[b]) {
+                (Some(a), Some(b)) => new_keys.iter().position(|key| *key == line_key((a, b))),
+                _ => None
+            };
+
+            match found {
+                Some(new_line) => new_found[new_line] = true,
+                None => self.removed_lines.push(line_index)
+            }
+        }
+
+        self.added_lines = (0..new.n_lines()).filter(|line_index| !new_found[*line_index]).collect();
+    }
+
+    // The same three vertices wound the other way round is a flipped face
+    fn compare_faces(&mut self, old: &Model, new: &Model) {
+        let new_keys: Vec<(usize, usize, usize)> = (0..new.n_faces())
+            .map(|face_index| face_key(new.face_verts(face_index)))
+            .collect();
+        let mut new_found = vec![false; new.n_faces()];
+
+        for face_index in 0..old.n_faces() {
+            let (a, b, c) = old.face_verts(face_index);
+            let mapped = match (self.vert_map[a], self.vert_map[b], self.vert_map[c]) {
+                (Some(a), Some(b), Some(c)) => Some((a, b, c)),
+                _ => None
+            };
+
+            let (a, b, c) = match mapped {
+                Some(mapped) => mapped,
+                None => {
+                    self.removed_faces.push(face_index);
+                    continue;
+                }
+            };
+
+            let key = face_key((a, b, c));
+            let flipped_key = face_key((a, c, b));
+            if let Some(new_face) = new_keys.iter().position(|new_key| *new_key == key) {
+                new_found[new_face] = true;
+                if colour_name(old, old.face_colour(face_index)) != colour_name(new, new.face_colour(new_face)) {
+                    self.recoloured_faces.push((face_index, new_face));
+                }
+            } else if let Some(new_face) = new_keys.iter().position(|new_key| *new_key == flipped_key) {
+                new_found[new_face] = true;
+                self.flipped_faces.push((face_index, new_face));
+            } else {
+                self.removed_faces.push(face_index);
+            }
+        }
+
+        self.added_faces = (0..new.n_faces()).filter(|face_index| !new_found[*face_index]).collect();
+    }
+
+    // Entries are matched by name since their order doesn't matter
+    fn compare_palettes(&mut self, old: &Model, new: &Model) {
+        for entry in old.palette().entries().iter() {
+            match new.palette().find(&entry.name) {
+                Some(new_entry) => {
+                    if new.palette().entries()[new_entry].colour != entry.colour {
+                        self.changed_colours.push(entry.name.clone());
+                    }
+                },
+                None => self.removed_colours.push(entry.name.clone())
+            }
+        }
+
+        for entry in new.palette().entries().iter() {
+            if old.palette().find(&entry.name).is_none() {
+                self.added_colours.push(entry.name.clone());
+            }
+        }
+    }
+
+    // -------------------------------------------------------------------------
+    // SUMMARY
+    // -------------------------------------------------------------------------
+    // One line per change, vertices are written as positions since the indices
+    // mean nothing outside of their own file
+    pub fn summary(&self, old: &Model, new: &Model) -> Vec<String> {
+        let mut lines = Vec::<String>::new();
+        if self.is_empty() {
+            lines.push(String::from("No differences"));
+            return lines;
+        }
+
+        lines.push(format!("Vertices: {} added, {} removed, {} moved, {} recoloured",
+                           self.added_verts.len(), self.removed_verts.len(),
+                           self.moved_verts.len(), self.recoloured_verts.len()));
+        lines.push(format!("Lines: {} added, {} removed",
+                           self.added_lines.len(), self.removed_lines.len()));
+        lines.push(format!("Faces: {} added, {} removed, {} flipped, {} recoloured",
+                           self.added_faces.len(), self.removed_faces.len(),
+                           self.flipped_faces.len(), self.recoloured_faces.len()));
+
+        for vert_index in self.added_verts.iter() {
+            lines.push(format!("+ vertex {}", pos_string(new.vert_pos(*vert_index))));
+        }
+        for vert_index in self.removed_verts.iter() {
+            lines.push(format!("- vertex {}", pos_string(old.vert_pos(*vert_index))));
+        }
+        for vert_move in self.moved_verts.iter() {
+            lines.push(format!("~ vertex {} -> {} by {}", pos_string(old.vert_pos(vert_move.old)),
+                                                          pos_string(new.vert_pos(vert_move.new)),
+                                                          pos_string(vert_move.offset)));
+        }
+        for (old_index, new_index) in self.recoloured_verts.iter() {
+            lines.push(format!("~ vertex colour {} {:?} -> {:?}", pos_string(new.vert_pos(*new_index)),
+                                                                  old.vert_colour(*old_index),
+                                                                  new.vert_colour(*new_index)));
+        }
+
+        for line_index in self.added_lines.iter() {
+            let (a, b) = new.line_verts(*line_index);
+            lines.push(format!("+ line {} {}", pos_string(new.vert_pos(a)), pos_string(new.vert_pos(b))));
+        }
+        for line_index in self.removed_lines.iter() {
+            let (a, b) = old.line_verts(*line_index);
+            lines.push(format!("- line {} {}", pos_string(old.vert_pos(a)), pos_string(old.vert_pos(b))));
+        }
+
+        for face_index in self.added_faces.iter() {
+            lines.push(format!("+ face {}", face_string(new, *face_index)));
+        }
+        for face_index in self.removed_faces.iter() {
+            lines.push(format!("- face {}", face_string(old, *face_index)));
+        }
+        for (_old_index, new_index) in self.flipped_faces.iter() {
+            lines.push(format!("~ face flipped {}", face_string(new, *new_index)));
+        }
+        for (old_index, new_index) in self.recoloured_faces.iter() {
+            lines.push(format!("~ face colour {} {} -> {}", face_string(new, *new_index),
+                                                            colour_name(old, old.face_colour(*old_index)),
+                                                            colour_name(new, new.face_colour(*new_index))));
+        }
+
+        for name in self.added_colours.iter() {
+            lines.push(format!("+ palette {}", name));
+        }
+        for name in self.removed_colours.iter() {
+            lines.push(format!("- palette {}", name));
+        }
+        for name in self.changed_colours.iter() {
+            lines.push(format!("~ palette {}", name));
+        }
+
+        lines
+    }
+}
+
+
+// -----------------------------------------------------------------------------
+// MISC FUNCTIONS
+// -----------------------------------------------------------------------------
+// Vertices joined to each vertex by a line or a face
+fn neighbours(model: &Model) -> Vec<Vec<usize>> {
+    let mut neighbours = vec![Vec::<usize>::new(); model.n_verts()];
+    let mut join = |a: usize, b: usize| {
+        if !neighbours[a].contains(&b) {
+            neighbours[a].push(b);
+        }
+        if !neighbours[b].contains(&a) {
+            neighbours[b].push(a);
+        }
+    };
+
+    for line_index in 0..model.n_lines() {
+        let (a, b) = model.line_verts(line_index);
+        join(a, b);
+    }
+    for face_index in 0..model.n_faces() {
+        let (a, b, c) = model.face_verts(face_index);
+        join(a, b);
+        join(b, c);
+        join(c, a);
+    }
+
+    neighbours
+}
+
+fn line_key(verts: (usize, usize)) -> (usize, usize) {
+    (verts.0.min(verts.1), verts.0.max(verts.1))
+}
+
+// Rotated so the smallest index goes first, which keeps the winding
+fn face_key(verts: (usize, usize, usize)) -> (usize, usize, usize) {
+    let (a, b, c) = verts;
+    if a <= b && a <= c {
+        (a, b, c)
+    } else if b <= a && b <= c {
+        (b, c, a)
+    } else {
+        (c, a, b)
+    }
+}
+
+// Palette name of a face colour, the raw index if the palette has no entry
+fn colour_name(model: &Model, colour: f32) -> String {
+    match model.palette().entries().get(colour as usize) {
+        Some(entry) => entry.name.clone(),
+        None => format!("#{}", colour)
+    }
+}
+
+fn pos_string(pos: Vector3<f32>) -> String {
+    format!("({}, {}, {})", pos.x, pos.y, pos.z)
+}
+
+fn face_string(model: &Model, face_index: usize) -> String {
+    let (a, b, c) = model.face_verts(face_index);
+    format!("{} {} {}", pos_string(model.vert_pos(a)),
+                        pos_string(model.vert_pos(b)),
+                        pos_string(model.vert_pos(c)))
+}
@@ -0,0 +1,104 @@
+use super::backend::with_backend;
+
+
+// ---- HOW MODELS ARE SHOWN ----
+// Vertex points are drawn in every mode
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DisplayMode {
+    // Faces only
+    Solid,
+    // Every line, including the ones behind faces
+    Wireframe,
+    // Faces with the lines over them
+    SolidWireframe,
+    // Lines hidden by faces are not drawn, the faces themselves are invisible
+    HiddenLine,
+    // See-through faces with every line over them
+    XRay
+}
+
+// Faces go back by this (factor, units) so lines and points on them win the
+// depth test
+const FACE_POLYGON_OFFSET: (f32, f32) = (1.0, 1.0);
+
+// How much of the faces is let through in x-ray
+const XRAY_TRANSPARENCY: f32 = 0.7;
+
+
+impl DisplayMode {
+    // Order of the Tab key
+    pub fn next(self) -> DisplayMode {
+        match self {
+            DisplayMode::Wireframe => DisplayMode::Solid,
+            DisplayMode::Solid => DisplayMode::SolidWireframe,
+            DisplayMode::SolidWireframe => DisplayMode::HiddenLine,
+            DisplayMode::HiddenLine => DisplayMode::XRay,
+            DisplayMode::XRay => DisplayMode::Wireframe
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DisplayMode::Solid => "solid",
+            DisplayMode::Wireframe => "wireframe",
+            DisplayMode::SolidWireframe => "solid and wireframe",
+            DisplayMode::HiddenLine => "hidden line",
+            DisplayMode::XRay => "x-ray"
+        }
+    }
+
+    // Faces are drawn, though maybe only into the depth buffer
+    pub fn draws_faces(self) -> bool {
+        self != DisplayMode::Wireframe
+    }
+
+    // Faces can be seen and so picked
+    pub fn shows_faces(self) -> bool {
+        self.draws_faces() && self != DisplayMode::HiddenLine
+    }
+
+    pub fn draws_lines(self) -> bool {
+        self != DisplayMode::Solid
+    }
+
+    // Value of the transparency uniform of the solid shaders
+    pub fn transparency(self) -> f32 {
+        match self {
+            DisplayMode::XRay => XRAY_TRANSPARENCY,
+            _ => 0.0
+        }
+    }
+
+    // -------------------------------------------------------------------------
+    // STATE AROUND THE FACES
+    // -------------------------------------------------------------------------
+    // Hidden-line only fills the depth buffer, x-ray blends without writing
+    // depth so nothing behind a face is lost
+    pub fn begin_faces(self) {
+        with_backend(|backend| {
+            backend.set_polygon_offset(Some(FACE_POLYGON_OFFSET));
+            match self {
+                DisplayMode::HiddenLine => backend.set_colour_write(false),
+                DisplayMode::XRay => {
+                    backend.set_blending(true);
+                    backend.set_depth_write(false);
+                },
+                _ => ()
+            }
+        });
+    }
+
+    pub fn end_faces(self) {
+        with_backend(|backend| {
+            backend.set_polygon_offset(None);
+            match self {
+                DisplayMode::HiddenLine => backend.set_colour_write(true),
+                DisplayMode::XRay => {
+                    backend.set_blending(false);
+                    backend.set_depth_write(true);
+                },
+                _ => ()
+            }
+        });
+    }
+}
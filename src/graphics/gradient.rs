@@ -0,0 +1,70 @@
+use cgmath::Vector3;
+use cgmath::prelude::*;
+
+
+// ---- WHERE THE GRADIENT GOES FROM START TO END COLOUR ----
+// Positions are in model space. Linear gradients are flat across the planes
+// perpendicular to the segment, radial ones reach the end colour at the radius
+#[derive(Clone, Copy)]
+pub enum GradientShape {
+    Linear(Vector3<f32>, Vector3<f32>),
+    Radial(Vector3<f32>, f32)
+}
+
+#[derive(Clone, Copy)]
+pub struct Gradient {
+    pub shape: GradientShape,
+    pub start_colour: (f32, f32, f32, f32),
+    pub end_colour: (f32, f32, f32, f32)
+}
+
+
+impl Gradient {
+    pub fn linear(from: Vector3<f32>,
+                  to: Vector3<f32>,
+                  start_colour: (f32, f32, f32, f32),
+                  end_colour: (f32, f32, f32, f32)) -> Gradient {
+        Gradient {
+            shape: GradientShape::Linear(from, to),
+            start_colour,
+            end_colour
+        }
+    }
+
+    pub fn radial(centre: Vector3<f32>,
+                  radius: f32,
+                  start_colour: (f32, f32, f32, f32),
+                  end_colour: (f32, f32, f32, f32)) -> Gradient {
+        Gradient {
+            shape: GradientShape::Radial(centre, radius),
+            start_colour,
+            end_colour
+        }
+    }
+
+    // Position along the gradient, clamped to [0, 1]
+    pub fn factor_at(&self, pos: Vector3<f32>) -> f32 {
+        let factor = match self.shape {
+            GradientShape::Linear(from, to) => {
+                let axis = to - from;
+                let length2 = axis.magnitude2();
+                if length2 > 0.0 { (pos - from).dot(axis) / length2 } else { 0.0 }
+            },
+            GradientShape::Radial(centre, radius) => {
+                if radius > 0.0 { (pos - centre).magnitude() / radius } else { 0.0 }
+            }
+        };
+
+        factor.clamp(0.0, 1.0)
+    }
+
+    pub fn colour_at(&self, pos: Vector3<f32>) -> (f32, f32, f32, f32) {
+        let factor = self.factor_at(pos);
+        let mix = |start: f32, end: f32| start + (end - start) * factor;
+
+        (mix(self.start_colour.0, self.end_colour.0),
+         mix(self.start_colour.1, self.end_colour.1),
+         mix(self.start_colour.2, self.end_colour.2),
+         mix(self.start_colour.3, self.end_colour.3))
+    }
+}
@@ -0,0 +1,169 @@
+use super::selection::shift_after_removal;
+
+
+// ---- NAMED PART OF A MODEL ----
+// Every vertex, line and face belongs to one group at most
+pub struct Group {
+    name: String,
+    verts: Vec<usize>,
+    lines: Vec<usize>,
+    faces: Vec<usize>,
+    visible: bool,
+    locked: bool,
+    pub(super) solid_range: (usize, i32),
+    pub(super) wireframe_range: (usize, i32)
+}
+
+
+impl Group {
+    pub fn new(name: &str) -> Group {
+        Group {
+            name: String::from(name),
+            verts: Vec::new(),
+            lines: Vec::new(),
+            faces: Vec::new(),
+            visible: true,
+            locked: false,
+            solid_range: (0, 0),
+            wireframe_range: (0, 0)
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn verts(&self) -> &Vec<usize> {
+        &self.verts
+    }
+
+    pub fn lines(&self) -> &Vec<usize> {
+        &self.lines
+    }
+
+    pub fn faces(&self) -> &Vec<usize> {
+        &self.faces
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    // Index range and length of the group inside the GPU index buffer, as
+    // built by the last update_gpu_data
+    pub fn solid_range(&self) -> (usize, i32) {
+        self.solid_range
+    }
+
+    pub fn wireframe_range(&self) -> (usize, i32) {
+        self.wireframe_range
+    }
+
+    pub(super) fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    pub(super) fn set_locked(&mut self, locked: bool) {
+        self.locked = locked;
+    }
+
+    // -------------------------------------------------------------------------
+    // MEMBERSHIP
+    // -------------------------------------------------------------------------
+    pub(super) fn add(&mut self, verts: &[usize], lines: &[usize], faces: &[usize]) {
+        add_unique(&mut self.verts, verts);
+        add_unique(&mut self.lines, lines);
+        add_unique(&mut self.faces, faces);
+    }
+
+    pub(super) fn remove(&mut self, verts: &[usize], lines: &[usize], faces: &[usize]) {
+        self.verts.retain(|index| !verts.contains(index));
+        self.lines.retain(|index| !lines.contains(index));
+        self.faces.retain(|index| !faces.contains(index));
+    }
+
+    // Drop members that point past the end of the model
+    pub(super) fn clamp(&mut self, n_verts: usize, n_lines: usize, n_faces: usize) {
+        self.verts.retain(|index| *index < n_verts);
+        self.lines.retain(|index| *index < n_lines);
+        self.faces.retain(|index| *index < n_faces);
+    }
+
+    // -------------------------------------------------------------------------
+    // KEEP INDICES VALID WHEN ELEMENTS ARE REMOVED
+    // -------------------------------------------------------------------------
+    pub(super) fn vert_removed(&mut self, vert_index: usize) {
+        shift_after_removal(&mut self.verts, vert_index);
+    }
+
+    pub(super) fn line_removed(&mut self, line_index: usize) {
+        shift_after_removal(&mut self.lines, line_index);
+    }
+
+    pub(super) fn face_removed(&mut self, face_index: usize) {
+        shift_after_removal(&mut self.faces, face_index);
+    }
+
+    // -------------------------------------------------------------------------
+    // FILE FORMAT
+    // -------------------------------------------------------------------------
+    // A header line followed by one line per kind of member, the name goes
+    // last so it can contain commas
+    pub(super) fn to_lines(&self) -> Vec<String> {
+        vec![format!("Group,{},{},{}", self.visible as u8, self.locked as u8, self.name),
+             list_to_line("V", &self.verts),
+             list_to_line("L", &self.lines),
+             list_to_line("F", &self.faces)]
+    }
+
+    pub(super) fn from_header(line: &str) -> Option<Group> {
+        let str_vec: Vec<&str> = line.splitn(4, ',').collect();
+        if str_vec.len() != 4 || str_vec[0] != "Group" {
+            return None;
+        }
+
+        let mut group = Group::new(str_vec[3]);
+        group.visible = str_vec[1] != "0";
+        group.locked = str_vec[2] != "0";
+
+        Some(group)
+    }
+
+    pub(super) fn parse_members(&mut self, line: &str) {
+        let str_vec: Vec<&str> = line.split(',').collect();
+        let indices: Vec<usize> = str_vec[1..].iter()
+            .filter_map(|index| index.parse::<usize>().ok())
+            .collect();
+
+        match str_vec[0] {
+            "V" => add_unique(&mut self.verts, &indices),
+            "L" => add_unique(&mut self.lines, &indices),
+            "F" => add_unique(&mut self.faces, &indices),
+            _ => println!("Unknown group line: {}", line)
+        }
+    }
+}
+
+
+// -----------------------------------------------------------------------------
+// MISC FUNCTIONS
+// -----------------------------------------------------------------------------
+fn add_unique(set: &mut Vec<usize>, indices: &[usize]) {
+    for index in indices.iter() {
+        if !set.contains(index) {
+            set.push(*index);
+        }
+    }
+}
+
+fn list_to_line(tag: &str, indices: &[usize]) -> String {
+    let mut line = String::from(tag);
+    for index in indices.iter() {
+        line.push_str(&format!(",{}", index));
+    }
+    line
+}
@@ -0,0 +1,263 @@
+use cgmath::{Point3, Vector3};
+use cgmath::prelude::*;
+
+use std::fs::File;
+use std::io::{Write, BufReader, BufRead};
+
+use super::shader::Shader;
+
+
+// ---- SIZE OF THE UNIFORM ARRAYS IN THE SHADERS ----
+// The directional array has one more slot for the headlight
+pub const MAX_DIRECTIONAL_LIGHTS: usize = 4;
+pub const MAX_POINT_LIGHTS: usize = 4;
+
+// Colours are RGB and can go past one for brighter lights
+#[derive(Clone, Copy)]
+pub struct DirectionalLight {
+    // Towards the light, doesn't need to be normalised
+    pub direction: Vector3<f32>,
+    pub colour: Vector3<f32>
+}
+
+// The light fades out quadratically until nothing is left at the range
+#[derive(Clone, Copy)]
+pub struct PointLight {
+    pub position: Vector3<f32>,
+    pub colour: Vector3<f32>,
+    pub range: f32
+}
+
+// Blinn-Phong highlight added on top of every light
+#[derive(Clone, Copy)]
+pub struct Specular {
+    pub strength: f32,
+    pub shininess: f32
+}
+
+// The headlight is a directional light coming from the camera, so whatever is
+// looked at is lit whichever the view
+pub struct Lighting {
+    pub ambient: Vector3<f32>,
+    pub directional: Vec<DirectionalLight>,
+    pub points: Vec<PointLight>,
+    pub specular: Option<Specular>,
+    pub headlight: Option<Vector3<f32>>
+}
+
+
+impl Lighting {
+    pub fn create_empty() -> Lighting {
+        Lighting {
+            ambient: Vector3::zero(),
+            directional: Vec::new(),
+            points: Vec::new(),
+            specular: None,
+            headlight: None
+        }
+    }
+
+    // The light the shader used to have hard-coded
+    pub fn create_default() -> Lighting {
+        let mut lighting = Lighting::create_empty();
+
+        lighting.ambient = Vector3::new(0.1, 0.1, 0.1);
+        lighting.add_directional(Vector3::new(1.0, 0.0, 1.0), Vector3::new(1.0, 1.0, 1.0));
+
+        lighting
+    }
+
+    // Lights past the shader array size are refused
+    pub fn add_directional(&mut self, direction: Vector3<f32>, colour: Vector3<f32>) -> Option<usize> {
+        if self.directional.len() >= MAX_DIRECTIONAL_LIGHTS {
+            println!("Too many directional lights, the light was not added");
+            return None;
+        }
+
+        self.directional.push(DirectionalLight { direction, colour });
+        Some(self.directional.len() - 1)
+    }
+
+    pub fn add_point(&mut self, position: Vector3<f32>, colour: Vector3<f32>, range: f32) -> Option<usize> {
+        if self.points.len() >= MAX_POINT_LIGHTS {
+            println!("Too many point lights, the light was not added");
+            return None;
+        }
+
+        self.points.push(PointLight { position, colour, range });
+        Some(self.points.len() - 1)
+    }
+
+    // Directional lights with the headlight last, towards the eye
+    fn directional_with_headlight(&self, eye_dir: Vector3<f32>) -> Vec<DirectionalLight> {
+        let mut directional = self.directional.clone();
+        if let Some(colour) = self.headlight {
+            directional.push(DirectionalLight {
+                direction: -eye_dir,
+                colour
+            });
+        }
+
+        directional
+    }
+
+    // -------------------------------------------------------------------------
+    // PASS TO SHADER
+    // -------------------------------------------------------------------------
+    // The eye is where the camera is and where it looks, for the headlight and
+    // the specular highlights
+    pub fn pass_to_shader(&self, shader: &Shader, eye_pos: Point3<f32>, eye_dir: Vector3<f32>) {
+        let directional = self.directional_with_headlight(eye_dir);
+        let directions: Vec<Vector3<f32>> = directional.iter()
+            .map(|light| normalize_or_zero(light.direction))
+            .collect();
+        let colours: Vec<Vector3<f32>> = directional.iter().map(|light| light.colour).collect();
+
+        shader.pass_vec3("ambientLight", self.ambient);
+        shader.pass_vec3_array("lightDirections", &directions);
+        shader.pass_vec3_array("lightColours", &colours);
+        shader.pass_int("directionalCount", directional.len() as i32);
+
+        let positions: Vec<Vector3<f32>> = self.points.iter().map(|light| light.position).collect();
+        let colours: Vec<Vector3<f32>> = self.points.iter().map(|light| light.colour).collect();
+        let ranges: Vec<f32> = self.points.iter().map(|light| light.range).collect();
+
+        shader.pass_vec3_array("pointPositions", &positions);
+        shader.pass_vec3_array("pointColours", &colours);
+        shader.pass_float_array("pointRanges", &ranges);
+        shader.pass_int("pointCount", self.points.len() as i32);
+
+        let specular = self.specular.unwrap_or(Specular { strength: 0.0, shininess: 1.0 });
+        shader.pass_float("specularStrength", specular.strength);
+        shader.pass_float("shininess", specular.shininess);
+        shader.pass_vec3("eyePos", eye_pos.to_vec());
+    }
+
+    // -------------------------------------------------------------------------
+    // LIGHT ON THE CPU
+    // -------------------------------------------------------------------------
+    // Same as lightColour in the shaders, what a surface colour is multiplied by
+    pub fn light_at(&self, pos: Vector3<f32>, normal: Vector3<f32>, eye_pos: Point3<f32>, eye_dir: Vector3<f32>) -> Vector3<f32> {
+        let normal = normalize_or_zero(normal);
+        let to_eye = normalize_or_zero(eye_pos.to_vec() - pos);
+
+        let mut light = self.ambient;
+        for directional in self.directional_with_headlight(eye_dir).iter() {
+            let to_light = normalize_or_zero(directional.direction);
+            light += self.diffuse_specular(normal, to_light, to_eye) * directional.colour;
+        }
+        for point in self.points.iter() {
+            let offset = point.position - pos;
+            let distance = offset.magnitude();
+            let falloff = (1.0 - distance / point.range).max(0.0);
+            let light_share = self.diffuse_specular(normal, normalize_or_zero(offset), to_eye);
+            light += light_share * falloff * falloff * point.colour;
+        }
+
+        light
+    }
+
+    fn diffuse_specular(&self, normal: Vector3<f32>, to_light: Vector3<f32>, to_eye: Vector3<f32>) -> f32 {
+        let diffuse = normal.dot(to_light).max(0.0);
+        match self.specular {
+            Some(specular) if diffuse > 0.0 => {
+                let halfway = normalize_or_zero(to_light + to_eye);
+                diffuse + specular.strength * normal.dot(halfway).max(0.0).powf(specular.shininess)
+            },
+            _ => diffuse
+        }
+    }
+
+    // -------------------------------------------------------------------------
+    // WRITE TO FILE
+    // -------------------------------------------------------------------------
+    // One line per setting, also used for the lighting section of scenes
+    pub(super) fn to_lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("Ambient,{},{},{}", self.ambient.x, self.ambient.y, self.ambient.z)];
+
+        for light in self.directional.iter() {
+            lines.push(format!("Directional,{},{},{},{},{},{}",
+                               light.direction.x, light.direction.y, light.direction.z,
+                               light.colour.x, light.colour.y, light.colour.z));
+        }
+        for light in self.points.iter() {
+            lines.push(format!("Point,{},{},{},{},{},{},{}",
+                               light.position.x, light.position.y, light.position.z,
+                               light.colour.x, light.colour.y, light.colour.z,
+                               light.range));
+        }
+        if let Some(specular) = self.specular {
+            lines.push(format!("Specular,{},{}", specular.strength, specular.shininess));
+        }
+        if let Some(colour) = self.headlight {
+            lines.push(format!("Headlight,{},{},{}", colour.x, colour.y, colour.z));
+        }
+
+        lines
+    }
+
+    // False for lines that aren't lighting settings
+    pub(super) fn read_line(&mut self, line: &str) -> bool {
+        let str_vec: Vec<&str> = line.split(',').collect();
+        let values: Vec<f32> = match str_vec[1..].iter().map(|value| value.parse::<f32>()).collect() {
+            Ok(values) => values,
+            Err(_e) => return false
+        };
+
+        match (str_vec[0], values.len()) {
+            ("Ambient", 3) => self.ambient = Vector3::new(values[0], values[1], values[2]),
+            ("Directional", 6) => {
+                self.add_directional(Vector3::new(values[0], values[1], values[2]),
+                                     Vector3::new(values[3], values[4], values[5]));
+            },
+            ("Point", 7) => {
+                self.add_point(Vector3::new(values[0], values[1], values[2]),
+                               Vector3::new(values[3], values[4], values[5]),
+                               values[6]);
+            },
+            ("Specular", 2) => self.specular = Some(Specular { strength: values[0], shininess: values[1] }),
+            ("Headlight", 3) => self.headlight = Some(Vector3::new(values[0], values[1], values[2])),
+            _ => return false
+        }
+
+        true
+    }
+
+    pub fn write_to_file(&self, path_to_file: &str) {
+        let mut file = File::create(path_to_file).unwrap();
+
+        for line in self.to_lines() {
+            writeln!(file, "{}", line).expect("Could not write to file");
+        }
+    }
+
+    // -------------------------------------------------------------------------
+    // LOAD FROM FILE
+    // -------------------------------------------------------------------------
+    pub fn load_from_file(path_to_file: &str) -> Option<Lighting> {
+        match File::open(path_to_file) {
+            Ok(file) => {
+                let mut lighting = Lighting::create_empty();
+
+                let file = BufReader::new(file);
+                for line in file.lines() {
+                    let actual_line = line.unwrap();
+                    if !lighting.read_line(&actual_line) {
+                        println!("Wrong lighting line: {}", actual_line);
+                    }
+                }
+
+                Some(lighting)
+            },
+            Err(_e) => None
+        }
+    }
+}
+
+
+// -----------------------------------------------------------------------------
+// MISC FUNCTIONS
+// -----------------------------------------------------------------------------
+fn normalize_or_zero(vector: Vector3<f32>) -> Vector3<f32> {
+    if vector.magnitude() > 0.0 { vector.normalize() } else { vector }
+}
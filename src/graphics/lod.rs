@@ -0,0 +1,197 @@
+// ---- ONE LEVEL OF DETAIL ----
+// The level is used while the projected diameter of the bounding sphere is at
+// least min_size pixels, levels go from the most detailed to the coarsest
+#[derive(Clone, Copy)]
+pub struct LodLevel {
+    pub model: usize,
+    pub min_size: f32
+}
+
+// Hysteresis is the fraction of min_size the projected size has to go past
+// before switching. A fade time of zero switches at once
+pub struct LodSet {
+    pub levels: Vec<LodLevel>,
+    pub hysteresis: f32,
+    pub fade_time: f32
+}
+
+// What an instance of a set is showing, kept per node and camera
+#[derive(Clone, Copy)]
+pub struct LodState {
+    active: usize,
+    previous: Option<usize>,
+    fade: f32
+}
+
+// One model to draw for an instance, faded in or out by the given fraction
+#[derive(Clone, Copy)]
+pub struct LodDraw {
+    pub level: usize,
+    pub model: usize,
+    pub fade: f32,
+    pub fade_in: bool
+}
+
+// ---- COLOURS OF THE DEBUG OVERLAY ----
+// Green for the most detailed level through to red, alpha is the tint amount
+const LOD_TINTS: [(f32, f32, f32, f32); 4] = [(0.2, 0.9, 0.2, 0.5),
+                                              (0.9, 0.9, 0.2, 0.5),
+                                              (0.9, 0.5, 0.1, 0.5),
+                                              (0.9, 0.1, 0.1, 0.5)];
+
+
+impl LodSet {
+    pub fn new() -> LodSet {
+        LodSet {
+            levels: Vec::new(),
+            hysteresis: 0.1,
+            fade_time: 0.0
+        }
+    }
+
+    // Levels are kept sorted from the biggest min_size down
+    pub fn add_level(&mut self, model: usize, min_size: f32) -> usize {
+        let level_index = self.levels.iter()
+            .position(|level| level.min_size < min_size)
+            .unwrap_or(self.levels.len());
+        self.levels.insert(level_index, LodLevel { model, min_size });
+
+        level_index
+    }
+
+    // Level for a projected size in pixels, starting from the current one. A
+    // level is only left once the size is clearly outside of its range
+    pub fn pick(&self, size: f32, current: usize) -> usize {
+        if self.levels.is_empty() {
+            return 0;
+        }
+
+        let mut level_index = current.min(self.levels.len() - 1);
+
+        // Coarser while too small for this level
+        while level_index + 1 < self.levels.len() &&
+              size < self.levels[level_index].min_size * (1.0 - self.hysteresis) {
+            level_index += 1;
+        }
+
+        // Finer while big enough for the previous level
+        while level_index > 0 &&
+              size > self.levels[level_index - 1].min_size * (1.0 + self.hysteresis) {
+            level_index -= 1;
+        }
+
+        level_index
+    }
+
+    // -------------------------------------------------------------------------
+    // TEXT FORMAT
+    // -------------------------------------------------------------------------
+    // `hysteresis,fade_time,model:min_size,...` on a single line
+    pub(super) fn to_line(&self) -> String {
+        let mut line = format!("{},{}", self.hysteresis, self.fade_time);
+        for level in self.levels.iter() {
+            line.push_str(&format!(",{}:{}", level.model, level.min_size));
+        }
+        line
+    }
+
+    pub(super) fn from_line(line: &str) -> Option<LodSet> {
+        let str_vec: Vec<&str> = line.split(',').collect();
+        if str_vec.len() < 2 {
+            return None;
+        }
+
+        let mut lod_set = LodSet::new();
+        lod_set.hysteresis = str_vec[0].parse::<f32>().ok()?;
+        lod_set.fade_time = str_vec[1].parse::<f32>().ok()?;
+
+        for level in str_vec[2..].iter() {
+            let values: Vec<&str> = level.split(':').collect();
+            if values.len() != 2 {
+                return None;
+            }
+            lod_set.add_level(values[0].parse::<usize>().ok()?,
+                              values[1].parse::<f32>().ok()?);
+        }
+
+        Some(lod_set)
+    }
+}
+
+
+impl LodState {
+    pub fn new() -> LodState {
+        LodState {
+            active: 0,
+            previous: None,
+            fade: 1.0
+        }
+    }
+
+    // A switch in the middle of a fade starts a new one from the level that
+    // was fading in
+    pub fn update(&mut self, lod_set: &LodSet, size: f32, delta_time: f32) {
+        let level_index = lod_set.pick(size, self.active);
+        if level_index != self.active {
+            if lod_set.fade_time > 0.0 {
+                self.previous = Some(self.active);
+                self.fade = 0.0;
+            }
+            self.active = level_index;
+        }
+
+        if self.previous.is_some() {
+            self.fade += delta_time / lod_set.fade_time.max(1e-6);
+            if self.fade >= 1.0 {
+                self.fade = 1.0;
+                self.previous = None;
+            }
+        }
+    }
+
+    // The outgoing level dithers away while the new one dithers in, over the
+    // same pixels so the two never overlap
+    pub fn draws(&self, lod_set: &LodSet) -> Vec<LodDraw> {
+        let mut draws = Vec::<LodDraw>::new();
+        if lod_set.levels.is_empty() {
+            return draws;
+        }
+
+        let active = self.active.min(lod_set.levels.len() - 1);
+        match self.previous {
+            Some(previous) if previous < lod_set.levels.len() => {
+                draws.push(LodDraw {
+                    level: previous,
+                    model: lod_set.levels[previous].model,
+                    fade: self.fade,
+                    fade_in: false
+                });
+                draws.push(LodDraw {
+                    level: active,
+                    model: lod_set.levels[active].model,
+                    fade: self.fade,
+                    fade_in: true
+                });
+            },
+            _ => {
+                draws.push(LodDraw {
+                    level: active,
+                    model: lod_set.levels[active].model,
+                    fade: 0.0,
+                    fade_in: false
+                });
+            }
+        }
+
+        draws
+    }
+}
+
+
+// -----------------------------------------------------------------------------
+// MISC FUNCTIONS
+// -----------------------------------------------------------------------------
+// Levels past the last tint stay red
+pub fn lod_tint(level_index: usize) -> (f32, f32, f32, f32) {
+    LOD_TINTS[level_index.min(LOD_TINTS.len() - 1)]
+}
@@ -0,0 +1,433 @@
+use cgmath::{Vector2, Vector3};
+use cgmath::prelude::*;
+
+use super::model::Model;
+use super::primitives::{Mesh, PrimitiveSettings};
+
+
+// -----------------------------------------------------------------------------
+// CROSS SECTIONS
+// -----------------------------------------------------------------------------
+// Sections are given already placed in model space. Curves are sampled with
+// the parameter going from 0 to 1
+pub enum CrossSection {
+    Polyline(Vec<Vector3<f32>>),
+    Curve(Box<dyn Fn(f32) -> Vector3<f32>>)
+}
+
+// Surface through a sequence of sections. U runs along each section and V
+// along the path, faces point towards U x V unless flipped
+pub struct Loft {
+    pub sections: Vec<CrossSection>,
+    pub n_samples: usize,
+    pub close_sections: bool,
+    pub close_path: bool,
+    pub cap_start: bool,
+    pub cap_end: bool,
+    pub flip: bool
+}
+
+// Where the loft ended up inside the model
+pub struct LoftGrid {
+    first_vert: usize,
+    n_u: usize,
+    n_v: usize
+}
+
+
+impl CrossSection {
+    fn sample(&self, n_samples: usize, closed: bool) -> Vec<Vector3<f32>> {
+        match self {
+            CrossSection::Curve(curve) => {
+                (0..n_samples).map(|index| {
+                    if closed {
+                        curve(index as f32 / n_samples as f32)
+                    } else {
+                        curve(index as f32 / (n_samples - 1) as f32)
+                    }
+                }).collect()
+            },
+            CrossSection::Polyline(points) => {
+                if points.len() == n_samples {
+                    points.clone()
+                } else {
+                    resample_polyline(points, n_samples, closed)
+                }
+            }
+        }
+    }
+}
+
+
+impl Loft {
+    pub fn new(n_samples: usize) -> Loft {
+        Loft {
+            sections: Vec::new(),
+            n_samples,
+            close_sections: false,
+            close_path: false,
+            cap_start: false,
+            cap_end: false,
+            flip: false
+        }
+    }
+
+    pub fn add_section(&mut self, section: CrossSection) {
+        self.sections.push(section);
+    }
+
+    // -------------------------------------------------------------------------
+    // SWEEP A 2D PROFILE ALONG A PATH
+    // -------------------------------------------------------------------------
+    // The profile X goes to the left of the path and Y goes towards up
+    pub fn sweep(profile: &[Vector2<f32>], path: &[Vector3<f32>], up: Vector3<f32>) -> Loft {
+        let mut loft = Loft::new(profile.len());
+
+        for (index, point) in path.iter().enumerate() {
+            let prev = path[if index > 0 { index - 1 } else { index }];
+            let next = path[if index + 1 < path.len() { index + 1 } else { index }];
+            let tangent = (next - prev).normalize();
+
+            let mut side = up.cross(tangent);
+            if side.magnitude() < 1e-6 {
+                side = Vector3::unit_x().cross(tangent);
+            }
+            let side = side.normalize();
+            let frame_up = tangent.cross(side);
+
+            loft.add_section(CrossSection::Polyline(
+                profile.iter().map(|coords| point + side * coords.x + frame_up * coords.y).collect()));
+        }
+
+        loft
+    }
+
+    // -------------------------------------------------------------------------
+    // BUILD THE SURFACE
+    // -------------------------------------------------------------------------
+    // Less than two sections make no surface, nothing is added
+    pub fn build(&self, model: &mut Model, settings: PrimitiveSettings) -> LoftGrid {
+        let n_u = self.n_samples.max(2);
+        let n_v = self.sections.len();
+        if n_v < 2 {
+            return LoftGrid { first_vert: model.n_verts(), n_u: 0, n_v: 0 };
+        }
+
+        let mut mesh = Mesh::new();
+
+        // Sample every section with the same number of points
+        for section in self.sections.iter() {
+            for point in section.sample(n_u, self.close_sections) {
+                mesh.add_vert(point, Vector3::zero());
+            }
+        }
+
+        let vert = |u: usize, v: usize| (u % n_u) + (v % n_v) * n_u;
+
+        // Normals from the tangents in both directions
+        for v_index in 0..n_v {
+            for u_index in 0..n_u {
+                let tangent_u = neighbour_diff(&mesh.positions, n_u, u_index, self.close_sections,
+                                               |u| vert(u, v_index));
+                let tangent_v = neighbour_diff(&mesh.positions, n_v, v_index, self.close_path,
+                                               |v| vert(u_index, v));
+                let mut normal = tangent_u.cross(tangent_v);
+                if self.flip {
+                    normal = -normal;
+                }
+                if normal.magnitude() > 0.0 {
+                    normal = normal.normalize();
+                }
+                mesh.normals[vert(u_index, v_index)] = normal;
+            }
+        }
+
+        // Quads between consecutive sections
+        let u_quads = if self.close_sections { n_u } else { n_u - 1 };
+        let v_quads = if self.close_path { n_v } else { n_v - 1 };
+        for v_index in 0..v_quads {
+            for u_index in 0..u_quads {
+                let corners = (vert(u_index, v_index),
+                               vert(u_index + 1, v_index),
+                               vert(u_index + 1, v_index + 1),
+                               vert(u_index, v_index + 1));
+                if self.flip {
+                    mesh.add_quad(corners.0, corners.3, corners.2, corners.1);
+                } else {
+                    mesh.add_quad(corners.0, corners.1, corners.2, corners.3);
+                }
+            }
+        }
+
+        // Caps are fans around the centre of the end sections, facing away
+        // from the rest of the loft
+        if self.close_sections && !self.close_path {
+            if self.cap_start {
+                add_cap(&mut mesh, n_u, 0, 1);
+            }
+            if self.cap_end {
+                add_cap(&mut mesh, n_u, n_v - 1, n_v - 2);
+            }
+        }
+
+        let first_vert = mesh.append_to(model, settings);
+
+        LoftGrid {
+            first_vert,
+            n_u,
+            n_v
+        }
+    }
+}
+
+
+impl LoftGrid {
+    pub fn vert(&self, u_index: usize, v_index: usize) -> usize {
+        self.first_vert + u_index + v_index * self.n_u
+    }
+
+    // Vertices of one section, in U order
+    pub fn section(&self, v_index: usize) -> Vec<usize> {
+        (0..self.n_u).map(|u_index| self.vert(u_index, v_index)).collect()
+    }
+
+    // Vertices along the path at a fixed U, in V order
+    pub fn rail(&self, u_index: usize) -> Vec<usize> {
+        (0..self.n_v).map(|v_index| self.vert(u_index, v_index)).collect()
+    }
+
+    // Outline of an open grid going through increasing U first
+    pub fn boundary(&self) -> Vec<usize> {
+        if self.n_u == 0 || self.n_v == 0 {
+            return Vec::new();
+        }
+
+        let mut boundary = self.section(0);
+        boundary.extend(self.rail(self.n_u - 1).into_iter().skip(1));
+        boundary.extend(self.section(self.n_v - 1).into_iter().rev().skip(1));
+        boundary.extend(self.rail(0).into_iter().rev().skip(1).take(self.n_v.saturating_sub(2)));
+        boundary
+    }
+}
+
+
+// -----------------------------------------------------------------------------
+// MISC FUNCTIONS
+// -----------------------------------------------------------------------------
+fn resample_polyline(points: &[Vector3<f32>], n_samples: usize, closed: bool) -> Vec<Vector3<f32>> {
+    let mut path = points.to_vec();
+    if closed && !points.is_empty() {
+        path.push(points[0]);
+    }
+    if path.len() < 2 {
+        return vec![path.first().cloned().unwrap_or(Vector3::zero()); n_samples];
+    }
+
+    // Accumulated length at each point
+    let mut lengths = vec![0.0];
+    for index in 1..path.len() {
+        let length = lengths[index - 1] + (path[index] - path[index - 1]).magnitude();
+        lengths.push(length);
+    }
+    let total = lengths[lengths.len() - 1];
+
+    let mut samples = Vec::<Vector3<f32>>::new();
+    let mut segment = 1;
+    for sample_index in 0..n_samples {
+        let target = if closed {
+            total * sample_index as f32 / n_samples as f32
+        } else {
+            total * sample_index as f32 / (n_samples - 1) as f32
+        };
+
+        while segment < path.len() - 1 && lengths[segment] < target {
+            segment += 1;
+        }
+
+        let span = lengths[segment] - lengths[segment - 1];
+        let fraction = if span > 0.0 { (target - lengths[segment - 1]) / span } else { 0.0 };
+        samples.push(path[segment - 1].lerp(path[segment], fraction.clamp(0.0, 1.0)));
+    }
+
+    samples
+}
+
+fn neighbour_diff<F>(positions: &[Vector3<f32>],
+                     count: usize,
+                     index: usize,
+                     closed: bool,
+                     vert: F) -> Vector3<f32>
+  where F: Fn(usize) -> usize {
+    if count < 2 {
+        return Vector3::zero();
+    }
+
+    let (prev, next) = if closed {
+        ((index + count - 1) % count, (index + 1) % count)
+    } else {
+        (if index > 0 { index - 1 } else { index },
+         if index + 1 < count { index + 1 } else { index })
+    };
+
+    positions[vert(next)] - positions[vert(prev)]
+}
+
+// Fan from the centre of one section, the winding is chosen so the cap faces
+// away from the neighbouring section
+fn add_cap(mesh: &mut Mesh, n_u: usize, v_index: usize, neighbour_v: usize) {
+    let first = v_index * n_u;
+
+    let mut centre = Vector3::<f32>::zero();
+    let mut neighbour_centre = Vector3::<f32>::zero();
+    for u_index in 0..n_u {
+        centre += mesh.positions[first + u_index];
+        neighbour_centre += mesh.positions[neighbour_v * n_u + u_index];
+    }
+    centre /= n_u as f32;
+    neighbour_centre /= n_u as f32;
+
+    // Area vector of the section in U order
+    let mut area = Vector3::<f32>::zero();
+    for u_index in 0..n_u {
+        let curr = mesh.positions[first + u_index] - centre;
+        let next = mesh.positions[first + (u_index + 1) % n_u] - centre;
+        area += curr.cross(next);
+    }
+
+    let outwards = centre - neighbour_centre;
+    let reverse = area.dot(outwards) < 0.0;
+    let normal = if outwards.magnitude() > 0.0 { outwards.normalize() } else { outwards };
+    let centre_index = mesh.add_vert(centre, normal);
+
+    for u_index in 0..n_u {
+        let curr = first + u_index;
+        let next = first + (u_index + 1) % n_u;
+        if reverse {
+            mesh.add_tri(centre_index, next, curr);
+        } else {
+            mesh.add_tri(centre_index, curr, next);
+        }
+        mesh.add_line(centre_index, curr);
+    }
+}
+
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SETTINGS: PrimitiveSettings = PrimitiveSettings { normals: false, lines: true, colour: 0.0 };
+
+    fn face_normal(model: &Model, face_index: usize) -> Vector3<f32> {
+        let corners = model.face_triangles()[face_index];
+        (corners[1] - corners[0]).cross(corners[2] - corners[0])
+    }
+
+    // Square of side two around the Z axis at some height, counter clockwise
+    // seen from above
+    fn square_at(z: f32) -> CrossSection {
+        CrossSection::Polyline(vec![Vector3::new( 1.0, -1.0, z), Vector3::new( 1.0,  1.0, z),
+                                    Vector3::new(-1.0,  1.0, z), Vector3::new(-1.0, -1.0, z)])
+    }
+
+    #[test]
+    fn fewer_than_two_sections_build_nothing() {
+        let mut model = Model::create_headless();
+        let mut loft = Loft::new(4);
+        loft.add_section(square_at(0.0));
+
+        let grid = loft.build(&mut model, SETTINGS);
+
+        assert_eq!(model.n_verts(), 0);
+        assert_eq!(model.n_faces(), 0);
+        assert!(grid.boundary().is_empty());
+    }
+
+    #[test]
+    fn open_grid_faces_u_cross_v() {
+        // U along X and V along Y, so the faces look up +Z
+        let mut model = Model::create_headless();
+        let mut loft = Loft::new(4);
+        for y in 0..3 {
+            loft.add_section(CrossSection::Curve(Box::new(move |t: f32| Vector3::new(3.0 * t, y as f32, 0.0))));
+        }
+
+        let grid = loft.build(&mut model, SETTINGS);
+
+        assert_eq!(model.n_verts(), 4 * 3);
+        assert_eq!(model.n_faces(), 3 * 2 * 2);
+        for face_index in 0..model.n_faces() {
+            assert!(face_normal(&model, face_index).z > 0.0);
+        }
+
+        // Sections keep the positions they were given, in order
+        assert_eq!(model.vert_pos(grid.vert(0, 0)), Vector3::new(0.0, 0.0, 0.0));
+        assert_eq!(model.vert_pos(grid.vert(3, 0)), Vector3::new(3.0, 0.0, 0.0));
+        assert_eq!(model.vert_pos(grid.vert(1, 2)), Vector3::new(1.0, 2.0, 0.0));
+        assert_eq!(grid.boundary().len(), 2 * (4 + 3) - 4);
+
+        // Flipping turns every face over
+        let mut flipped = Model::create_headless();
+        let mut loft = loft;
+        loft.flip = true;
+        loft.build(&mut flipped, SETTINGS);
+        for face_index in 0..flipped.n_faces() {
+            assert!(face_normal(&flipped, face_index).z < 0.0);
+        }
+    }
+
+    #[test]
+    fn caps_face_away_from_the_loft() {
+        let mut model = Model::create_headless();
+        let mut loft = Loft::new(4);
+        loft.close_sections = true;
+        loft.cap_start = true;
+        loft.cap_end = true;
+        loft.add_section(square_at(0.0));
+        loft.add_section(square_at(2.0));
+
+        loft.build(&mut model, SETTINGS);
+
+        // The sides, then a fan of four around each centre
+        assert_eq!(model.n_verts(), 4 * 2 + 2);
+        assert_eq!(model.n_faces(), 4 * 2 + 4 + 4);
+        for face_index in 0..8 {
+            let normal = face_normal(&model, face_index);
+            let centre = model.face_triangles()[face_index].iter()
+                .fold(Vector3::zero(), |sum, corner| sum + corner) / 3.0;
+            assert!(normal.dot(Vector3::new(centre.x, centre.y, 0.0)) > 0.0);
+        }
+        for face_index in 8..12 {
+            assert!(face_normal(&model, face_index).z < 0.0);
+        }
+        for face_index in 12..16 {
+            assert!(face_normal(&model, face_index).z > 0.0);
+        }
+    }
+
+    #[test]
+    fn polylines_are_resampled_by_length() {
+        let points = vec![Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0), Vector3::new(1.0, 3.0, 0.0)];
+
+        let samples = resample_polyline(&points, 5, false);
+
+        assert_eq!(samples, vec![Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0),
+                                 Vector3::new(1.0, 1.0, 0.0), Vector3::new(1.0, 2.0, 0.0),
+                                 Vector3::new(1.0, 3.0, 0.0)]);
+    }
+
+    #[test]
+    fn closed_polylines_come_back_to_the_start() {
+        let square = vec![Vector3::new(0.0, 0.0, 0.0), Vector3::new(2.0, 0.0, 0.0),
+                          Vector3::new(2.0, 2.0, 0.0), Vector3::new(0.0, 2.0, 0.0)];
+
+        let samples = resample_polyline(&square, 8, true);
+
+        assert_eq!(samples.len(), 8);
+        assert_eq!(samples[1], Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(samples[7], Vector3::new(0.0, 1.0, 0.0));
+    }
+}
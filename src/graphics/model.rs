@@ -1,15 +1,20 @@
-extern crate gl;
-use self::gl::types::*;
-
 use cgmath::{Vector2, Vector3, Matrix4};
 use cgmath::prelude::*;
 
-use std::ptr;
-use std::mem;
-use std::os::raw::c_void;
 use std::fs::File;
 use std::io::{Write, BufReader, BufRead};
 
+use super::selection::*;
+use super::group::Group;
+use super::palette::Palette;
+use super::gradient::Gradient;
+use super::uv_projection::UvProjection;
+use super::morph::Morphs;
+use super::animation::{Part, Clip};
+use super::collision::CollisionMesh;
+use super::diff::ModelDiff;
+use super::backend::{with_backend, Primitive, VertexAttrib};
+
 
 pub struct Model {
     vao: u32,
@@ -22,13 +27,26 @@ pub struct Model {
     solid_length: i32,
     wireframe_index: usize,
     wireframe_length: i32,
-    vert_length: i32
+    vert_length: i32,
+    selection: Selection,
+    groups: Vec<Group>,
+    palette: Palette,
+    texture_path: Option<String>,
+    morphs: Morphs,
+    parts: Vec<Part>,
+    part_poses: Vec<Matrix4<f32>>,
+    collision_path: Option<String>,
+    vertex_data: Vec<f32>,
+    index_data: Vec<i32>
 }
 
+// Pos morph is where the vertex is drawn once the morph targets are blended
 struct Vertex {
     pos_model: Vector3<f32>,
+    pos_morph: Vector3<f32>,
     pos_screen: Vector2<f32>,
     normal_model: Option<Vector3<f32>>,
+    colour: Option<(f32, f32, f32, f32)>,
     indices: Vec<u32>,
     colours: Vec<f32>,
     pushed: bool,
@@ -40,19 +58,55 @@ struct Line {
     verts: (usize, usize)
 }
 
+// UVs are per corner so faces sharing a vertex can map it differently
 struct Face {
     verts: (usize, usize, usize),
-    colour: f32
+    colour: f32,
+    uvs: Option<[Vector2<f32>; 3]>
+}
+
+// ---- SETTINGS FOR BRIDGING TWO LOOPS ----
+// Offset rotates the start of loop B when the loops are closed. Faces are
+// wound so the strip faces away from the centre of both loops unless flipped
+#[derive(Clone, Copy)]
+pub struct BridgeSettings {
+    pub closed: bool,
+    pub offset: usize,
+    pub flip: bool,
+    pub colour: f32
+}
+
+impl Default for BridgeSettings {
+    fn default() -> BridgeSettings {
+        BridgeSettings {
+            closed: true,
+            offset: 0,
+            flip: false,
+            colour: COLOUR_DEFAULT
+        }
+    }
 }
 
 // ---- CONSTANTS FOR COLOURS ----
-const COLOUR_GREY: f32 = 0.0;
-const COLOUR_RED: f32 = 1.0;
-const COLOUR_BLUE: f32 = 2.0;
-const COLOUR_GREEN: f32 = 3.0;
+// Faces store an index into the palette of the model
+const COLOUR_DEFAULT: f32 = 0.0;
 
 // ---- GPU CONSTANTS ----
-const SIZE_VERTEX_F32: u32 = 8;
+// Position, highlight, normal, palette index, RGBA colour, colour weight, UV
+// and the index of the model vertex for blending morph targets on the GPU
+pub(super) const SIZE_VERTEX_F32: u32 = 16;
+
+const VERTEX_ATTRIBS: [VertexAttrib; 8] = [VertexAttrib { location: 0, size: 3, offset: 0 },
+                                           VertexAttrib { location: 1, size: 1, offset: 3 },
+                                           VertexAttrib { location: 2, size: 3, offset: 4 },
+                                           VertexAttrib { location: 3, size: 1, offset: 7 },
+                                           VertexAttrib { location: 4, size: 4, offset: 8 },
+                                           VertexAttrib { location: 5, size: 1, offset: 12 },
+                                           VertexAttrib { location: 6, size: 2, offset: 13 },
+                                           VertexAttrib { location: 7, size: 1, offset: 15 }];
+
+// Element indices going to the GPU, each run tagged with its group if any
+type ElementOrder = Vec<(Option<usize>, Vec<usize>)>;
 
 
 impl Model {
@@ -61,7 +115,22 @@ impl Model {
     // -------------------------------------------------------------------------
     // ---- EMPTY MODEL ----
     pub fn create_empty() -> Model {
-        let mut model = Model {
+        let mut model = Model::create_headless();
+
+        // Create OpenGL variables
+        with_backend(|backend| {
+            model.vao = backend.create_vertex_array();
+            model.vbo = backend.create_buffer();
+            model.ebo = backend.create_buffer();
+        });
+
+        model
+    }
+
+    // ---- EMPTY MODEL WITHOUT GPU OBJECTS ----
+    // For tools running without a GL context, nothing is ever uploaded
+    pub fn create_headless() -> Model {
+        Model {
             vao: 0,
             vbo: 0,
             ebo: 0,
@@ -72,17 +141,18 @@ impl Model {
             solid_length: 0,
             wireframe_index: 0,
             wireframe_length: 0,
-            vert_length: 0
-        };
-
-        // Create OpenGL variables
-        unsafe {
-            gl::GenVertexArrays(1, &mut model.vao);
-            gl::GenBuffers(1, &mut model.vbo);
-            gl::GenBuffers(1, &mut model.ebo);
+            vert_length: 0,
+            selection: Selection::new(),
+            groups: Vec::new(),
+            palette: Palette::create_default(),
+            texture_path: None,
+            morphs: Morphs::new(),
+            parts: Vec::new(),
+            part_poses: Vec::new(),
+            collision_path: None,
+            vertex_data: Vec::new(),
+            index_data: Vec::new()
         }
-
-        model
     }
 
     // ---- DEFAULT CUBE ----
@@ -99,15 +169,25 @@ impl Model {
             solid_length: 0,
             wireframe_index: 0,
             wireframe_length: 0,
-            vert_length: 0
+            vert_length: 0,
+            selection: Selection::new(),
+            groups: Vec::new(),
+            palette: Palette::create_default(),
+            texture_path: None,
+            morphs: Morphs::new(),
+            parts: Vec::new(),
+            part_poses: Vec::new(),
+            collision_path: None,
+            vertex_data: Vec::new(),
+            index_data: Vec::new()
         };
 
         // Create OpenGL variables
-        unsafe {
-            gl::GenVertexArrays(1, &mut model.vao);
-            gl::GenBuffers(1, &mut model.vbo);
-            gl::GenBuffers(1, &mut model.ebo);
-        }
+        with_backend(|backend| {
+            model.vao = backend.create_vertex_array();
+            model.vbo = backend.create_buffer();
+            model.ebo = backend.create_buffer();
+        });
 
         // Push vertices to the model
         model.add_vert(Vector3::new( 0.5,  0.5,  0.5));
@@ -136,51 +216,63 @@ impl Model {
         // Define faces
         model.faces.push(Face {
             verts: (0, 2, 3),
-            colour: COLOUR_GREY
+            colour: COLOUR_DEFAULT,
+            uvs: None
         });
         model.faces.push(Face {
             verts: (0, 3, 1),
-            colour: COLOUR_GREY
+            colour: COLOUR_DEFAULT,
+            uvs: None
         });
         model.faces.push(Face {
             verts: (0, 1, 5),
-            colour: COLOUR_GREY
+            colour: COLOUR_DEFAULT,
+            uvs: None
         });
         model.faces.push(Face {
             verts: (0, 5, 4),
-            colour: COLOUR_GREY
+            colour: COLOUR_DEFAULT,
+            uvs: None
         });
         model.faces.push(Face {
             verts: (0, 4, 6),
-            colour: COLOUR_GREY
+            colour: COLOUR_DEFAULT,
+            uvs: None
         });
         model.faces.push(Face {
             verts: (0, 6, 2),
-            colour: COLOUR_GREY
+            colour: COLOUR_DEFAULT,
+            uvs: None
         });
         model.faces.push(Face {
             verts: (1, 3, 7),
-            colour: COLOUR_GREY
+            colour: COLOUR_DEFAULT,
+            uvs: None
         });
         model.faces.push(Face {
             verts: (1, 7, 5),
-            colour: COLOUR_GREY
+            colour: COLOUR_DEFAULT,
+            uvs: None
         });
         model.faces.push(Face {
             verts: (2, 7, 3),
-            colour: COLOUR_GREY
+            colour: COLOUR_DEFAULT,
+            uvs: None
         });
         model.faces.push(Face {
             verts: (2, 6, 7),
-            colour: COLOUR_GREY
+            colour: COLOUR_DEFAULT,
+            uvs: None
         });
         model.faces.push(Face {
             verts: (4, 5, 7),
-            colour: COLOUR_GREY
+            colour: COLOUR_DEFAULT,
+            uvs: None
         });
         model.faces.push(Face {
             verts: (4, 7, 6),
-            colour: COLOUR_GREY
+            colour: COLOUR_DEFAULT,
+            uvs: None
         });
 
         // Remove wrong faces and lines
@@ -196,15 +288,32 @@ impl Model {
     // CLEAN UP FACES AND LINES
     // -------------------------------------------------------------------------
     pub fn clean(&mut self) {
-        // Check faces first
+        // Check faces first, going backwards so that groups and selection can
+        // follow the removals
         let n_vert = self.vertices.len();
-        self.faces.retain(|face| face.verts.0 < n_vert && 
-                                 face.verts.1 < n_vert &&
-                                 face.verts.2 < n_vert);
+        for face_index in (0..self.faces.len()).rev() {
+            let face = &self.faces[face_index];
+            if face.verts.0 >= n_vert || face.verts.1 >= n_vert ||
+               face.verts.2 >= n_vert {
+                self.faces.remove(face_index);
+                self.face_removed(face_index);
+            }
+        }
 
         // Check lines
-        self.lines.retain(|line| line.verts.0 < n_vert &&
-                                 line.verts.1 < n_vert);
+        for line_index in (0..self.lines.len()).rev() {
+            let line = &self.lines[line_index];
+            if line.verts.0 >= n_vert || line.verts.1 >= n_vert {
+                self.lines.remove(line_index);
+                self.line_removed(line_index);
+            }
+        }
+
+        // Check groups
+        let (n_lines, n_faces) = (self.lines.len(), self.faces.len());
+        for group in self.groups.iter_mut() {
+            group.clamp(n_vert, n_lines, n_faces);
+        }
     }
 
     // -------------------------------------------------------------------------
@@ -215,40 +324,86 @@ impl Model {
         let mut vertices = Vec::<f32>::new();
         let mut indices = Vec::<i32>::new();
 
-        // Clean vertices flag
-        for vert in self.vertices.iter_mut() {
+        // Clean vertices flag and refresh the selected flag from the selection,
+        // morph targets are blended here unless the shader does it
+        let selected_mask = self.selected_vert_mask();
+        let cpu_morph = self.morphs.is_active() && !self.morphs.uses_gpu();
+        for (index, vert) in self.vertices.iter_mut().enumerate() {
             vert.indices = Vec::new();
             vert.colours = Vec::new();
             vert.pushed = false;
+            vert.selected = selected_mask[index];
+            vert.pos_morph = if cpu_morph {
+                vert.pos_model + self.morphs.pos_offset(index)
+            } else {
+                vert.pos_model
+            };
+        }
+
+        if self.morphs.uses_gpu() && self.vao != 0 {
+            self.morphs.upload(self.vertices.len());
+        }
+
+        // Animated parts move rigidly on top of the morphs
+        for (part, pose) in self.parts.iter().zip(self.part_poses.iter()) {
+            if let Some(group_index) = self.find_group(&part.name) {
+                for vert_index in self.groups[group_index].verts().iter() {
+                    let vertex = &mut self.vertices[*vert_index];
+                    vertex.pos_morph = (pose * vertex.pos_morph.extend(1.0)).truncate();
+                }
+            }
         }
 
+        // Faces and lines are pushed group by group so that every group ends up
+        // in a contiguous range, ungrouped ones go first
+        let (face_order, line_order) = self.element_order();
+        let hidden_verts = self.hidden_vert_mask();
+
         // ---- PROCESS FACES ----
         self.solid_index = 0;
 
-        for face in self.faces.iter_mut() {
-            // Calculate face normal
-            let vec1 = self.vertices[face.verts.1].pos_model -
-                       self.vertices[face.verts.0].pos_model;
-            let vec2 = self.vertices[face.verts.2].pos_model -
-                       self.vertices[face.verts.0].pos_model;
-            let normal = vec1.cross(vec2).normalize();
-
-            // Process each of the vertices
-            process_vertex_flat(&self.vertices[face.verts.0],
-                           face.colour,
-                           normal,
-                           &mut vertices,
-                           &mut indices);
-            process_vertex_flat(&self.vertices[face.verts.1],
-                           face.colour,
-                           normal,
-                           &mut vertices,
-                           &mut indices);
-            process_vertex_flat(&self.vertices[face.verts.2],
-                           face.colour,
-                           normal,
-                           &mut vertices,
-                           &mut indices);
+        for (group_index, face_indices) in face_order.iter() {
+            let range_start = indices.len();
+
+            for face_index in face_indices.iter() {
+                let face = &self.faces[*face_index];
+
+                // Calculate face normal
+                let vec1 = self.vertices[face.verts.1].pos_morph -
+                           self.vertices[face.verts.0].pos_morph;
+                let vec2 = self.vertices[face.verts.2].pos_morph -
+                           self.vertices[face.verts.0].pos_morph;
+                let normal = vec1.cross(vec2).normalize();
+                let uvs = face.uvs.unwrap_or([Vector2::zero(); 3]);
+
+                // Process each of the vertices
+                process_vertex_flat(&self.vertices[face.verts.0],
+                               face.verts.0,
+                               face.colour,
+                               normal,
+                               uvs[0],
+                               &mut vertices,
+                               &mut indices);
+                process_vertex_flat(&self.vertices[face.verts.1],
+                               face.verts.1,
+                               face.colour,
+                               normal,
+                               uvs[1],
+                               &mut vertices,
+                               &mut indices);
+                process_vertex_flat(&self.vertices[face.verts.2],
+                               face.verts.2,
+                               face.colour,
+                               normal,
+                               uvs[2],
+                               &mut vertices,
+                               &mut indices);
+            }
+
+            if let Some(group_index) = group_index {
+                self.groups[*group_index].solid_range =
+                    (range_start, (indices.len() - range_start) as i32);
+            }
         }
 
         self.solid_length = indices.len() as i32;
@@ -257,71 +412,84 @@ impl Model {
         self.wireframe_index = self.solid_length as usize;
 
         let mut final_vertex_index;
-        for line in self.lines.iter_mut() {
-            let mut curr_vert = &mut self.vertices[line.verts.0];
-            if curr_vert.pushed == false {
-                vertices.push(curr_vert.pos_model.x);
-                vertices.push(curr_vert.pos_model.y);
-                vertices.push(curr_vert.pos_model.z);
+        for (group_index, line_indices) in line_order.iter() {
+            let range_start = indices.len();
+
+            for line_index in line_indices.iter() {
+                let line_verts = self.lines[*line_index].verts;
+
+                let mut curr_vert = &mut self.vertices[line_verts.0];
+                if !curr_vert.pushed {
+                    vertices.push(curr_vert.pos_morph.x);
+                    vertices.push(curr_vert.pos_morph.y);
+                    vertices.push(curr_vert.pos_morph.z);
+
+                    if curr_vert.highlight || curr_vert.selected {
+                        vertices.push(1.0);
+                    } else {
+                        vertices.push(0.0);
+                    }
 
-                if curr_vert.highlight || curr_vert.selected {
-                    vertices.push(1.0);
-                } else {
                     vertices.push(0.0);
-                }
+                    vertices.push(0.0);
+                    vertices.push(0.0);
 
-                vertices.push(0.0);
-                vertices.push(0.0);
-                vertices.push(0.0);
+                    vertices.push(0.0);
+                    push_vertex_tail(curr_vert, Vector2::zero(), line_verts.0, &mut vertices);
 
-                vertices.push(0.0);
+                    curr_vert.pushed = true;
+                    final_vertex_index =
+                        vertices.len() as u32 / SIZE_VERTEX_F32 - 1;
+                    curr_vert.indices.push(final_vertex_index);
+                    curr_vert.colours.push(0.0);
+                } else {
+                    final_vertex_index = curr_vert.indices[0];
+                }
+                indices.push(final_vertex_index as i32);
+
+                curr_vert = &mut self.vertices[line_verts.1];
+                if !curr_vert.pushed {
+                    vertices.push(curr_vert.pos_morph.x);
+                    vertices.push(curr_vert.pos_morph.y);
+                    vertices.push(curr_vert.pos_morph.z);
+
+                    if curr_vert.highlight || curr_vert.selected {
+                        vertices.push(1.0);
+                    } else {
+                        vertices.push(0.0);
+                    }
 
-                curr_vert.pushed = true;
-                final_vertex_index =
-                    vertices.len() as u32 / SIZE_VERTEX_F32 - 1;
-                curr_vert.indices.push(final_vertex_index);
-                curr_vert.colours.push(0.0);
-            } else {
-                final_vertex_index = curr_vert.indices[0];
-            }
-            indices.push(final_vertex_index as i32);
+                    vertices.push(0.0);
+                    vertices.push(0.0);
+                    vertices.push(0.0);
 
-            curr_vert = &mut self.vertices[line.verts.1];
-            if curr_vert.pushed == false {
-                vertices.push(curr_vert.pos_model.x);
-                vertices.push(curr_vert.pos_model.y);
-                vertices.push(curr_vert.pos_model.z);
+                    vertices.push(0.0);
+                    push_vertex_tail(curr_vert, Vector2::zero(), line_verts.1, &mut vertices);
 
-                if curr_vert.highlight || curr_vert.selected {
-                    vertices.push(1.0);
+                    curr_vert.pushed = true;
+                    final_vertex_index =
+                        vertices.len() as u32 / SIZE_VERTEX_F32 - 1;
+                    curr_vert.indices.push(final_vertex_index);
+                    curr_vert.colours.push(0.0);
                 } else {
-                    vertices.push(0.0);
+                    final_vertex_index = curr_vert.indices[0];
                 }
+                indices.push(final_vertex_index as i32);
+            }
 
-                vertices.push(0.0);
-                vertices.push(0.0);
-                vertices.push(0.0);
-
-                vertices.push(0.0);
-
-                curr_vert.pushed = true;
-                final_vertex_index =
-                    vertices.len() as u32 / SIZE_VERTEX_F32 - 1;
-                curr_vert.indices.push(final_vertex_index);
-                curr_vert.colours.push(0.0);
-            } else {
-                final_vertex_index = curr_vert.indices[0];
+            if let Some(group_index) = group_index {
+                self.groups[*group_index].wireframe_range =
+                    (range_start, (indices.len() - range_start) as i32);
             }
-            indices.push(final_vertex_index as i32);
         }
         self.wireframe_length = indices.len() as i32 - self.solid_length;
 
         // Process remaining vertices
-        for vertex in self.vertices.iter() {
-            if vertex.pushed == false {
-                vertices.push(vertex.pos_model.x);
-                vertices.push(vertex.pos_model.y);
-                vertices.push(vertex.pos_model.z);
+        for (index, vertex) in self.vertices.iter().enumerate() {
+            if !vertex.pushed && !hidden_verts[index] {
+                vertices.push(vertex.pos_morph.x);
+                vertices.push(vertex.pos_morph.y);
+                vertices.push(vertex.pos_morph.z);
 
                 if vertex.highlight || vertex.selected {
                     vertices.push(1.0);
@@ -334,6 +502,7 @@ impl Model {
                 vertices.push(0.0);
 
                 vertices.push(0.0);
+                push_vertex_tail(vertex, Vector2::zero(), index, &mut vertices);
             }
         }
 
@@ -356,46 +525,20 @@ impl Model {
         */
 
         // ---- PASS DATA TO GPU ----
-        unsafe {
-            gl::BindVertexArray(self.vao);
-
-            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
-            gl::BufferData
-               (gl::ARRAY_BUFFER,
-               (vertices.len() * mem::size_of::<GLfloat>()) as GLsizeiptr,
-                &vertices[0] as *const f32 as *const c_void,
-                gl::DYNAMIC_DRAW);
-
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
-            gl::BufferData(gl::ELEMENT_ARRAY_BUFFER,
-               (indices.len() * mem::size_of::<GLint>()) as GLsizeiptr,
-                &indices[0] as *const i32 as *const c_void,
-                gl::DYNAMIC_DRAW);
-
-            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE,
-                SIZE_VERTEX_F32 as i32 * mem::size_of::<GLfloat>() as GLsizei,
-                ptr::null());
-            gl::EnableVertexAttribArray(0);
-
-            gl::VertexAttribPointer(1, 1, gl::FLOAT, gl::FALSE,
-                SIZE_VERTEX_F32 as i32 * mem::size_of::<GLfloat>() as GLsizei,
-               (3 * mem::size_of::<GLfloat>()) as *const c_void);
-            gl::EnableVertexAttribArray(1);
-
-            gl::VertexAttribPointer(2, 3, gl::FLOAT, gl::FALSE,
-                SIZE_VERTEX_F32 as i32 * mem::size_of::<GLfloat>() as GLsizei,
-               (4 * mem::size_of::<GLfloat>()) as *const c_void);
-            gl::EnableVertexAttribArray(2);
-
-            gl::VertexAttribPointer(3, 1, gl::FLOAT, gl::FALSE,
-                SIZE_VERTEX_F32 as i32 * mem::size_of::<GLfloat>() as GLsizei,
-               (7 * mem::size_of::<GLfloat>()) as *const c_void);
-            gl::EnableVertexAttribArray(3);
+        // The arrays are kept so the model can be drawn without a GPU too
+        if self.vao != 0 && !indices.is_empty() {
+            self.upload(&vertices, &indices);
+        }
 
-            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        self.vertex_data = vertices;
+        self.index_data = indices;
+    }
 
-            gl::BindVertexArray(0);
-        }
+    fn upload(&self, vertices: &[f32], indices: &[i32]) {
+        with_backend(|backend| {
+            backend.buffer_vertices(self.vao, self.vbo, vertices, SIZE_VERTEX_F32, &VERTEX_ATTRIBS);
+            backend.buffer_indices(self.vao, self.ebo, indices);
+        });
     }
 
     // -------------------------------------------------------------------------
@@ -407,7 +550,7 @@ impl Model {
         size: (u32, u32),
         cursor_pos_screen: Vector2::<f32>) {
         for vertex in self.vertices.iter_mut() {
-            let pos_screen = proj_view_mat * vertex.pos_model.extend(1.0);
+            let pos_screen = proj_view_mat * vertex.pos_morph.extend(1.0);
             vertex.pos_screen = pos_screen.truncate().truncate() / pos_screen.w;
             vertex.pos_screen.x =
                 (vertex.pos_screen.x + 1.0) * size.0 as f32 / 2.0;
@@ -456,29 +599,58 @@ impl Model {
     // RENDER SOLID FACES
     // -------------------------------------------------------------------------
     pub fn render_solid(&self) {
-        unsafe {
-            gl::BindVertexArray(self.vao);
-            gl::DrawElements
-               (gl::TRIANGLES,
-                self.solid_length, gl::UNSIGNED_INT,
-               (self.solid_index * mem::size_of::<GLfloat>()) as *const c_void);
-            gl::BindVertexArray(0);
-        }
+        with_backend(|backend| {
+            backend.draw_elements(self.vao, Primitive::Triangles,
+                                  self.solid_index, self.solid_length as usize);
+        });
     }
 
     // -------------------------------------------------------------------------
     // RENDER VERTICES AND LINES
     // -------------------------------------------------------------------------
     pub fn render_wf(&self) {
-        unsafe {
-            gl::BindVertexArray(self.vao);
-            gl::DrawArrays(gl::POINTS, 0, self.vert_length);
-            gl::DrawElements
-               (gl::LINES,
-                self.wireframe_length, gl::UNSIGNED_INT,
-               (self.wireframe_index * mem::size_of::<GLfloat>()) as *const c_void);
-            gl::BindVertexArray(0);
-        }
+        self.render_points();
+        self.render_lines();
+    }
+
+    pub fn render_points(&self) {
+        with_backend(|backend| {
+            backend.draw_arrays(self.vao, Primitive::Points, 0, self.vert_length as usize);
+        });
+    }
+
+    pub fn render_lines(&self) {
+        with_backend(|backend| {
+            backend.draw_elements(self.vao, Primitive::Lines,
+                                  self.wireframe_index, self.wireframe_length as usize);
+        });
+    }
+
+    // -------------------------------------------------------------------------
+    // DATA SENT TO THE GPU
+    // -------------------------------------------------------------------------
+    // Interleaved vertices of SIZE_VERTEX_F32 floats and the element indices,
+    // for drawing the model on the CPU
+    pub fn vertex_data(&self) -> &Vec<f32> {
+        &self.vertex_data
+    }
+
+    pub fn index_data(&self) -> &Vec<i32> {
+        &self.index_data
+    }
+
+    // Start and length in index_data of the faces and of the lines, the points
+    // are the first n_points vertices
+    pub fn solid_range(&self) -> (usize, usize) {
+        (self.solid_index, self.solid_length as usize)
+    }
+
+    pub fn wireframe_range(&self) -> (usize, usize) {
+        (self.wireframe_index, self.wireframe_length as usize)
+    }
+
+    pub fn n_points(&self) -> usize {
+        self.vert_length as usize
     }
 
     // -------------------------------------------------------------------------
@@ -487,8 +659,10 @@ impl Model {
     pub fn add_vert(&mut self, pos_model: Vector3<f32>) {
         self.vertices.push(Vertex {
             pos_model,
+            pos_morph: pos_model,
             pos_screen: Vector2::zero(),
             normal_model: None,
+            colour: None,
             indices: Vec::new(),
             colours: Vec::new(),
             pushed: false,
@@ -500,8 +674,10 @@ impl Model {
     pub fn add_vert_with_normal(&mut self, pos_model: Vector3<f32>, normal_model: Vector3<f32>) {
         self.vertices.push(Vertex {
             pos_model,
+            pos_morph: pos_model,
             pos_screen: Vector2::zero(),
             normal_model: Some(normal_model.normalize()),
+            colour: None,
             indices: Vec::new(),
             colours: Vec::new(),
             pushed: false,
@@ -510,34 +686,276 @@ impl Model {
         });
     }
 
+    pub fn n_verts(&self) -> usize {
+        self.vertices.len()
+    }
+
+    pub fn vert_pos(&self, vert_index: usize) -> Vector3<f32> {
+        self.vertices[vert_index].pos_model
+    }
+
+    // Vertex under the cursor without touching the selection
+    pub fn vert_at(&self, cursor_pos_screen: Vector2<f32>) -> Option<usize> {
+        self.vertices.iter()
+            .position(|vertex| (vertex.pos_screen - cursor_pos_screen).magnitude() < 5.0)
+    }
+
+    // -------------------------------------------------------------------------
+    // VERTEX COLOURS
+    // -------------------------------------------------------------------------
+    // True colours interpolate across faces and take over the palette entry of
+    // the faces around the vertex, None goes back to the palette
+    pub fn vert_colour(&self, vert_index: usize) -> Option<(f32, f32, f32, f32)> {
+        self.vertices[vert_index].colour
+    }
+
+    // Paint every selected vertex, locked groups are left alone
+    pub fn paint_gradient(&mut self, gradient: &Gradient) {
+        let locked = self.locked_vert_mask();
+        for vert_index in self.selected_verts() {
+            if !locked[vert_index] {
+                let pos = self.vertices[vert_index].pos_model;
+                self.vertices[vert_index].colour = Some(gradient.colour_at(pos));
+            }
+        }
+    }
+
+    pub fn clear_selected_colours(&mut self) {
+        let locked = self.locked_vert_mask();
+        for vert_index in self.selected_verts() {
+            if !locked[vert_index] {
+                self.vertices[vert_index].colour = None;
+            }
+        }
+    }
+
     // -------------------------------------------------------------------------
     // SELECT VERTEX
     // -------------------------------------------------------------------------
     pub fn select_vert(&mut self, cursor_pos_screen: Vector2<f32>)
       -> Option<usize> {
+        let blocked = self.blocked_vert_mask();
         for (index, vertex) in self.vertices.iter_mut().enumerate() {
             if (vertex.pos_screen - cursor_pos_screen).magnitude() < 5.0
-             && vertex.selected == false {
+             && !self.selection.contains_vert(index)
+             && !blocked[index] {
                 vertex.selected = true;
+                self.selection.apply_verts(&[index], SelectOp::Add);
                 return Some(index);
             }
         }
         return None;
     }
 
+    // -------------------------------------------------------------------------
+    // SELECTION SETS
+    // -------------------------------------------------------------------------
+    pub fn selection(&self) -> &Selection {
+        &self.selection
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.selection.clear();
+    }
+
+    // Change the selection mode converting what is currently selected: going
+    // down to vertices keeps every vertex touched, going up keeps only the
+    // edges or faces whose vertices are all selected
+    pub fn set_select_mode(&mut self, mode: SelectMode) {
+        if mode == self.selection.mode() {
+            return;
+        }
+
+        let verts = self.selected_verts();
+        let lines = self.lines_within(&verts);
+        let faces = self.faces_within(&verts);
+
+        self.selection.clear();
+        self.selection.set_mode(mode);
+        match mode {
+            SelectMode::Vertex => self.selection.apply_verts(&verts, SelectOp::Replace),
+            SelectMode::Edge => self.selection.apply_lines(&lines, SelectOp::Replace),
+            SelectMode::Face => self.selection.apply_faces(&faces, SelectOp::Replace)
+        }
+    }
+
+    // Select using a rectangle or lasso in screen space, relies on the
+    // pos_screen values computed in process_vertices. Edges and faces need all
+    // their vertices inside the region. Hidden or locked groups are skipped
+    pub fn select_region(&mut self, region: &SelectRegion, op: SelectOp) {
+        let blocked = self.blocked_vert_mask();
+        let inside: Vec<bool> = self.vertices.iter().enumerate()
+            .map(|(index, vertex)| region.contains(vertex.pos_screen) && !blocked[index])
+            .collect();
+
+        let mut hits = Vec::<usize>::new();
+        match self.selection.mode() {
+            SelectMode::Vertex => {
+                for (index, is_inside) in inside.iter().enumerate() {
+                    if *is_inside {
+                        hits.push(index);
+                    }
+                }
+            },
+            SelectMode::Edge => {
+                for (index, line) in self.lines.iter().enumerate() {
+                    if inside[line.verts.0] && inside[line.verts.1] {
+                        hits.push(index);
+                    }
+                }
+            },
+            SelectMode::Face => {
+                for (index, face) in self.faces.iter().enumerate() {
+                    if inside[face.verts.0] && inside[face.verts.1] &&
+                       inside[face.verts.2] {
+                        hits.push(index);
+                    }
+                }
+            }
+        }
+
+        self.selection.apply(&hits, op);
+    }
+
+    // Every vertex touched by the selection, whatever the mode
+    pub fn selected_verts(&self) -> Vec<usize> {
+        let mask = self.selected_vert_mask();
+        (0..mask.len()).filter(|index| mask[*index]).collect()
+    }
+
+    // The edges whose vertices are all selected in vertex mode. In edge and
+    // face modes only the explicitly selected ones, so picking two edges of a
+    // triangle doesn't bring in the third
+    pub fn selected_lines(&self) -> Vec<usize> {
+        let mut lines = match self.selection.mode() {
+            SelectMode::Vertex => self.lines_within(&self.selected_verts()),
+            _ => self.selection.lines().clone()
+        };
+        lines.sort();
+        lines
+    }
+
+    // Same as the edges, explicitly selected faces outside vertex mode
+    pub fn selected_faces(&self) -> Vec<usize> {
+        let mut faces = match self.selection.mode() {
+            SelectMode::Vertex => self.faces_within(&self.selected_verts()),
+            _ => self.selection.faces().clone()
+        };
+        faces.sort();
+        faces
+    }
+
+    fn selected_vert_mask(&self) -> Vec<bool> {
+        let n_vert = self.vertices.len();
+        let mut mask = vec![false; n_vert];
+
+        for vert_index in self.selection.verts().iter() {
+            if *vert_index < n_vert {
+                mask[*vert_index] = true;
+            }
+        }
+        for line_index in self.selection.lines().iter() {
+            if let Some(line) = self.lines.get(*line_index) {
+                mask[line.verts.0] = true;
+                mask[line.verts.1] = true;
+            }
+        }
+        for face_index in self.selection.faces().iter() {
+            if let Some(face) = self.faces.get(*face_index) {
+                mask[face.verts.0] = true;
+                mask[face.verts.1] = true;
+                mask[face.verts.2] = true;
+            }
+        }
+
+        mask
+    }
+
+    fn lines_within(&self, verts: &[usize]) -> Vec<usize> {
+        let mut lines = Vec::<usize>::new();
+        for (index, line) in self.lines.iter().enumerate() {
+            if verts.contains(&line.verts.0) && verts.contains(&line.verts.1) {
+                lines.push(index);
+            }
+        }
+        lines
+    }
+
+    fn face_removed(&mut self, face_index: usize) {
+        self.selection.face_removed(face_index);
+        for group in self.groups.iter_mut() {
+            group.face_removed(face_index);
+        }
+    }
+
+    fn line_removed(&mut self, line_index: usize) {
+        self.selection.line_removed(line_index);
+        for group in self.groups.iter_mut() {
+            group.line_removed(line_index);
+        }
+    }
+
+    fn faces_within(&self, verts: &[usize]) -> Vec<usize> {
+        let mut faces = Vec::<usize>::new();
+        for (index, face) in self.faces.iter().enumerate() {
+            if verts.contains(&face.verts.0) && verts.contains(&face.verts.1) &&
+               verts.contains(&face.verts.2) {
+                faces.push(index);
+            }
+        }
+        faces
+    }
+
     // -------------------------------------------------------------------------
     // REMOVE VERTEX
     // -------------------------------------------------------------------------
     pub fn remove_vert(&mut self, vert_index: usize) {
+        // Locked groups can't be edited
+        if self.locked_vert_mask()[vert_index] {
+            println!("Vertex {} belongs to a locked group", vert_index);
+            return;
+        }
+
         // ---- REMOVE EVERYTHING TO DO WITH THE VERTEX ----
+        // Keep the selection and groups pointing at the right elements, going
+        // backwards so that the shifting does not affect the indices still to
+        // check
+        let removed_faces: Vec<usize> = (0..self.faces.len()).rev()
+            .filter(|face_index| {
+                let face = &self.faces[*face_index];
+                face.verts.0 == vert_index || face.verts.1 == vert_index ||
+                face.verts.2 == vert_index
+            })
+            .collect();
+        for face_index in removed_faces.iter() {
+            self.face_removed(*face_index);
+        }
+
+        let removed_lines: Vec<usize> = (0..self.lines.len()).rev()
+            .filter(|line_index| {
+                let line = &self.lines[*line_index];
+                line.verts.0 == vert_index || line.verts.1 == vert_index
+            })
+            .collect();
+        for line_index in removed_lines.iter() {
+            self.line_removed(*line_index);
+        }
+
+        self.selection.vert_removed(vert_index);
+        self.morphs.vert_removed(vert_index);
+        for group in self.groups.iter_mut() {
+            group.vert_removed(vert_index);
+        }
+
         // Clean faces
-        self.faces.retain(|face| face.verts.0 != vert_index && 
-                                 face.verts.1 != vert_index &&
-                                 face.verts.2 != vert_index);
+        for face_index in removed_faces.iter() {
+            self.faces.remove(*face_index);
+        }
 
         // Clean lines
-        self.lines.retain(|line| line.verts.0 != vert_index &&
-                                 line.verts.1 != vert_index);
+        for line_index in removed_lines.iter() {
+            self.lines.remove(*line_index);
+        }
 
         // Remove vertex
         self.vertices.remove(vert_index);
@@ -573,8 +991,7 @@ impl Model {
             verts: (vert_indices[0], vert_indices[1])
         });
 
-        self.vertices[vert_indices[0]].selected = false;
-        self.vertices[vert_indices[1]].selected = false;
+        self.selection.apply_verts(&vert_indices[0..2], SelectOp::Subtract);
     }
 
     // -------------------------------------------------------------------------
@@ -583,12 +1000,541 @@ impl Model {
     pub fn add_face(&mut self, vert_indices: &Vec::<usize>, colour: f32) {
         self.faces.push(Face {
             verts: (vert_indices[0], vert_indices[1], vert_indices[2]),
-            colour
+            colour,
+            uvs: None
+        });
+
+        self.selection.apply_verts(&vert_indices[0..3], SelectOp::Subtract);
+    }
+
+    // -------------------------------------------------------------------------
+    // MORPH TARGETS
+    // -------------------------------------------------------------------------
+    pub fn morphs(&self) -> &Morphs {
+        &self.morphs
+    }
+
+    pub fn morphs_mut(&mut self) -> &mut Morphs {
+        &mut self.morphs
+    }
+
+    pub fn vert_positions(&self) -> Vec<Vector3<f32>> {
+        self.vertices.iter().map(|vertex| vertex.pos_model).collect()
+    }
+
+    // Vertices past the end of the list are left where they are
+    pub fn set_vert_positions(&mut self, positions: &[Vector3<f32>]) {
+        for (vertex, pos) in self.vertices.iter_mut().zip(positions.iter()) {
+            vertex.pos_model = *pos;
+        }
+    }
+
+    // Store how far the vertices moved since the base positions were taken
+    // and put them back, so the model is left as it was
+    pub fn record_morph_target(&mut self, name: &str, base_positions: &[Vector3<f32>]) -> usize {
+        let mut pos_deltas = Vec::<Vector3<f32>>::new();
+        for (vert_index, vertex) in self.vertices.iter_mut().enumerate() {
+            match base_positions.get(vert_index) {
+                Some(base) => {
+                    pos_deltas.push(vertex.pos_model - base);
+                    vertex.pos_model = *base;
+                },
+                None => pos_deltas.push(Vector3::zero())
+            }
+        }
+
+        self.morphs.add_target(name, pos_deltas, None)
+    }
+
+    // Weights follow their tracks at the given time in seconds
+    pub fn animate_morphs(&mut self, time: f32) {
+        self.morphs.evaluate(time);
+    }
+
+    // Move the selected vertices, locked groups stay put
+    pub fn translate_selected(&mut self, delta: Vector3<f32>) {
+        let locked = self.locked_vert_mask();
+        for vert_index in self.selected_verts() {
+            if !locked[vert_index] {
+                self.vertices[vert_index].pos_model += delta;
+            }
+        }
+    }
+
+    // -------------------------------------------------------------------------
+    // ANIMATED PARTS
+    // -------------------------------------------------------------------------
+    pub fn find_part(&self, name: &str) -> Option<usize> {
+        self.parts.iter().position(|part| part.name == name)
+    }
+
+    // Parts are matched to the group of the same name, adding one again
+    // replaces its pivot and axis
+    pub fn add_part(&mut self, name: &str, pivot: Vector3<f32>, axis: Vector3<f32>) -> usize {
+        match self.find_part(name) {
+            Some(part_index) => {
+                self.parts[part_index] = Part::new(name, pivot, axis);
+                part_index
+            },
+            None => {
+                self.parts.push(Part::new(name, pivot, axis));
+                self.part_poses.push(Matrix4::identity());
+                self.parts.len() - 1
+            }
+        }
+    }
+
+    // Pivot at the centre of the vertices of the group
+    pub fn part_from_group(&mut self, group_index: usize, axis: Vector3<f32>) -> usize {
+        let verts = self.groups[group_index].verts().clone();
+        let mut pivot = Vector3::<f32>::zero();
+        for vert_index in verts.iter() {
+            pivot += self.vertices[*vert_index].pos_model;
+        }
+        if !verts.is_empty() {
+            pivot /= verts.len() as f32;
+        }
+
+        let name = String::from(self.groups[group_index].name());
+        self.add_part(&name, pivot, axis)
+    }
+
+    // Pose every part as the clip has it at the given time, parts without a
+    // track go back to rest
+    pub fn set_pose(&mut self, clip: &Clip, time: f32) {
+        for (part, pose) in self.parts.iter().zip(self.part_poses.iter_mut()) {
+            *pose = match clip.track(&part.name) {
+                Some(track) => {
+                    let (angle, translation) = track.sample(time);
+                    part.matrix(angle, translation)
+                },
+                None => Matrix4::identity()
+            };
+        }
+    }
+
+    pub fn clear_pose(&mut self) {
+        for pose in self.part_poses.iter_mut() {
+            *pose = Matrix4::identity();
+        }
+    }
+
+    // -------------------------------------------------------------------------
+    // COLLISION
+    // -------------------------------------------------------------------------
+    pub fn face_triangles(&self) -> Vec<[Vector3<f32>; 3]> {
+        self.faces.iter().map(|face| [self.vertices[face.verts.0].pos_model,
+                                      self.vertices[face.verts.1].pos_model,
+                                      self.vertices[face.verts.2].pos_model]).collect()
+    }
+
+    // Simplified mesh used for collisions instead of the faces of the model
+    pub fn collision_path(&self) -> Option<&str> {
+        self.collision_path.as_deref()
+    }
+
+    pub fn set_collision_path(&mut self, path_to_file: Option<&str>) {
+        self.collision_path = path_to_file.map(String::from);
+    }
+
+    // From the collision mesh when there is one that loads, otherwise from the
+    // faces of the model itself
+    pub fn collision_mesh(&self) -> CollisionMesh {
+        if let Some(path) = &self.collision_path {
+            if let Some(mesh) = CollisionMesh::load_from_file(path) {
+                return mesh;
+            }
+        }
+
+        CollisionMesh::new(self.face_triangles())
+    }
+
+    // -------------------------------------------------------------------------
+    // TEXTURE COORDINATES
+    // -------------------------------------------------------------------------
+    // Generate UVs for the selected faces, faces touching locked groups are
+    // left alone
+    pub fn project_uvs(&mut self, projection: &UvProjection) {
+        let locked = self.locked_vert_mask();
+        for face_index in self.selected_faces() {
+            let verts = self.faces[face_index].verts;
+            if locked[verts.0] || locked[verts.1] || locked[verts.2] {
+                continue;
+            }
+
+            let corners = [self.vertices[verts.0].pos_model,
+                           self.vertices[verts.1].pos_model,
+                           self.vertices[verts.2].pos_model];
+            self.faces[face_index].uvs = Some(projection.project_face(corners));
+        }
+    }
+
+    // Centre of the bounding box and distance to the furthest vertex
+    pub fn bounding_sphere(&self) -> (Vector3<f32>, f32) {
+        if self.vertices.is_empty() {
+            return (Vector3::zero(), 0.0);
+        }
+
+        let mut min = self.vertices[0].pos_model;
+        let mut max = min;
+        for vertex in self.vertices.iter() {
+            let pos = vertex.pos_model;
+            min = Vector3::new(min.x.min(pos.x), min.y.min(pos.y), min.z.min(pos.z));
+            max = Vector3::new(max.x.max(pos.x), max.y.max(pos.y), max.z.max(pos.z));
+        }
+
+        let centre = (min + max) / 2.0;
+        let radius = self.vertices.iter()
+            .fold(0.0f32, |radius, vertex| radius.max((vertex.pos_model - centre).magnitude()));
+
+        (centre, radius)
+    }
+
+    // Bounding box of the selected vertices
+    pub fn selected_bounds(&self) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        let verts = self.selected_verts();
+        if verts.is_empty() {
+            return None;
+        }
+
+        let mut min = self.vertices[verts[0]].pos_model;
+        let mut max = min;
+        for vert_index in verts.iter() {
+            let pos = self.vertices[*vert_index].pos_model;
+            min = Vector3::new(min.x.min(pos.x), min.y.min(pos.y), min.z.min(pos.z));
+            max = Vector3::new(max.x.max(pos.x), max.y.max(pos.y), max.z.max(pos.z));
+        }
+
+        Some((min, max))
+    }
+
+    // Image used by the textured shader, loaded through a TextureCache
+    pub fn texture_path(&self) -> Option<&str> {
+        self.texture_path.as_deref()
+    }
+
+    pub fn set_texture_path(&mut self, path_to_file: Option<&str>) {
+        self.texture_path = path_to_file.map(String::from);
+    }
+
+    // -------------------------------------------------------------------------
+    // PALETTE
+    // -------------------------------------------------------------------------
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    pub fn palette_mut(&mut self) -> &mut Palette {
+        &mut self.palette
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    // -------------------------------------------------------------------------
+    // NAMED GROUPS
+    // -------------------------------------------------------------------------
+    pub fn n_lines(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn n_faces(&self) -> usize {
+        self.faces.len()
+    }
+
+    pub fn line_verts(&self, line_index: usize) -> (usize, usize) {
+        self.lines[line_index].verts
+    }
+
+    pub fn face_verts(&self, face_index: usize) -> (usize, usize, usize) {
+        self.faces[face_index].verts
+    }
+
+    pub fn face_colour(&self, face_index: usize) -> f32 {
+        self.faces[face_index].colour
+    }
+
+    // Semantic differences going from this model to the other one
+    pub fn diff(&self, other: &Model, tolerance: f32) -> ModelDiff {
+        ModelDiff::between(self, other, tolerance)
+    }
+
+    pub fn groups(&self) -> &Vec<Group> {
+        &self.groups
+    }
+
+    pub fn find_group(&self, name: &str) -> Option<usize> {
+        self.groups.iter().position(|group| group.name() == name)
+    }
+
+    // Elements already owned by another group are moved to this one. A group
+    // with the same name is reused
+    pub fn add_group(&mut self,
+                     name: &str,
+                     verts: &[usize],
+                     lines: &[usize],
+                     faces: &[usize]) -> usize {
+        for group in self.groups.iter_mut() {
+            group.remove(verts, lines, faces);
+        }
+
+        let group_index = match self.find_group(name) {
+            Some(group_index) => group_index,
+            None => {
+                self.groups.push(Group::new(name));
+                self.groups.len() - 1
+            }
+        };
+        self.groups[group_index].add(verts, lines, faces);
+
+        group_index
+    }
+
+    // Group everything added after the given counts of vertices, lines and
+    // faces, handy when building a model part by part
+    pub fn add_group_since(&mut self, name: &str, counts: (usize, usize, usize)) -> usize {
+        let verts: Vec<usize> = (counts.0..self.vertices.len()).collect();
+        let lines: Vec<usize> = (counts.1..self.lines.len()).collect();
+        let faces: Vec<usize> = (counts.2..self.faces.len()).collect();
+
+        self.add_group(name, &verts, &lines, &faces)
+    }
+
+    pub fn group_from_selection(&mut self, name: &str) -> usize {
+        let verts = self.selected_verts();
+        let lines = self.selected_lines();
+        let faces = self.selected_faces();
+
+        self.add_group(name, &verts, &lines, &faces)
+    }
+
+    pub fn set_group_visible(&mut self, group_index: usize, visible: bool) {
+        self.groups[group_index].set_visible(visible);
+    }
+
+    pub fn set_group_locked(&mut self, group_index: usize, locked: bool) {
+        self.groups[group_index].set_locked(locked);
+    }
+
+    // Selects the whole group in the current selection mode
+    pub fn select_group(&mut self, group_index: usize, op: SelectOp) {
+        let group = &self.groups[group_index];
+        let hits = match self.selection.mode() {
+            SelectMode::Vertex => {
+                let mut verts = group.verts().clone();
+                for face_index in group.faces().iter() {
+                    let face = &self.faces[*face_index];
+                    verts.extend_from_slice(&[face.verts.0, face.verts.1, face.verts.2]);
+                }
+                for line_index in group.lines().iter() {
+                    let line = &self.lines[*line_index];
+                    verts.extend_from_slice(&[line.verts.0, line.verts.1]);
+                }
+                verts
+            },
+            SelectMode::Edge => group.lines().clone(),
+            SelectMode::Face => group.faces().clone()
+        };
+
+        self.selection.apply(&hits, op);
+    }
+
+    // Hidden groups are not sent to the GPU and have nothing to draw
+    pub fn render_group_solid(&self, group_index: usize) {
+        if !self.groups[group_index].is_visible() {
+            return;
+        }
+        let (start, length) = self.groups[group_index].solid_range();
+        with_backend(|backend| {
+            backend.draw_elements(self.vao, Primitive::Triangles, start, length as usize);
         });
+    }
+
+    pub fn render_group_wf(&self, group_index: usize) {
+        if !self.groups[group_index].is_visible() {
+            return;
+        }
+        let (start, length) = self.groups[group_index].wireframe_range();
+        with_backend(|backend| {
+            backend.draw_elements(self.vao, Primitive::Lines, start, length as usize);
+        });
+    }
+
+    // Order in which faces and lines go to the GPU, tagged with their group.
+    // Hidden groups are left out
+    fn element_order(&self) -> (ElementOrder, ElementOrder) {
+        let mut face_group = vec![None; self.faces.len()];
+        let mut line_group = vec![None; self.lines.len()];
+        for (group_index, group) in self.groups.iter().enumerate() {
+            for face_index in group.faces().iter() {
+                face_group[*face_index] = Some(group_index);
+            }
+            for line_index in group.lines().iter() {
+                line_group[*line_index] = Some(group_index);
+            }
+        }
+
+        let mut face_order = vec![(None, (0..self.faces.len()).filter(|index| face_group[*index].is_none()).collect())];
+        let mut line_order = vec![(None, (0..self.lines.len()).filter(|index| line_group[*index].is_none()).collect())];
+        for (group_index, group) in self.groups.iter().enumerate() {
+            if group.is_visible() {
+                face_order.push((Some(group_index), group.faces().clone()));
+                line_order.push((Some(group_index), group.lines().clone()));
+            }
+        }
+
+        (face_order, line_order)
+    }
+
+    fn hidden_vert_mask(&self) -> Vec<bool> {
+        let mut mask = vec![false; self.vertices.len()];
+        for group in self.groups.iter() {
+            if !group.is_visible() {
+                for vert_index in group.verts().iter() {
+                    mask[*vert_index] = true;
+                }
+            }
+        }
+        mask
+    }
+
+    fn locked_vert_mask(&self) -> Vec<bool> {
+        let mut mask = vec![false; self.vertices.len()];
+        for group in self.groups.iter() {
+            if group.is_locked() {
+                for vert_index in group.verts().iter() {
+                    mask[*vert_index] = true;
+                }
+            }
+        }
+        mask
+    }
+
+    // Vertices that can't be picked because their group is hidden or locked
+    fn blocked_vert_mask(&self) -> Vec<bool> {
+        let hidden = self.hidden_vert_mask();
+        let locked = self.locked_vert_mask();
+        hidden.iter().zip(locked.iter()).map(|(hidden, locked)| *hidden || *locked).collect()
+    }
+
+    // -------------------------------------------------------------------------
+    // BRIDGE TWO LOOPS OF VERTICES
+    // -------------------------------------------------------------------------
+    // Zips both loops together walking along them by arc length, so they can
+    // have a different number of vertices. Loops of the same size are walked
+    // vertex by vertex instead. Lines are added on the rungs where both loops
+    // have moved forward
+    pub fn bridge_loops(&mut self, loop_a: &[usize], loop_b: &[usize], settings: BridgeSettings) {
+        if loop_a.is_empty() || loop_b.is_empty() {
+            return;
+        }
+
+        // Sequences to walk, closed loops come back to their first vertex
+        let mut seq_a = loop_a.to_vec();
+        let mut seq_b = loop_b.to_vec();
+        if settings.closed {
+            let n_b = loop_b.len();
+            for (index, vert_index) in seq_b.iter_mut().enumerate() {
+                *vert_index = loop_b[(index + settings.offset % n_b) % n_b];
+            }
+            seq_a.push(seq_a[0]);
+            seq_b.push(seq_b[0]);
+        }
+
+        let same_size = seq_a.len() == seq_b.len();
+        let frac_a = self.arc_fractions(&seq_a, same_size);
+        let frac_b = self.arc_fractions(&seq_b, same_size);
+
+        // Walk both sequences choosing the loop that falls behind
+        let mut tris = Vec::<(usize, usize, usize)>::new();
+        let mut rungs = vec![(seq_a[0], seq_b[0])];
+        let mut last_rung = (0, 0);
+        let (mut index_a, mut index_b) = (0, 0);
+        while index_a + 1 < seq_a.len() || index_b + 1 < seq_b.len() {
+            let advance_a = if index_a + 1 >= seq_a.len() {
+                false
+            } else if index_b + 1 >= seq_b.len() {
+                true
+            } else {
+                frac_a[index_a + 1] <= frac_b[index_b + 1]
+            };
+
+            if advance_a {
+                tris.push((seq_a[index_a], seq_a[index_a + 1], seq_b[index_b]));
+                index_a += 1;
+            } else {
+                tris.push((seq_a[index_a], seq_b[index_b + 1], seq_b[index_b]));
+                index_b += 1;
+            }
+
+            if index_a != last_rung.0 && index_b != last_rung.1 {
+                rungs.push((seq_a[index_a], seq_b[index_b]));
+                last_rung = (index_a, index_b);
+            }
+        }
+        let last_pair = (seq_a[index_a], seq_b[index_b]);
+        if !rungs.contains(&last_pair) {
+            rungs.push(last_pair);
+        }
+
+        // Orient the strip away from the centre of both loops
+        let mut centre = Vector3::<f32>::zero();
+        for vert_index in loop_a.iter().chain(loop_b.iter()) {
+            centre += self.vertices[*vert_index].pos_model;
+        }
+        centre /= (loop_a.len() + loop_b.len()) as f32;
+
+        let mut facing = 0.0;
+        for tri in tris.iter() {
+            let pos_0 = self.vertices[tri.0].pos_model;
+            let pos_1 = self.vertices[tri.1].pos_model;
+            let pos_2 = self.vertices[tri.2].pos_model;
+            let normal = (pos_1 - pos_0).cross(pos_2 - pos_0);
+            facing += normal.dot((pos_0 + pos_1 + pos_2) / 3.0 - centre);
+        }
+        let reverse = (facing < 0.0) != settings.flip;
 
-        self.vertices[vert_indices[0]].selected = false;
-        self.vertices[vert_indices[1]].selected = false;
-        self.vertices[vert_indices[2]].selected = false;
+        // Add everything to the model
+        for rung in rungs.iter() {
+            if rung.0 != rung.1 {
+                self.add_line(&vec![rung.0, rung.1]);
+            }
+        }
+        for tri in tris.iter() {
+            if tri.0 == tri.1 || tri.1 == tri.2 || tri.2 == tri.0 {
+                continue;
+            }
+            if reverse {
+                self.add_face(&vec![tri.0, tri.2, tri.1], settings.colour);
+            } else {
+                self.add_face(&vec![tri.0, tri.1, tri.2], settings.colour);
+            }
+        }
+    }
+
+    // Accumulated length along a sequence of vertices, from 0 to 1
+    fn arc_fractions(&self, sequence: &[usize], uniform: bool) -> Vec<f32> {
+        let mut fractions = vec![0.0];
+        for index in 1..sequence.len() {
+            let length = (self.vertices[sequence[index]].pos_model -
+                          self.vertices[sequence[index - 1]].pos_model).magnitude();
+            fractions.push(fractions[index - 1] + length);
+        }
+
+        let total = fractions[fractions.len() - 1];
+        if total > 0.0 && !uniform {
+            for fraction in fractions.iter_mut() {
+                *fraction /= total;
+            }
+        } else {
+            let n_steps = (sequence.len() - 1).max(1) as f32;
+            for (index, fraction) in fractions.iter_mut().enumerate() {
+                *fraction = index as f32 / n_steps;
+            }
+        }
+
+        fractions
     }
 
     // -------------------------------------------------------------------------
@@ -597,23 +1543,74 @@ impl Model {
     pub fn write_to_file(&self, path_to_file: &str) {
         let mut file = File::create(path_to_file).unwrap();
 
+        // Vertices with a colour of their own carry it after the position
         for vertex in self.vertices.iter() {
-            write!(file, "{},{},{}\n", vertex.pos_model.x,
-                                         vertex.pos_model.y,
-                                         vertex.pos_model.z).expect("Could not write to file");
+            write!(file, "{},{},{}", vertex.pos_model.x,
+                                     vertex.pos_model.y,
+                                     vertex.pos_model.z).expect("Could not write to file");
+            if let Some(colour) = vertex.colour {
+                write!(file, ",{},{},{},{}", colour.0,
+                                             colour.1,
+                                             colour.2,
+                                             colour.3).expect("Could not write to file");
+            }
+            writeln!(file).expect("Could not write to file");
         }
 
-        write!(file, "Lines\n").expect("Could not write to file");
+        writeln!(file, "Lines").expect("Could not write to file");
         for line in self.lines.iter() {
-            write!(file, "{},{}\n", line.verts.0, line.verts.1).expect("Could not write to file");
+            writeln!(file, "{},{}", line.verts.0, line.verts.1).expect("Could not write to file");
         }
 
-        write!(file, "Faces\n").expect("Could not write to file");
+        writeln!(file, "Faces").expect("Could not write to file");
+        // Faces with UVs carry them after the colour, corner by corner
         for face in self.faces.iter() {
-            write!(file, "{},{},{},{}\n", face.verts.0,
-                                             face.verts.1,
-                                             face.verts.2,
-                                             face.colour).expect("Could not write to file");
+            write!(file, "{},{},{},{}", face.verts.0,
+                                        face.verts.1,
+                                        face.verts.2,
+                                        face.colour).expect("Could not write to file");
+            if let Some(uvs) = face.uvs {
+                for uv in uvs.iter() {
+                    write!(file, ",{},{}", uv.x, uv.y).expect("Could not write to file");
+                }
+            }
+            writeln!(file).expect("Could not write to file");
+        }
+
+        if let Some(path) = &self.texture_path {
+            writeln!(file, "Texture\n{}", path).expect("Could not write to file");
+        }
+        if let Some(path) = &self.collision_path {
+            writeln!(file, "Collision\n{}", path).expect("Could not write to file");
+        }
+
+        writeln!(file, "Palette").expect("Could not write to file");
+        for line in self.palette.to_lines() {
+            writeln!(file, "{}", line).expect("Could not write to file");
+        }
+
+        if !self.morphs.targets().is_empty() {
+            writeln!(file, "Morphs").expect("Could not write to file");
+            for line in self.morphs.to_lines() {
+                writeln!(file, "{}", line).expect("Could not write to file");
+            }
+        }
+
+        if !self.groups.is_empty() {
+            writeln!(file, "Groups").expect("Could not write to file");
+            for group in self.groups.iter() {
+                for line in group.to_lines() {
+                    writeln!(file, "{}", line).expect("Could not write to file");
+                }
+            }
+        }
+
+        // Parts go after the groups they refer to
+        if !self.parts.is_empty() {
+            writeln!(file, "Parts").expect("Could not write to file");
+            for part in self.parts.iter() {
+                writeln!(file, "{}", part.to_line()).expect("Could not write to file");
+            }
         }
     }
 
@@ -626,6 +1623,14 @@ impl Model {
                 self.vertices = Vec::new();
                 self.lines = Vec::new();
                 self.faces = Vec::new();
+                self.selection.clear();
+                self.groups = Vec::new();
+                self.palette = Palette::create_default();
+                self.texture_path = None;
+                self.morphs = Morphs::new();
+                self.parts = Vec::new();
+                self.part_poses = Vec::new();
+                self.collision_path = None;
 
                 let file = BufReader::new(file);
 
@@ -645,6 +1650,15 @@ impl Model {
                                 vert_coords.z = str_vec[2].parse::<f32>().unwrap();
 
                                 self.add_vert(vert_coords);
+
+                                if str_vec.len() == 7 {
+                                    let values: Vec<f32> = str_vec[3..7].iter()
+                                        .map(|value| value.parse::<f32>().unwrap())
+                                        .collect();
+                                    let last_vert = self.vertices.len() - 1;
+                                    self.vertices[last_vert].colour =
+                                        Some((values[0], values[1], values[2], values[3]));
+                                }
                             }
                         },
                         1 => if actual_line == "Faces" {
@@ -658,7 +1672,17 @@ impl Model {
 
                             self.add_line(&line_ind);
                         },
-                        2 => {
+                        2 => if actual_line == "Groups" {
+                            mode = 3;
+                        } else if actual_line == "Palette" {
+                            // Models without one keep the default palette
+                            self.palette = Palette::create_empty();
+                            mode = 4;
+                        } else if actual_line == "Texture" {
+                            mode = 5;
+                        } else if actual_line == "Collision" {
+                            mode = 8;
+                        } else {
                             let str_vec: Vec<&str> = actual_line.split(',').collect();
                             let mut face_ind: Vec<usize> = Vec::new();
 
@@ -669,6 +1693,63 @@ impl Model {
                             let colour = str_vec[3].parse::<f32>().unwrap();
 
                             self.add_face(&face_ind, colour);
+
+                            if str_vec.len() == 10 {
+                                let values: Vec<f32> = str_vec[4..10].iter()
+                                    .map(|value| value.parse::<f32>().unwrap())
+                                    .collect();
+                                let last_face = self.faces.len() - 1;
+                                self.faces[last_face].uvs =
+                                    Some([Vector2::new(values[0], values[1]),
+                                          Vector2::new(values[2], values[3]),
+                                          Vector2::new(values[4], values[5])]);
+                            }
+                        },
+                        3 if actual_line == "Parts" => mode = 7,
+                        3 => match Group::from_header(&actual_line) {
+                            Some(group) => self.groups.push(group),
+                            None => match self.groups.last_mut() {
+                                Some(group) => group.parse_members(&actual_line),
+                                None => println!("Group data before any group: {}", actual_line)
+                            }
+                        },
+                        4 => if actual_line == "Groups" {
+                            mode = 3;
+                        } else if actual_line == "Morphs" {
+                            mode = 6;
+                        } else if actual_line == "Parts" {
+                            mode = 7;
+                        } else {
+                            self.palette.parse_line(&actual_line);
+                        },
+                        // Single lines, the palette always follows
+                        5 => if actual_line == "Palette" {
+                            self.palette = Palette::create_empty();
+                            mode = 4;
+                        } else if actual_line == "Collision" {
+                            mode = 8;
+                        } else {
+                            self.texture_path = Some(actual_line);
+                        },
+                        8 => if actual_line == "Palette" {
+                            self.palette = Palette::create_empty();
+                            mode = 4;
+                        } else {
+                            self.collision_path = Some(actual_line);
+                        },
+                        6 => if actual_line == "Groups" {
+                            mode = 3;
+                        } else if actual_line == "Parts" {
+                            mode = 7;
+                        } else {
+                            self.morphs.parse_line(&actual_line);
+                        },
+                        7 => match Part::from_line(&actual_line) {
+                            Some(part) => {
+                                self.parts.push(part);
+                                self.part_poses.push(Matrix4::identity());
+                            },
+                            None => println!("Wrong part line: {}", actual_line)
                         },
                         _ => ()
                     }
@@ -683,18 +1764,34 @@ impl Model {
 }
 
 
+// Headless models never made any GPU objects
+impl Drop for Model {
+    fn drop(&mut self) {
+        if self.vao != 0 {
+            with_backend(|backend| {
+                backend.delete_buffer(self.ebo);
+                backend.delete_buffer(self.vbo);
+                backend.delete_vertex_array(self.vao);
+            });
+        }
+    }
+}
+
+
 // -----------------------------------------------------------------------------
 // MISC FUNCTIONS
 // -----------------------------------------------------------------------------
 fn process_vertex_flat(curr_vert: &Vertex,
+                       vert_index: usize,
                        colour: f32,
                        normal: Vector3::<f32>,
+                       uv: Vector2::<f32>,
                        vertices: &mut Vec<f32>,
                        indices: &mut Vec<i32>) {
     // Push an entire vertex
-    vertices.push(curr_vert.pos_model.x);
-    vertices.push(curr_vert.pos_model.y);
-    vertices.push(curr_vert.pos_model.z);
+    vertices.push(curr_vert.pos_morph.x);
+    vertices.push(curr_vert.pos_morph.y);
+    vertices.push(curr_vert.pos_morph.z);
 
     if curr_vert.highlight || curr_vert.selected {
         vertices.push(1.0);
@@ -707,14 +1804,43 @@ fn process_vertex_flat(curr_vert: &Vertex,
     vertices.push(normal.z);
 
     vertices.push(colour);
+    push_vertex_tail(curr_vert, uv, vert_index, vertices);
 
     indices.push((vertices.len() as u32 / SIZE_VERTEX_F32 - 1) as i32);
 }
 
 
+// Everything after the palette index. Vertices without a colour of their own
+// get a zero weight so the shader falls back to the palette
+fn push_vertex_tail(curr_vert: &Vertex,
+                    uv: Vector2::<f32>,
+                    vert_index: usize,
+                    vertices: &mut Vec<f32>) {
+    match curr_vert.colour {
+        Some(colour) => {
+            vertices.push(colour.0);
+            vertices.push(colour.1);
+            vertices.push(colour.2);
+            vertices.push(colour.3);
+            vertices.push(1.0);
+        },
+        None => {
+            vertices.extend_from_slice(&[0.0, 0.0, 0.0, 0.0, 0.0]);
+        }
+    }
+
+    vertices.push(uv.x);
+    vertices.push(uv.y);
+
+    vertices.push(vert_index as f32);
+}
+
+
 fn process_vertex(curr_vert: &mut Vertex,
+                  vert_index: usize,
                   colour: f32,
                   face_normal: Vector3::<f32>,
+                  uv: Vector2::<f32>,
                   vertices: &mut Vec<f32>,
                   indices: &mut Vec<i32>) {
     // ---- PROCESS THE FIRST VERTEX ----
@@ -741,10 +1867,14 @@ fn process_vertex(curr_vert: &mut Vertex,
             updated_normal = curr_vert.normal_model.unwrap();
         }
 
-        // Vertex was already pushed, but maybe with a different colour
+        // Vertex was already pushed, but maybe with a different colour or UV
         let mut new_colour_flag = true;
         for (index, curr_colour) in curr_vert.colours.iter().enumerate() {
-            if *curr_colour == colour {
+            let pushed_index = curr_vert.indices[index] as usize;
+            let pushed_uv = Vector2::new
+                (vertices[pushed_index * SIZE_VERTEX_F32 as usize + 13],
+                 vertices[pushed_index * SIZE_VERTEX_F32 as usize + 14]);
+            if *curr_colour == colour && pushed_uv == uv {
                 new_colour_flag = false;
                 final_vertex_index = curr_vert.indices[index];
             }
@@ -754,9 +1884,9 @@ fn process_vertex(curr_vert: &mut Vertex,
         if new_colour_flag {
             // If the colour is new, push an entire new vertex with the
             // updated normal
-            vertices.push(curr_vert.pos_model.x);
-            vertices.push(curr_vert.pos_model.y);
-            vertices.push(curr_vert.pos_model.z);
+            vertices.push(curr_vert.pos_morph.x);
+            vertices.push(curr_vert.pos_morph.y);
+            vertices.push(curr_vert.pos_morph.z);
 
             if curr_vert.highlight || curr_vert.selected {
                 vertices.push(1.0);
@@ -769,6 +1899,7 @@ fn process_vertex(curr_vert: &mut Vertex,
             vertices.push(updated_normal.z);
 
             vertices.push(colour);
+            push_vertex_tail(curr_vert, uv, vert_index, vertices);
 
             // Tell the Vertex struct that it has a new vertex now
             curr_vert.indices.push
@@ -781,9 +1912,9 @@ fn process_vertex(curr_vert: &mut Vertex,
         }
     } else {
         // Push an entire vertex
-        vertices.push(curr_vert.pos_model.x);
-        vertices.push(curr_vert.pos_model.y);
-        vertices.push(curr_vert.pos_model.z);
+        vertices.push(curr_vert.pos_morph.x);
+        vertices.push(curr_vert.pos_morph.y);
+        vertices.push(curr_vert.pos_morph.z);
 
         if curr_vert.highlight || curr_vert.selected {
             vertices.push(1.0);
@@ -802,6 +1933,7 @@ fn process_vertex(curr_vert: &mut Vertex,
         }
 
         vertices.push(colour);
+        push_vertex_tail(curr_vert, uv, vert_index, vertices);
 
         // Tell the Vertex struct that it has been pushed
         curr_vert.pushed = true;
@@ -815,4 +1947,223 @@ fn process_vertex(curr_vert: &mut Vertex,
     }
 
     indices.push(final_vertex_index as i32);
-}
\ No newline at end of file
+}
+
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::backend::{set_backend, BackendCall, RecordingBackend};
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // The backend is per thread, and so is every test
+    fn record() -> Rc<RefCell<Vec<BackendCall>>> {
+        let backend = RecordingBackend::new();
+        let calls = backend.calls();
+        set_backend(Box::new(backend));
+        calls
+    }
+
+    #[test]
+    fn update_gpu_data_buffers_the_arrays_it_keeps() {
+        let calls = record();
+        let mut model = Model::create_cube();
+        calls.borrow_mut().clear();
+
+        model.update_gpu_data();
+
+        let calls = calls.borrow();
+        assert_eq!(calls.len(), 2);
+        match &calls[0] {
+            BackendCall::BufferVertices { vao, buffer, data, stride, attribs } => {
+                assert_eq!(*vao, model.vao);
+                assert_eq!(*buffer, model.vbo);
+                assert_eq!(data, model.vertex_data());
+                assert_eq!(*stride, SIZE_VERTEX_F32);
+                assert_eq!(attribs.as_slice(), &VERTEX_ATTRIBS[..]);
+            },
+            call => panic!("Expected the vertices, got {:?}", call)
+        }
+        match &calls[1] {
+            BackendCall::BufferIndices { vao, buffer, data } => {
+                assert_eq!(*vao, model.vao);
+                assert_eq!(*buffer, model.ebo);
+                assert_eq!(data, model.index_data());
+            },
+            call => panic!("Expected the indices, got {:?}", call)
+        }
+
+        // Twelve triangles and twelve edges
+        assert_eq!(model.solid_range(), (0, 36));
+        assert_eq!(model.wireframe_range(), (36, 24));
+        assert_eq!(model.vertex_data().len(), model.n_points() * SIZE_VERTEX_F32 as usize);
+    }
+
+    #[test]
+    fn headless_model_buffers_nothing() {
+        let calls = record();
+        let mut model = Model::create_headless();
+        model.add_vert(Vector3::new(0.0, 0.0, 0.0));
+        model.update_gpu_data();
+        drop(model);
+
+        assert!(calls.borrow().is_empty());
+    }
+
+    #[test]
+    fn render_solid_draws_the_faces() {
+        let calls = record();
+        let model = Model::create_cube();
+        calls.borrow_mut().clear();
+
+        model.render_solid();
+
+        assert_eq!(*calls.borrow(), vec![BackendCall::DrawElements {
+            vao: model.vao,
+            primitive: Primitive::Triangles,
+            first: 0,
+            count: 36
+        }]);
+    }
+
+    #[test]
+    fn dropping_a_model_deletes_its_objects() {
+        let calls = record();
+        let model = Model::create_empty();
+        let (vao, vbo, ebo) = (model.vao, model.vbo, model.ebo);
+        calls.borrow_mut().clear();
+
+        drop(model);
+
+        assert_eq!(*calls.borrow(), vec![BackendCall::DeleteBuffer(ebo),
+                                         BackendCall::DeleteBuffer(vbo),
+                                         BackendCall::DeleteVertexArray(vao)]);
+    }
+
+    // ---- BRIDGES ----
+    // Loop of vertices around the Z axis at some height, counter clockwise
+    // seen from above
+    fn add_ring(model: &mut Model, n_verts: usize, z: f32, start_angle: f32) -> Vec<usize> {
+        (0..n_verts)
+            .map(|index| {
+                let angle = start_angle + 2.0 * std::f32::consts::PI * index as f32 / n_verts as f32;
+                model.add_vert(Vector3::new(angle.cos(), angle.sin(), z));
+                model.n_verts() - 1
+            })
+            .collect()
+    }
+
+    fn add_row(model: &mut Model, xs: &[f32], y: f32) -> Vec<usize> {
+        xs.iter()
+            .map(|x| {
+                model.add_vert(Vector3::new(*x, y, 0.0));
+                model.n_verts() - 1
+            })
+            .collect()
+    }
+
+    fn has_line(model: &Model, vert_a: usize, vert_b: usize) -> bool {
+        (0..model.n_lines()).any(|line_index| {
+            let line = model.line_verts(line_index);
+            line == (vert_a, vert_b) || line == (vert_b, vert_a)
+        })
+    }
+
+    fn share_face(model: &Model, vert_a: usize, vert_b: usize) -> bool {
+        (0..model.n_faces()).any(|face_index| {
+            let face = model.face_verts(face_index);
+            let verts = [face.0, face.1, face.2];
+            verts.contains(&vert_a) && verts.contains(&vert_b)
+        })
+    }
+
+    // Dot of each face normal with the way out from the Z axis at the face
+    fn outward_facing(model: &Model) -> Vec<f32> {
+        model.face_triangles().iter()
+            .map(|corners| {
+                let normal = (corners[1] - corners[0]).cross(corners[2] - corners[0]);
+                let centre = (corners[0] + corners[1] + corners[2]) / 3.0;
+                normal.dot(Vector3::new(centre.x, centre.y, 0.0))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bridge_zips_loops_of_different_sizes() {
+        let mut model = Model::create_headless();
+        let square = add_ring(&mut model, 4, 0.0, std::f32::consts::FRAC_PI_4);
+        let octagon = add_ring(&mut model, 8, 1.0, 0.0);
+
+        model.bridge_loops(&square, &octagon, BridgeSettings::default());
+
+        // One triangle per step along either loop, every vertex is used
+        assert_eq!(model.n_faces(), 4 + 8);
+        for vert_index in square.iter().chain(octagon.iter()) {
+            assert!((0..model.n_faces()).any(|face_index| {
+                let face = model.face_verts(face_index);
+                face.0 == *vert_index || face.1 == *vert_index || face.2 == *vert_index
+            }));
+        }
+
+        // Wound away from the axis, or towards it when flipped
+        assert!(outward_facing(&model).iter().all(|facing| *facing > 0.0));
+
+        let mut flipped = Model::create_headless();
+        let square = add_ring(&mut flipped, 4, 0.0, std::f32::consts::FRAC_PI_4);
+        let octagon = add_ring(&mut flipped, 8, 1.0, 0.0);
+        flipped.bridge_loops(&square, &octagon, BridgeSettings { flip: true, ..BridgeSettings::default() });
+        assert!(outward_facing(&flipped).iter().all(|facing| *facing < 0.0));
+    }
+
+    #[test]
+    fn bridge_pairs_vertices_by_arc_length() {
+        // Three quarters of the way along both rows are (3, 0) and (3, 1)
+        let mut model = Model::create_headless();
+        let row_a = add_row(&mut model, &[0.0, 3.0, 4.0], 0.0);
+        let row_b = add_row(&mut model, &[0.0, 1.0, 2.0, 3.0, 4.0], 1.0);
+
+        model.bridge_loops(&row_a, &row_b, BridgeSettings { closed: false, ..BridgeSettings::default() });
+
+        assert_eq!(model.n_faces(), 2 + 4);
+        assert!(has_line(&model, row_a[0], row_b[0]));
+        assert!(has_line(&model, row_a[2], row_b[4]));
+
+        // The long first step of A faces the first three steps of B
+        assert!(share_face(&model, row_a[1], row_b[3]));
+        assert!(share_face(&model, row_a[0], row_b[2]));
+        assert!(!share_face(&model, row_a[1], row_b[1]));
+        assert!(!share_face(&model, row_a[0], row_b[3]));
+        assert!(!share_face(&model, row_a[2], row_b[2]));
+    }
+
+    #[test]
+    fn bridge_offset_only_rotates_closed_loops() {
+        let mut model = Model::create_headless();
+        let lower = add_ring(&mut model, 4, 0.0, 0.0);
+        let upper = add_ring(&mut model, 4, 1.0, 0.0);
+
+        model.bridge_loops(&lower, &upper, BridgeSettings { offset: 1, ..BridgeSettings::default() });
+
+        assert_eq!(model.n_faces(), 8);
+        for index in 0..4 {
+            assert!(has_line(&model, lower[index], upper[(index + 1) % 4]));
+            assert!(!has_line(&model, lower[index], upper[index]));
+        }
+
+        let mut model = Model::create_headless();
+        let row_a = add_row(&mut model, &[0.0, 1.0, 2.0], 0.0);
+        let row_b = add_row(&mut model, &[0.0, 1.0, 2.0], 1.0);
+
+        model.bridge_loops(&row_a, &row_b, BridgeSettings { closed: false, offset: 1, ..BridgeSettings::default() });
+
+        assert_eq!(model.n_faces(), 4);
+        for index in 0..3 {
+            assert!(has_line(&model, row_a[index], row_b[index]));
+        }
+    }
+}
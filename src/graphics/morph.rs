@@ -0,0 +1,396 @@
+extern crate gl;
+use self::gl::types::*;
+
+use cgmath::Vector3;
+use cgmath::prelude::*;
+
+use std::mem;
+use std::os::raw::c_void;
+
+use super::shader::Shader;
+
+
+// ---- SIZE OF THE WEIGHT ARRAY IN THE SHADERS ----
+// More targets than this are blended on the CPU
+pub const MAX_GPU_MORPH_TARGETS: usize = 8;
+
+// Texture unit of the delta buffer, unit 0 is left for the diffuse texture
+const MORPH_TEXTURE_UNIT: u32 = 1;
+
+// Deltas are stored for every vertex, vertices added after the target was
+// recorded simply don't move
+pub struct MorphTarget {
+    name: String,
+    pos_deltas: Vec<Vector3<f32>>,
+    normal_deltas: Option<Vec<Vector3<f32>>>
+}
+
+#[derive(Clone, Copy)]
+pub struct WeightKey {
+    pub time: f32,
+    pub weight: f32
+}
+
+// Keys are kept sorted by time, the weight is linear between them and held
+// before the first and after the last
+pub struct WeightTrack {
+    target: usize,
+    keys: Vec<WeightKey>
+}
+
+pub struct Morphs {
+    targets: Vec<MorphTarget>,
+    weights: Vec<f32>,
+    tracks: Vec<WeightTrack>,
+    gpu_blending: bool,
+    delta_buffer: u32,
+    delta_texture: u32,
+    delta_len: usize,
+    gpu_n_verts: usize
+}
+
+
+impl MorphTarget {
+    pub fn pos_delta(&self, vert_index: usize) -> Vector3<f32> {
+        self.pos_deltas.get(vert_index).cloned().unwrap_or(Vector3::zero())
+    }
+
+    pub fn normal_delta(&self, vert_index: usize) -> Vector3<f32> {
+        match &self.normal_deltas {
+            Some(deltas) => deltas.get(vert_index).cloned().unwrap_or(Vector3::zero()),
+            None => Vector3::zero()
+        }
+    }
+}
+
+
+impl WeightTrack {
+    // A key at the same time replaces the old one
+    pub fn add_key(&mut self, time: f32, weight: f32) {
+        match self.keys.iter().position(|key| key.time >= time) {
+            Some(key_index) if self.keys[key_index].time == time => {
+                self.keys[key_index].weight = weight;
+            },
+            Some(key_index) => self.keys.insert(key_index, WeightKey { time, weight }),
+            None => self.keys.push(WeightKey { time, weight })
+        }
+    }
+
+    pub fn weight_at(&self, time: f32) -> f32 {
+        if self.keys.is_empty() {
+            return 0.0;
+        }
+
+        match self.keys.iter().position(|key| key.time > time) {
+            Some(0) => self.keys[0].weight,
+            Some(key_index) => {
+                let prev = self.keys[key_index - 1];
+                let next = self.keys[key_index];
+                let fraction = (time - prev.time) / (next.time - prev.time);
+                prev.weight + (next.weight - prev.weight) * fraction
+            },
+            None => self.keys[self.keys.len() - 1].weight
+        }
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.keys.last().map(|key| key.time).unwrap_or(0.0)
+    }
+}
+
+
+impl Morphs {
+    pub fn new() -> Morphs {
+        Morphs {
+            targets: Vec::new(),
+            weights: Vec::new(),
+            tracks: Vec::new(),
+            gpu_blending: false,
+            delta_buffer: 0,
+            delta_texture: 0,
+            delta_len: 0,
+            gpu_n_verts: 0
+        }
+    }
+
+    // -------------------------------------------------------------------------
+    // TARGETS AND WEIGHTS
+    // -------------------------------------------------------------------------
+    pub fn targets(&self) -> &Vec<MorphTarget> {
+        &self.targets
+    }
+
+    pub fn add_target(&mut self,
+                      name: &str,
+                      pos_deltas: Vec<Vector3<f32>>,
+                      normal_deltas: Option<Vec<Vector3<f32>>>) -> usize {
+        self.targets.push(MorphTarget {
+            name: String::from(name),
+            pos_deltas,
+            normal_deltas
+        });
+        self.weights.push(0.0);
+
+        self.targets.len() - 1
+    }
+
+    pub fn set_weight(&mut self, target_index: usize, weight: f32) {
+        self.weights[target_index] = weight;
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.weights.iter().any(|weight| *weight != 0.0)
+    }
+
+    // -------------------------------------------------------------------------
+    // KEYFRAMED WEIGHTS
+    // -------------------------------------------------------------------------
+    // One track per target, created on the first key
+    pub fn add_key(&mut self, target_index: usize, time: f32, weight: f32) {
+        let track_index = match self.tracks.iter().position(|track| track.target == target_index) {
+            Some(track_index) => track_index,
+            None => {
+                self.tracks.push(WeightTrack {
+                    target: target_index,
+                    keys: Vec::new()
+                });
+                self.tracks.len() - 1
+            }
+        };
+
+        self.tracks[track_index].add_key(time, weight);
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.tracks.iter().fold(0.0, |duration, track| duration.max(track.duration()))
+    }
+
+    // Targets without a track keep their weight
+    pub fn evaluate(&mut self, time: f32) {
+        for track in self.tracks.iter() {
+            self.weights[track.target] = track.weight_at(time);
+        }
+    }
+
+    // -------------------------------------------------------------------------
+    // CPU BLENDING
+    // -------------------------------------------------------------------------
+    pub fn pos_offset(&self, vert_index: usize) -> Vector3<f32> {
+        let mut offset = Vector3::zero();
+        for (target, weight) in self.targets.iter().zip(self.weights.iter()) {
+            if *weight != 0.0 {
+                offset += target.pos_delta(vert_index) * *weight;
+            }
+        }
+        offset
+    }
+
+    // -------------------------------------------------------------------------
+    // GPU BLENDING
+    // -------------------------------------------------------------------------
+    // Only possible while the targets fit in the weight array of the shaders
+    pub fn set_gpu_blending(&mut self, gpu_blending: bool) {
+        self.gpu_blending = gpu_blending;
+    }
+
+    pub fn gpu_blending(&self) -> bool {
+        self.gpu_blending
+    }
+
+    pub fn uses_gpu(&self) -> bool {
+        self.gpu_blending && self.targets.len() <= MAX_GPU_MORPH_TARGETS
+    }
+
+    // Two texels per vertex and target, position delta then normal delta. The
+    // buffer is updated in place and only reallocated when its size changes
+    pub(super) fn upload(&mut self, n_verts: usize) {
+        let mut texels = Vec::<f32>::with_capacity(8 * n_verts * self.targets.len());
+        for target in self.targets.iter() {
+            for vert_index in 0..n_verts {
+                let pos_delta = target.pos_delta(vert_index);
+                let normal_delta = target.normal_delta(vert_index);
+                texels.extend_from_slice(&[pos_delta.x, pos_delta.y, pos_delta.z, 0.0,
+                                           normal_delta.x, normal_delta.y, normal_delta.z, 0.0]);
+            }
+        }
+        if texels.is_empty() {
+            texels.extend_from_slice(&[0.0; 8]);
+        }
+
+        unsafe {
+            if self.delta_buffer == 0 {
+                gl::GenBuffers(1, &mut self.delta_buffer);
+                gl::GenTextures(1, &mut self.delta_texture);
+            }
+
+            gl::BindBuffer(gl::TEXTURE_BUFFER, self.delta_buffer);
+            if texels.len() == self.delta_len {
+                gl::BufferSubData
+                   (gl::TEXTURE_BUFFER, 0,
+                   (texels.len() * mem::size_of::<GLfloat>()) as GLsizeiptr,
+                    &texels[0] as *const f32 as *const c_void);
+            } else {
+                gl::BufferData
+                   (gl::TEXTURE_BUFFER,
+                   (texels.len() * mem::size_of::<GLfloat>()) as GLsizeiptr,
+                    &texels[0] as *const f32 as *const c_void,
+                    gl::DYNAMIC_DRAW);
+
+                gl::BindTexture(gl::TEXTURE_BUFFER, self.delta_texture);
+                gl::TexBuffer(gl::TEXTURE_BUFFER, gl::RGBA32F, self.delta_buffer);
+                gl::BindTexture(gl::TEXTURE_BUFFER, 0);
+
+                self.delta_len = texels.len();
+            }
+            gl::BindBuffer(gl::TEXTURE_BUFFER, 0);
+        }
+
+        self.gpu_n_verts = n_verts;
+    }
+
+    // Shaders skip blending when the count is zero. The sampler always gets its
+    // own unit so it never shares one with a 2D sampler of the same program
+    pub fn pass_to_shader(&self, shader: &Shader) {
+        // Shaders that don't blend morphs never read the vertex index
+        if shader.attribute_location("model_vertex").is_none() {
+            return;
+        }
+
+        shader.pass_sampler("morphDeltas", MORPH_TEXTURE_UNIT);
+        if !self.uses_gpu() || self.delta_texture == 0 {
+            shader.pass_int("morphCount", 0);
+            return;
+        }
+
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + MORPH_TEXTURE_UNIT);
+            gl::BindTexture(gl::TEXTURE_BUFFER, self.delta_texture);
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+
+        shader.pass_int("morphCount", self.targets.len() as i32);
+        shader.pass_int("morphVerts", self.gpu_n_verts as i32);
+        shader.pass_float_array("morphWeights", &self.weights);
+    }
+
+    // -------------------------------------------------------------------------
+    // KEEP INDICES VALID WHEN VERTICES ARE REMOVED
+    // -------------------------------------------------------------------------
+    pub(super) fn vert_removed(&mut self, vert_index: usize) {
+        for target in self.targets.iter_mut() {
+            if vert_index < target.pos_deltas.len() {
+                target.pos_deltas.remove(vert_index);
+            }
+            if let Some(deltas) = &mut target.normal_deltas {
+                if vert_index < deltas.len() {
+                    deltas.remove(vert_index);
+                }
+            }
+        }
+    }
+
+    // -------------------------------------------------------------------------
+    // FILE FORMAT
+    // -------------------------------------------------------------------------
+    // Targets only list the vertices that move, as D for positions and N for
+    // normals. Tracks list their keys as time,weight
+    pub(super) fn to_lines(&self) -> Vec<String> {
+        let mut lines = Vec::<String>::new();
+
+        for target in self.targets.iter() {
+            lines.push(format!("Target,{}", target.name));
+            for (vert_index, delta) in target.pos_deltas.iter().enumerate() {
+                if *delta != Vector3::zero() {
+                    lines.push(format!("D,{},{},{},{}", vert_index, delta.x, delta.y, delta.z));
+                }
+            }
+            if let Some(deltas) = &target.normal_deltas {
+                for (vert_index, delta) in deltas.iter().enumerate() {
+                    if *delta != Vector3::zero() {
+                        lines.push(format!("N,{},{},{},{}", vert_index, delta.x, delta.y, delta.z));
+                    }
+                }
+            }
+        }
+
+        for track in self.tracks.iter() {
+            lines.push(format!("Track,{}", track.target));
+            for key in track.keys.iter() {
+                lines.push(format!("K,{},{}", key.time, key.weight));
+            }
+        }
+
+        lines
+    }
+
+    pub(super) fn parse_line(&mut self, line: &str) {
+        let str_vec: Vec<&str> = line.splitn(2, ',').collect();
+        if str_vec.len() != 2 {
+            println!("Wrong morph line: {}", line);
+            return;
+        }
+
+        match str_vec[0] {
+            "Target" => { self.add_target(str_vec[1], Vec::new(), None); },
+            "Track" => match str_vec[1].parse::<usize>() {
+                Ok(target_index) if target_index < self.targets.len() => {
+                    self.tracks.push(WeightTrack {
+                        target: target_index,
+                        keys: Vec::new()
+                    });
+                },
+                _ => println!("Track of a missing target: {}", line)
+            },
+            "D" | "N" => {
+                let values: Vec<f32> = str_vec[1].split(',')
+                    .map(|value| value.parse::<f32>().unwrap_or(0.0))
+                    .collect();
+                let target = match self.targets.last_mut() {
+                    Some(target) => target,
+                    None => {
+                        println!("Morph delta before any target: {}", line);
+                        return;
+                    }
+                };
+                if values.len() != 4 {
+                    println!("Wrong morph line: {}", line);
+                    return;
+                }
+
+                let vert_index = values[0] as usize;
+                let delta = Vector3::new(values[1], values[2], values[3]);
+                let deltas = if str_vec[0] == "D" {
+                    &mut target.pos_deltas
+                } else {
+                    target.normal_deltas.get_or_insert(Vec::new())
+                };
+                if deltas.len() <= vert_index {
+                    deltas.resize(vert_index + 1, Vector3::zero());
+                }
+                deltas[vert_index] = delta;
+            },
+            "K" => {
+                let values: Vec<f32> = str_vec[1].split(',')
+                    .map(|value| value.parse::<f32>().unwrap_or(0.0))
+                    .collect();
+                match self.tracks.last_mut() {
+                    Some(track) if values.len() == 2 => track.add_key(values[0], values[1]),
+                    _ => println!("Wrong morph key: {}", line)
+                }
+            },
+            _ => println!("Unknown morph line: {}", line)
+        }
+    }
+}
+
+
+impl Drop for Morphs {
+    fn drop(&mut self) {
+        if self.delta_buffer != 0 {
+            unsafe {
+                gl::DeleteBuffers(1, &self.delta_buffer as *const GLuint);
+                gl::DeleteTextures(1, &self.delta_texture as *const GLuint);
+            }
+        }
+    }
+}
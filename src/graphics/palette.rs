@@ -0,0 +1,138 @@
+use std::fs::File;
+use std::io::{Write, BufReader, BufRead};
+
+use super::shader::Shader;
+
+
+// ---- SIZE OF THE UNIFORM ARRAY IN THE SHADERS ----
+pub const MAX_PALETTE_SIZE: usize = 32;
+
+pub struct PaletteEntry {
+    pub name: String,
+    pub colour: (f32, f32, f32, f32)
+}
+
+// Faces store the index of their entry
+pub struct Palette {
+    entries: Vec<PaletteEntry>
+}
+
+
+impl Palette {
+    pub fn create_empty() -> Palette {
+        Palette {
+            entries: Vec::new()
+        }
+    }
+
+    // The colours the shader used to have hard-coded
+    pub fn create_default() -> Palette {
+        let mut palette = Palette::create_empty();
+
+        palette.add("grey", (0.4, 0.4, 0.4, 1.0));
+        palette.add("red", (0.7, 0.3, 0.3, 1.0));
+        palette.add("blue", (0.3, 0.3, 0.7, 1.0));
+        palette.add("green", (0.3, 0.7, 0.3, 1.0));
+
+        palette
+    }
+
+    pub fn entries(&self) -> &Vec<PaletteEntry> {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.entries.iter().position(|entry| entry.name == name)
+    }
+
+    // Entries past the shader array size are refused
+    pub fn add(&mut self, name: &str, colour: (f32, f32, f32, f32)) -> Option<usize> {
+        if self.entries.len() >= MAX_PALETTE_SIZE {
+            println!("Palette is full, {} was not added", name);
+            return None;
+        }
+
+        self.entries.push(PaletteEntry {
+            name: String::from(name),
+            colour
+        });
+
+        Some(self.entries.len() - 1)
+    }
+
+    pub fn set_colour(&mut self, index: usize, colour: (f32, f32, f32, f32)) {
+        self.entries[index].colour = colour;
+    }
+
+    // -------------------------------------------------------------------------
+    // PASS TO SHADER
+    // -------------------------------------------------------------------------
+    pub fn pass_to_shader(&self, shader: &Shader) {
+        let colours: Vec<(f32, f32, f32, f32)> =
+            self.entries.iter().map(|entry| entry.colour).collect();
+
+        shader.pass_colour_array("palette", &colours);
+        shader.pass_int("paletteSize", colours.len() as i32);
+    }
+
+    // -------------------------------------------------------------------------
+    // TEXT FORMAT
+    // -------------------------------------------------------------------------
+    // One entry per line as r,g,b,a,name, the same lines go in palette files
+    // and inside models
+    pub(super) fn to_lines(&self) -> Vec<String> {
+        self.entries.iter().map(|entry| {
+            format!("{},{},{},{},{}", entry.colour.0,
+                                      entry.colour.1,
+                                      entry.colour.2,
+                                      entry.colour.3,
+                                      entry.name)
+        }).collect()
+    }
+
+    pub(super) fn parse_line(&mut self, line: &str) {
+        let str_vec: Vec<&str> = line.splitn(5, ',').collect();
+        if str_vec.len() != 5 {
+            println!("Wrong palette line: {}", line);
+            return;
+        }
+
+        let values: Vec<f32> = str_vec[0..4].iter()
+            .map(|value| value.parse::<f32>().unwrap_or(1.0))
+            .collect();
+
+        self.add(str_vec[4], (values[0], values[1], values[2], values[3]));
+    }
+
+    // -------------------------------------------------------------------------
+    // WRITE TO FILE
+    // -------------------------------------------------------------------------
+    pub fn write_to_file(&self, path_to_file: &str) {
+        let mut file = File::create(path_to_file).unwrap();
+
+        for line in self.to_lines() {
+            writeln!(file, "{}", line).expect("Could not write to file");
+        }
+    }
+
+    // -------------------------------------------------------------------------
+    // LOAD FROM FILE
+    // -------------------------------------------------------------------------
+    pub fn load_from_file(&mut self, path_to_file: &str) {
+        match File::open(path_to_file) {
+            Ok(file) => {
+                self.entries = Vec::new();
+
+                let file = BufReader::new(file);
+                for line in file.lines() {
+                    self.parse_line(&line.unwrap());
+                }
+            },
+            Err(_e) => { println!("Could not open {}", path_to_file);}
+        }
+    }
+}
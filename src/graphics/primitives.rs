@@ -0,0 +1,574 @@
+use cgmath::{Vector3};
+use cgmath::prelude::*;
+
+use std::collections::HashMap;
+
+use super::model::Model;
+
+const MATH_PI: f32 = std::f32::consts::PI;
+
+
+// -----------------------------------------------------------------------------
+// SETTINGS SHARED BY ALL GENERATORS
+// -----------------------------------------------------------------------------
+// All primitives are centred at the origin with Z up, faces are wound counter
+// clockwise when seen from the outside
+#[derive(Clone, Copy)]
+pub struct PrimitiveSettings {
+    pub normals: bool,
+    pub lines: bool,
+    pub colour: f32
+}
+
+impl Default for PrimitiveSettings {
+    fn default() -> PrimitiveSettings {
+        PrimitiveSettings {
+            normals: true,
+            lines: true,
+            colour: 0.0
+        }
+    }
+}
+
+
+// -----------------------------------------------------------------------------
+// PLANE GRID
+// -----------------------------------------------------------------------------
+// Grid on the XY plane facing +Z
+pub fn plane_grid(model: &mut Model,
+                  size: (f32, f32),
+                  segments: (usize, usize),
+                  settings: PrimitiveSettings) {
+    let mut mesh = Mesh::new();
+
+    mesh.add_grid(Vector3::new(-size.0 / 2.0, -size.1 / 2.0, 0.0),
+                  Vector3::new(size.0, 0.0, 0.0),
+                  Vector3::new(0.0, size.1, 0.0),
+                  segments);
+
+    mesh.append_to(model, settings);
+}
+
+
+// -----------------------------------------------------------------------------
+// BOX
+// -----------------------------------------------------------------------------
+// Each side is a grid, the sides are welded along the edges. Corners have no
+// single analytic normal so none are given
+pub fn box_mesh(model: &mut Model,
+                size: Vector3<f32>,
+                segments: (usize, usize, usize),
+                settings: PrimitiveSettings) {
+    let mut mesh = Mesh::new();
+
+    let half = size / 2.0;
+    let axis_x = Vector3::new(size.x, 0.0, 0.0);
+    let axis_y = Vector3::new(0.0, size.y, 0.0);
+    let axis_z = Vector3::new(0.0, 0.0, size.z);
+
+    // U x V always points outwards
+    mesh.add_grid(Vector3::new( half.x, -half.y, -half.z),  axis_y, axis_z, (segments.1, segments.2));
+    mesh.add_grid(Vector3::new(-half.x,  half.y, -half.z), -axis_y, axis_z, (segments.1, segments.2));
+    mesh.add_grid(Vector3::new( half.x,  half.y, -half.z), -axis_x, axis_z, (segments.0, segments.2));
+    mesh.add_grid(Vector3::new(-half.x, -half.y, -half.z),  axis_x, axis_z, (segments.0, segments.2));
+    mesh.add_grid(Vector3::new(-half.x, -half.y,  half.z),  axis_x, axis_y, (segments.0, segments.1));
+    mesh.add_grid(Vector3::new(-half.x, -half.y, -half.z),  axis_y, axis_x, (segments.1, segments.0));
+
+    mesh.weld();
+    mesh.normals = Vec::new();
+
+    mesh.append_to(model, settings);
+}
+
+
+// -----------------------------------------------------------------------------
+// UV SPHERE
+// -----------------------------------------------------------------------------
+pub fn uv_sphere(model: &mut Model,
+                 radius: f32,
+                 n_rings: usize,
+                 n_segments: usize,
+                 settings: PrimitiveSettings) {
+    let semi_axes = Vector3::new(radius, radius, radius);
+    ellipsoid(model, semi_axes, semi_axes, n_rings, n_segments, settings);
+}
+
+
+// -----------------------------------------------------------------------------
+// ELLIPSOID
+// -----------------------------------------------------------------------------
+// Semi axes can differ on each side of every axis, like the ship body: the
+// positive ones are used where the coordinate is positive and vice versa
+pub fn ellipsoid(model: &mut Model,
+                 semi_axes_pos: Vector3<f32>,
+                 semi_axes_neg: Vector3<f32>,
+                 n_rings: usize,
+                 n_segments: usize,
+                 settings: PrimitiveSettings) {
+    let n_rings = n_rings.max(2);
+    let n_segments = n_segments.max(3);
+    let mut mesh = Mesh::new();
+
+    let semi_axes = |dir: Vector3<f32>| Vector3::new(
+        if dir.x >= 0.0 { semi_axes_pos.x } else { semi_axes_neg.x },
+        if dir.y >= 0.0 { semi_axes_pos.y } else { semi_axes_neg.y },
+        if dir.z >= 0.0 { semi_axes_pos.z } else { semi_axes_neg.z });
+
+    let top = mesh.add_vert(Vector3::new(0.0, 0.0, semi_axes_pos.z), Vector3::unit_z());
+
+    let mut rings = Vec::<usize>::new();
+    for ring_index in 1..n_rings {
+        let phi = MATH_PI * ring_index as f32 / n_rings as f32;
+        rings.push(mesh.positions.len());
+
+        for segment_index in 0..n_segments {
+            let theta = 2.0 * MATH_PI * segment_index as f32 / n_segments as f32;
+            let dir = Vector3::new(phi.sin() * theta.cos(),
+                                   phi.sin() * theta.sin(),
+                                   phi.cos());
+            let axes = semi_axes(dir);
+            let pos = dir.mul_element_wise(axes);
+
+            mesh.add_vert(pos, pos.div_element_wise(axes.mul_element_wise(axes)));
+        }
+    }
+
+    let bottom = mesh.add_vert(Vector3::new(0.0, 0.0, -semi_axes_neg.z), -Vector3::unit_z());
+
+    mesh.add_fan(top, rings[0], n_segments, false);
+    mesh.add_rings(&rings, n_segments);
+    mesh.add_fan(bottom, rings[rings.len() - 1], n_segments, true);
+
+    mesh.append_to(model, settings);
+}
+
+
+// -----------------------------------------------------------------------------
+// CYLINDER
+// -----------------------------------------------------------------------------
+// Cap rims share the side vertices, so they keep the radial normal
+pub fn cylinder(model: &mut Model,
+                radius: f32,
+                height: f32,
+                n_segments: usize,
+                n_stacks: usize,
+                caps: bool,
+                settings: PrimitiveSettings) {
+    let n_segments = n_segments.max(3);
+    let n_stacks = n_stacks.max(1);
+    let mut mesh = Mesh::new();
+
+    let mut rings = Vec::<usize>::new();
+    for stack_index in (0..n_stacks + 1).rev() {
+        let z_pos = -height / 2.0 + height * stack_index as f32 / n_stacks as f32;
+        rings.push(mesh.positions.len());
+
+        for segment_index in 0..n_segments {
+            let theta = 2.0 * MATH_PI * segment_index as f32 / n_segments as f32;
+            let normal = Vector3::new(theta.cos(), theta.sin(), 0.0);
+            mesh.add_vert(radius * normal + Vector3::new(0.0, 0.0, z_pos), normal);
+        }
+    }
+
+    mesh.add_rings(&rings, n_segments);
+
+    if caps {
+        let top = mesh.add_vert(Vector3::new(0.0, 0.0, height / 2.0), Vector3::unit_z());
+        let bottom = mesh.add_vert(Vector3::new(0.0, 0.0, -height / 2.0), -Vector3::unit_z());
+
+        mesh.add_fan(top, rings[0], n_segments, false);
+        mesh.add_fan(bottom, rings[rings.len() - 1], n_segments, true);
+    }
+
+    mesh.append_to(model, settings);
+}
+
+
+// -----------------------------------------------------------------------------
+// CONE
+// -----------------------------------------------------------------------------
+// Apex on +Z, base on -Z
+pub fn cone(model: &mut Model,
+            radius: f32,
+            height: f32,
+            n_segments: usize,
+            n_stacks: usize,
+            cap: bool,
+            settings: PrimitiveSettings) {
+    let n_segments = n_segments.max(3);
+    let n_stacks = n_stacks.max(1);
+    let mut mesh = Mesh::new();
+
+    let apex = mesh.add_vert(Vector3::new(0.0, 0.0, height / 2.0), Vector3::unit_z());
+
+    let mut rings = Vec::<usize>::new();
+    for stack_index in 1..n_stacks + 1 {
+        let fraction = stack_index as f32 / n_stacks as f32;
+        let z_pos = height / 2.0 - height * fraction;
+        rings.push(mesh.positions.len());
+
+        for segment_index in 0..n_segments {
+            let theta = 2.0 * MATH_PI * segment_index as f32 / n_segments as f32;
+            let pos = Vector3::new(fraction * radius * theta.cos(),
+                                   fraction * radius * theta.sin(),
+                                   z_pos);
+            let normal = Vector3::new(height * theta.cos(),
+                                      height * theta.sin(),
+                                      radius);
+            mesh.add_vert(pos, normal);
+        }
+    }
+
+    mesh.add_fan(apex, rings[0], n_segments, false);
+    mesh.add_rings(&rings, n_segments);
+
+    if cap {
+        let bottom = mesh.add_vert(Vector3::new(0.0, 0.0, -height / 2.0), -Vector3::unit_z());
+        mesh.add_fan(bottom, rings[rings.len() - 1], n_segments, true);
+    }
+
+    mesh.append_to(model, settings);
+}
+
+
+// -----------------------------------------------------------------------------
+// TORUS
+// -----------------------------------------------------------------------------
+// Lies on the XY plane
+pub fn torus(model: &mut Model,
+             major_radius: f32,
+             minor_radius: f32,
+             n_major: usize,
+             n_minor: usize,
+             settings: PrimitiveSettings) {
+    let n_major = n_major.max(3);
+    let n_minor = n_minor.max(3);
+    let mut mesh = Mesh::new();
+
+    for major_index in 0..n_major {
+        let theta = 2.0 * MATH_PI * major_index as f32 / n_major as f32;
+
+        for minor_index in 0..n_minor {
+            let phi = 2.0 * MATH_PI * minor_index as f32 / n_minor as f32;
+            let normal = Vector3::new(phi.cos() * theta.cos(),
+                                      phi.cos() * theta.sin(),
+                                      phi.sin());
+            let centre = Vector3::new(major_radius * theta.cos(),
+                                      major_radius * theta.sin(),
+                                      0.0);
+            mesh.add_vert(centre + minor_radius * normal, normal);
+        }
+    }
+
+    for major_index in 0..n_major {
+        let next_major = (major_index + 1) % n_major;
+
+        for minor_index in 0..n_minor {
+            let next_minor = (minor_index + 1) % n_minor;
+            mesh.add_quad(minor_index + major_index * n_minor,
+                          minor_index + next_major * n_minor,
+                          next_minor + next_major * n_minor,
+                          next_minor + major_index * n_minor);
+        }
+    }
+
+    mesh.append_to(model, settings);
+}
+
+
+// -----------------------------------------------------------------------------
+// CAPSULE
+// -----------------------------------------------------------------------------
+// Cylinder of the given height along Z closed with two hemispheres, so the
+// total length is height + 2 * radius
+pub fn capsule(model: &mut Model,
+               radius: f32,
+               height: f32,
+               n_segments: usize,
+               n_hemisphere_rings: usize,
+               settings: PrimitiveSettings) {
+    let n_segments = n_segments.max(3);
+    let n_hemisphere_rings = n_hemisphere_rings.max(1);
+    let mut mesh = Mesh::new();
+
+    let top = mesh.add_vert(Vector3::new(0.0, 0.0, height / 2.0 + radius), Vector3::unit_z());
+
+    // Each hemisphere goes from its pole to the equator, both equators are
+    // kept so the cylinder section gets its own band of quads
+    let mut rings = Vec::<usize>::new();
+    for ring_index in 1..2 * n_hemisphere_rings + 1 {
+        let (phi, z_offset) = if ring_index <= n_hemisphere_rings {
+            (MATH_PI / 2.0 * ring_index as f32 / n_hemisphere_rings as f32, height / 2.0)
+        } else {
+            (MATH_PI / 2.0 * (ring_index - 1) as f32 / n_hemisphere_rings as f32, -height / 2.0)
+        };
+        rings.push(mesh.positions.len());
+
+        for segment_index in 0..n_segments {
+            let theta = 2.0 * MATH_PI * segment_index as f32 / n_segments as f32;
+            let normal = Vector3::new(phi.sin() * theta.cos(),
+                                      phi.sin() * theta.sin(),
+                                      phi.cos());
+            mesh.add_vert(radius * normal + Vector3::new(0.0, 0.0, z_offset), normal);
+        }
+    }
+
+    let bottom = mesh.add_vert(Vector3::new(0.0, 0.0, -height / 2.0 - radius), -Vector3::unit_z());
+
+    mesh.add_fan(top, rings[0], n_segments, false);
+    mesh.add_rings(&rings, n_segments);
+    mesh.add_fan(bottom, rings[rings.len() - 1], n_segments, true);
+
+    mesh.append_to(model, settings);
+}
+
+
+// -----------------------------------------------------------------------------
+// ICOSPHERE
+// -----------------------------------------------------------------------------
+pub fn icosphere(model: &mut Model,
+                 radius: f32,
+                 subdivisions: usize,
+                 settings: PrimitiveSettings) {
+    let mut mesh = Mesh::new();
+
+    let t = (1.0 + 5.0_f32.sqrt()) / 2.0;
+    let base_verts = [
+        (-1.0,  t,  0.0), ( 1.0,  t,  0.0), (-1.0, -t,  0.0), ( 1.0, -t,  0.0),
+        ( 0.0, -1.0,  t), ( 0.0,  1.0,  t), ( 0.0, -1.0, -t), ( 0.0,  1.0, -t),
+        ( t,  0.0, -1.0), ( t,  0.0,  1.0), (-t,  0.0, -1.0), (-t,  0.0,  1.0)
+    ];
+    let mut faces: Vec<(usize, usize, usize)> = vec![
+        (0, 11, 5), (0, 5, 1), (0, 1, 7), (0, 7, 10), (0, 10, 11),
+        (1, 5, 9), (5, 11, 4), (11, 10, 2), (10, 7, 6), (7, 1, 8),
+        (3, 9, 4), (3, 4, 2), (3, 2, 6), (3, 6, 8), (3, 8, 9),
+        (4, 9, 5), (2, 4, 11), (6, 2, 10), (8, 6, 7), (9, 8, 1)
+    ];
+
+    for vert in base_verts.iter() {
+        let normal = Vector3::new(vert.0, vert.1, vert.2).normalize();
+        mesh.add_vert(radius * normal, normal);
+    }
+
+    // Split every triangle in four, reusing the midpoints of shared edges
+    for _ in 0..subdivisions {
+        let mut midpoints = HashMap::<(usize, usize), usize>::new();
+        let mut new_faces = Vec::<(usize, usize, usize)>::new();
+
+        for face in faces.iter() {
+            let mid_01 = mesh.midpoint_on_sphere(&mut midpoints, face.0, face.1, radius);
+            let mid_12 = mesh.midpoint_on_sphere(&mut midpoints, face.1, face.2, radius);
+            let mid_20 = mesh.midpoint_on_sphere(&mut midpoints, face.2, face.0, radius);
+
+            new_faces.push((face.0, mid_01, mid_20));
+            new_faces.push((face.1, mid_12, mid_01));
+            new_faces.push((face.2, mid_20, mid_12));
+            new_faces.push((mid_01, mid_12, mid_20));
+        }
+
+        faces = new_faces;
+    }
+
+    for face in faces.iter() {
+        mesh.add_tri(face.0, face.1, face.2);
+        mesh.add_line(face.0, face.1);
+        mesh.add_line(face.1, face.2);
+        mesh.add_line(face.2, face.0);
+    }
+
+    mesh.append_to(model, settings);
+}
+
+
+// -----------------------------------------------------------------------------
+// INTERMEDIATE MESH USED BY THE GENERATORS
+// -----------------------------------------------------------------------------
+pub(super) struct Mesh {
+    pub(super) positions: Vec<Vector3<f32>>,
+    pub(super) normals: Vec<Vector3<f32>>,
+    pub(super) faces: Vec<(usize, usize, usize)>,
+    pub(super) lines: Vec<(usize, usize)>
+}
+
+impl Mesh {
+    pub(super) fn new() -> Mesh {
+        Mesh {
+            positions: Vec::new(),
+            normals: Vec::new(),
+            faces: Vec::new(),
+            lines: Vec::new()
+        }
+    }
+
+    pub(super) fn add_vert(&mut self, pos: Vector3<f32>, normal: Vector3<f32>) -> usize {
+        self.positions.push(pos);
+        self.normals.push(normal);
+        self.positions.len() - 1
+    }
+
+    pub(super) fn add_tri(&mut self, vert_0: usize, vert_1: usize, vert_2: usize) {
+        self.faces.push((vert_0, vert_1, vert_2));
+    }
+
+    // Lines are only stored once whatever their direction
+    pub(super) fn add_line(&mut self, vert_0: usize, vert_1: usize) {
+        if !self.lines.contains(&(vert_0, vert_1)) &&
+           !self.lines.contains(&(vert_1, vert_0)) {
+            self.lines.push((vert_0, vert_1));
+        }
+    }
+
+    // Vertices counter clockwise seen from the outside, the diagonal gets no
+    // line so the wireframe shows quads
+    pub(super) fn add_quad(&mut self, vert_0: usize, vert_1: usize, vert_2: usize, vert_3: usize) {
+        self.add_tri(vert_0, vert_1, vert_2);
+        self.add_tri(vert_0, vert_2, vert_3);
+
+        self.add_line(vert_0, vert_1);
+        self.add_line(vert_1, vert_2);
+        self.add_line(vert_2, vert_3);
+        self.add_line(vert_3, vert_0);
+    }
+
+    // Grid from the origin along U and V, it faces U x V
+    pub(super) fn add_grid(&mut self,
+                origin: Vector3<f32>,
+                axis_u: Vector3<f32>,
+                axis_v: Vector3<f32>,
+                segments: (usize, usize)) {
+        let n_u = segments.0.max(1) + 1;
+        let n_v = segments.1.max(1) + 1;
+        let normal = axis_u.cross(axis_v).normalize();
+        let first = self.positions.len();
+
+        for v_index in 0..n_v {
+            for u_index in 0..n_u {
+                let pos = origin +
+                          axis_u * (u_index as f32 / (n_u - 1) as f32) +
+                          axis_v * (v_index as f32 / (n_v - 1) as f32);
+                self.add_vert(pos, normal);
+            }
+        }
+
+        for v_index in 1..n_v {
+            for u_index in 1..n_u {
+                self.add_quad(first + u_index - 1 + (v_index - 1) * n_u,
+                              first + u_index + (v_index - 1) * n_u,
+                              first + u_index + v_index * n_u,
+                              first + u_index - 1 + v_index * n_u);
+            }
+        }
+    }
+
+    // Closed rings of n_segments vertices going around +Z, ordered from top
+    // to bottom
+    pub(super) fn add_rings(&mut self, rings: &[usize], n_segments: usize) {
+        for ring_index in 1..rings.len() {
+            let upper = rings[ring_index - 1];
+            let lower = rings[ring_index];
+
+            for segment_index in 0..n_segments {
+                let next_segment = (segment_index + 1) % n_segments;
+                self.add_quad(lower + segment_index,
+                              lower + next_segment,
+                              upper + next_segment,
+                              upper + segment_index);
+            }
+        }
+    }
+
+    // Triangles from a pole to a ring, flipped for poles below the ring
+    pub(super) fn add_fan(&mut self, pole: usize, ring: usize, n_segments: usize, flip: bool) {
+        for segment_index in 0..n_segments {
+            let next_segment = (segment_index + 1) % n_segments;
+
+            if flip {
+                self.add_tri(pole, ring + next_segment, ring + segment_index);
+            } else {
+                self.add_tri(pole, ring + segment_index, ring + next_segment);
+            }
+
+            self.add_line(pole, ring + segment_index);
+            self.add_line(ring + segment_index, ring + next_segment);
+        }
+    }
+
+    pub(super) fn midpoint_on_sphere(&mut self,
+                          midpoints: &mut HashMap<(usize, usize), usize>,
+                          vert_0: usize,
+                          vert_1: usize,
+                          radius: f32) -> usize {
+        let key = (vert_0.min(vert_1), vert_0.max(vert_1));
+
+        match midpoints.get(&key) {
+            Some(index) => *index,
+            None => {
+                let normal = (self.positions[vert_0] + self.positions[vert_1]).normalize();
+                let index = self.add_vert(radius * normal, normal);
+                midpoints.insert(key, index);
+                index
+            }
+        }
+    }
+
+    // Merge vertices sharing the same position, used where separate grids meet
+    pub(super) fn weld(&mut self) {
+        let mut remap = Vec::<usize>::new();
+        let mut positions = Vec::<Vector3<f32>>::new();
+        let mut normals = Vec::<Vector3<f32>>::new();
+
+        for (index, pos) in self.positions.iter().enumerate() {
+            match positions.iter().position(|other| (other - pos).magnitude() < 1e-5) {
+                Some(existing) => remap.push(existing),
+                None => {
+                    positions.push(*pos);
+                    normals.push(self.normals[index]);
+                    remap.push(positions.len() - 1);
+                }
+            }
+        }
+
+        let faces = self.faces.iter()
+            .map(|face| (remap[face.0], remap[face.1], remap[face.2]))
+            .collect();
+        let lines: Vec<(usize, usize)> = self.lines.iter()
+            .map(|line| (remap[line.0], remap[line.1]))
+            .collect();
+
+        self.positions = positions;
+        self.normals = normals;
+        self.faces = faces;
+        self.lines = Vec::new();
+        for line in lines.iter() {
+            self.add_line(line.0, line.1);
+        }
+    }
+
+    // Returns the index of the first vertex in the model
+    pub(super) fn append_to(&self, model: &mut Model, settings: PrimitiveSettings) -> usize {
+        let index_offset = model.n_verts();
+
+        for (index, pos) in self.positions.iter().enumerate() {
+            if settings.normals && index < self.normals.len() {
+                model.add_vert_with_normal(*pos, self.normals[index]);
+            } else {
+                model.add_vert(*pos);
+            }
+        }
+
+        if settings.lines {
+            for line in self.lines.iter() {
+                model.add_line(&vec![line.0 + index_offset,
+                                     line.1 + index_offset]);
+            }
+        }
+
+        for face in self.faces.iter() {
+            model.add_face(&vec![face.0 + index_offset,
+                                 face.1 + index_offset,
+                                 face.2 + index_offset], settings.colour);
+        }
+
+        index_offset
+    }
+}
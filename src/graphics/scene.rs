@@ -0,0 +1,468 @@
+use cgmath::{Matrix3, Matrix4, Vector3, Rad};
+use cgmath::prelude::*;
+
+use std::fs::File;
+use std::io::{Write, BufReader, BufRead};
+
+use super::model::Model;
+use super::shader::Shader;
+use super::camera::Camera;
+use super::lod::*;
+use super::lighting::Lighting;
+
+
+// ---- LOCAL TRANSFORM OF A NODE ----
+// Rotation uses the same Z * Y * X angles as the camera attitude
+#[derive(Clone, Copy)]
+pub struct Transform {
+    pub translation: Vector3<f32>,
+    pub rotation: Vector3<f32>,
+    pub scale: Vector3<f32>
+}
+
+pub struct SceneNode {
+    pub name: String,
+    pub transform: Transform,
+    pub model: Option<usize>,
+    pub lod: Option<usize>,
+    parent: Option<usize>,
+    lod_states: Vec<LodState>
+}
+
+// Models are shared assets, many nodes can point at the same one. Nodes with
+// a LOD set draw one of its models instead of their own
+pub struct Scene {
+    models: Vec<Model>,
+    model_paths: Vec<String>,
+    nodes: Vec<SceneNode>,
+    lod_sets: Vec<LodSet>,
+    pub lighting: Lighting,
+    pub lod_debug_tint: bool
+}
+
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform {
+            translation: Vector3::zero(),
+            rotation: Vector3::zero(),
+            scale: Vector3::new(1.0, 1.0, 1.0)
+        }
+    }
+
+    pub fn matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.translation)
+      * Matrix4::from_angle_z(Rad(self.rotation.z))
+      * Matrix4::from_angle_y(Rad(self.rotation.y))
+      * Matrix4::from_angle_x(Rad(self.rotation.x))
+      * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+}
+
+
+impl Scene {
+    pub fn new() -> Scene {
+        Scene {
+            models: Vec::new(),
+            model_paths: Vec::new(),
+            nodes: Vec::new(),
+            lod_sets: Vec::new(),
+            lighting: Lighting::create_default(),
+            lod_debug_tint: false
+        }
+    }
+
+    // -------------------------------------------------------------------------
+    // ASSETS
+    // -------------------------------------------------------------------------
+    // Models are loaded once per path
+    pub fn load_model(&mut self, path_to_file: &str) -> usize {
+        match self.find_model(path_to_file) {
+            Some(model_index) => model_index,
+            None => {
+                let mut model = Model::create_empty();
+                model.load_from_file(path_to_file);
+                model.update_gpu_data();
+                self.add_model(model, path_to_file)
+            }
+        }
+    }
+
+    // The path is where the model is saved to and loaded from with the scene
+    pub fn add_model(&mut self, model: Model, path_to_file: &str) -> usize {
+        self.models.push(model);
+        self.model_paths.push(String::from(path_to_file));
+        self.models.len() - 1
+    }
+
+    pub fn find_model(&self, path_to_file: &str) -> Option<usize> {
+        self.model_paths.iter().position(|path| path == path_to_file)
+    }
+
+    pub fn model(&self, model_index: usize) -> &Model {
+        &self.models[model_index]
+    }
+
+    pub fn model_mut(&mut self, model_index: usize) -> &mut Model {
+        &mut self.models[model_index]
+    }
+
+    // -------------------------------------------------------------------------
+    // NODES
+    // -------------------------------------------------------------------------
+    pub fn nodes(&self) -> &Vec<SceneNode> {
+        &self.nodes
+    }
+
+    pub fn find_node(&self, name: &str) -> Option<usize> {
+        self.nodes.iter().position(|node| node.name == name)
+    }
+
+    pub fn add_node(&mut self,
+                    name: &str,
+                    parent: Option<usize>,
+                    model: Option<usize>,
+                    transform: Transform) -> usize {
+        self.nodes.push(SceneNode {
+            name: String::from(name),
+            transform,
+            model,
+            lod: None,
+            parent: None,
+            lod_states: Vec::new()
+        });
+
+        let node_index = self.nodes.len() - 1;
+        self.set_parent(node_index, parent);
+
+        node_index
+    }
+
+    // Refuses parents that would create a cycle
+    pub fn set_parent(&mut self, node_index: usize, parent: Option<usize>) {
+        let mut ancestor = parent;
+        while let Some(ancestor_index) = ancestor {
+            if ancestor_index == node_index || ancestor_index >= self.nodes.len() {
+                println!("Invalid parent for node {}", self.nodes[node_index].name);
+                return;
+            }
+            ancestor = self.nodes[ancestor_index].parent;
+        }
+
+        self.nodes[node_index].parent = parent;
+    }
+
+    // Children of the removed node are attached to its parent
+    pub fn remove_node(&mut self, node_index: usize) {
+        let parent = self.nodes[node_index].parent;
+        self.nodes.remove(node_index);
+
+        for node in self.nodes.iter_mut() {
+            if node.parent == Some(node_index) {
+                node.parent = parent;
+            }
+            if let Some(parent_index) = node.parent {
+                if parent_index > node_index {
+                    node.parent = Some(parent_index - 1);
+                }
+            }
+        }
+    }
+
+    pub fn world_matrix(&self, node_index: usize) -> Matrix4<f32> {
+        let node = &self.nodes[node_index];
+        match node.parent {
+            Some(parent_index) => self.world_matrix(parent_index) * node.transform.matrix(),
+            None => node.transform.matrix()
+        }
+    }
+
+    // -------------------------------------------------------------------------
+    // LEVELS OF DETAIL
+    // -------------------------------------------------------------------------
+    pub fn lod_sets(&self) -> &Vec<LodSet> {
+        &self.lod_sets
+    }
+
+    pub fn lod_set_mut(&mut self, lod_index: usize) -> &mut LodSet {
+        &mut self.lod_sets[lod_index]
+    }
+
+    pub fn set_lod(&mut self, node_index: usize, lod: Option<usize>) {
+        self.nodes[node_index].lod = lod;
+    }
+
+    pub fn add_lod_set(&mut self, lod_set: LodSet) -> usize {
+        self.lod_sets.push(lod_set);
+        self.lod_sets.len() - 1
+    }
+
+    // Pick the level of every node from the size of the bounding sphere of
+    // its most detailed model as seen by the camera. Every view has its own
+    // levels since the same node can be near in one and far in another
+    pub fn update_lods(&mut self, view_index: usize, camera: &Camera, delta_time: f32) {
+        for node_index in 0..self.nodes.len() {
+            let lod_index = match self.nodes[node_index].lod {
+                Some(lod_index) if lod_index < self.lod_sets.len() => lod_index,
+                _ => continue
+            };
+            let lod_set = &self.lod_sets[lod_index];
+            if lod_set.levels.is_empty() {
+                continue;
+            }
+
+            let world_mat = self.world_matrix(node_index);
+            let (centre, radius) = self.models[lod_set.levels[0].model].bounding_sphere();
+            let scale = world_mat.x.truncate().magnitude()
+                .max(world_mat.y.truncate().magnitude())
+                .max(world_mat.z.truncate().magnitude());
+            let size = camera.projected_diameter((world_mat * centre.extend(1.0)).truncate(),
+                                                 radius * scale);
+
+            let lod_states = &mut self.nodes[node_index].lod_states;
+            if lod_states.len() <= view_index {
+                lod_states.resize(view_index + 1, LodState::new());
+            }
+            lod_states[view_index].update(lod_set, size, delta_time);
+        }
+    }
+
+    // -------------------------------------------------------------------------
+    // RENDER
+    // -------------------------------------------------------------------------
+    pub fn render_solid(&self, shader: &Shader, proj_view_mat: &Matrix4<f32>, view_index: usize) {
+        self.render(shader, proj_view_mat, view_index, |_model_index, model| model.render_solid());
+    }
+
+    // Draws every node with the model it shows in the view. The model index
+    // lets the caller treat some models differently, like the edited one.
+    // Shaders only get the uniforms they declare, the depth and outline ones
+    // go without most of them
+    pub fn render<F>(&self, shader: &Shader, proj_view_mat: &Matrix4<f32>, view_index: usize, draw: F)
+      where F: Fn(usize, &Model) {
+        for (node_index, node) in self.nodes.iter().enumerate() {
+            let draws = match node.lod.and_then(|lod_index| self.lod_sets.get(lod_index)) {
+                Some(lod_set) => node.lod_states.get(view_index)
+                    .cloned()
+                    .unwrap_or_else(LodState::new)
+                    .draws(lod_set),
+                None => match node.model {
+                    Some(model_index) => vec![LodDraw {
+                        level: 0,
+                        model: model_index,
+                        fade: 0.0,
+                        fade_in: false
+                    }],
+                    None => Vec::new()
+                }
+            };
+
+            let model_mat = self.world_matrix(node_index);
+            for lod_draw in draws.iter() {
+                let model = &self.models[lod_draw.model];
+
+                if shader.has_uniform("transMat") {
+                    shader.pass_matrix("transMat", &(proj_view_mat * model_mat));
+                }
+                if shader.has_uniform("modelMat") {
+                    shader.pass_matrix("modelMat", &model_mat);
+                }
+                if shader.has_uniform("normalMat") {
+                    shader.pass_matrix3("normalMat", &normal_matrix(&model_mat));
+                }
+                if shader.has_uniform("lodFade") {
+                    shader.pass_float("lodFade", lod_draw.fade);
+                    shader.pass_int("lodFadeIn", lod_draw.fade_in as i32);
+                    if self.lod_debug_tint && node.lod.is_some() {
+                        shader.pass_colour("lodTint", lod_tint(lod_draw.level));
+                    } else {
+                        shader.pass_colour("lodTint", (0.0, 0.0, 0.0, 0.0));
+                    }
+                }
+                if shader.has_uniform("paletteSize") {
+                    model.palette().pass_to_shader(shader);
+                }
+                model.morphs().pass_to_shader(shader);
+                draw(lod_draw.model, model);
+            }
+        }
+    }
+
+    // -------------------------------------------------------------------------
+    // WRITE TO FILE
+    // -------------------------------------------------------------------------
+    // Models are saved as well, each to its own path
+    pub fn write_to_file(&self, path_to_file: &str) {
+        let mut file = File::create(path_to_file).unwrap();
+
+        writeln!(file, "Models").expect("Could not write to file");
+        for (model_index, path) in self.model_paths.iter().enumerate() {
+            writeln!(file, "{}", path).expect("Could not write to file");
+            self.models[model_index].write_to_file(path);
+        }
+
+        writeln!(file, "Lods").expect("Could not write to file");
+        for lod_set in self.lod_sets.iter() {
+            writeln!(file, "{}", lod_set.to_line()).expect("Could not write to file");
+        }
+
+        writeln!(file, "Nodes").expect("Could not write to file");
+        for node in self.nodes.iter() {
+            let transform = &node.transform;
+            writeln!(file, "{},{},{},{},{},{},{},{},{},{},{},{}",
+                   option_to_str(node.parent),
+                   option_to_str(node.model),
+                   transform.translation.x,
+                   transform.translation.y,
+                   transform.translation.z,
+                   transform.rotation.x,
+                   transform.rotation.y,
+                   transform.rotation.z,
+                   transform.scale.x,
+                   transform.scale.y,
+                   transform.scale.z,
+                   node.name).expect("Could not write to file");
+        }
+
+        writeln!(file, "NodeLods").expect("Could not write to file");
+        for (node_index, node) in self.nodes.iter().enumerate() {
+            if let Some(lod_index) = node.lod {
+                writeln!(file, "{},{}", node_index, lod_index).expect("Could not write to file");
+            }
+        }
+
+        writeln!(file, "Lighting").expect("Could not write to file");
+        for line in self.lighting.to_lines() {
+            writeln!(file, "{}", line).expect("Could not write to file");
+        }
+    }
+
+    // -------------------------------------------------------------------------
+    // LOAD FROM FILE
+    // -------------------------------------------------------------------------
+    // Scenes saved before lighting was added keep the default one
+    pub fn load_from_file(&mut self, path_to_file: &str) {
+        match File::open(path_to_file) {
+            Ok(file) => {
+                self.models = Vec::new();
+                self.model_paths = Vec::new();
+                self.nodes = Vec::new();
+                self.lod_sets = Vec::new();
+                self.lighting = Lighting::create_default();
+
+                let file = BufReader::new(file);
+
+                let mut mode = 0;
+                let mut parents = Vec::<Option<usize>>::new();
+
+                for line in file.lines() {
+                    let actual_line = line.unwrap();
+                    if actual_line == "Models" {
+                        mode = 1;
+                        continue;
+                    }
+                    if actual_line == "Nodes" {
+                        mode = 2;
+                        continue;
+                    }
+                    if actual_line == "Lods" {
+                        mode = 3;
+                        continue;
+                    }
+                    if actual_line == "NodeLods" {
+                        mode = 4;
+                        continue;
+                    }
+                    if actual_line == "Lighting" {
+                        self.lighting = Lighting::create_empty();
+                        mode = 5;
+                        continue;
+                    }
+
+                    match mode {
+                        1 => { self.load_model(&actual_line); },
+                        2 => {
+                            // The name goes last so it can contain commas
+                            let str_vec: Vec<&str> = actual_line.splitn(12, ',').collect();
+                            if str_vec.len() != 12 {
+                                println!("Wrong node line: {}", actual_line);
+                                continue;
+                            }
+
+                            let values: Vec<f32> = str_vec[2..11].iter()
+                                .map(|value| value.parse::<f32>().unwrap())
+                                .collect();
+                            let transform = Transform {
+                                translation: Vector3::new(values[0], values[1], values[2]),
+                                rotation: Vector3::new(values[3], values[4], values[5]),
+                                scale: Vector3::new(values[6], values[7], values[8])
+                            };
+
+                            let model = str_to_option(str_vec[1])
+                                .filter(|model_index| *model_index < self.models.len());
+
+                            parents.push(str_to_option(str_vec[0]));
+                            self.add_node(str_vec[11], None, model, transform);
+                        },
+                        3 => match LodSet::from_line(&actual_line) {
+                            Some(lod_set) => { self.add_lod_set(lod_set); },
+                            None => println!("Wrong LOD line: {}", actual_line)
+                        },
+                        4 => {
+                            let str_vec: Vec<&str> = actual_line.split(',').collect();
+                            let node_index = str_to_option(str_vec[0]);
+                            let lod_index = str_vec.get(1).and_then(|value| str_to_option(value));
+                            match (node_index, lod_index) {
+                                (Some(node_index), Some(lod_index)) if node_index < self.nodes.len() => {
+                                    self.nodes[node_index].lod = Some(lod_index);
+                                },
+                                _ => println!("Wrong node LOD line: {}", actual_line)
+                            }
+                        },
+                        5 => {
+                            let known = self.lighting.read_line(&actual_line);
+                            if !known {
+                                println!("Wrong lighting line: {}", actual_line);
+                            }
+                        },
+                        _ => ()
+                    }
+                }
+
+                // Parents can come after their children in the file
+                for (node_index, parent) in parents.into_iter().enumerate() {
+                    if parent.is_some() {
+                        self.set_parent(node_index, parent);
+                    }
+                }
+            },
+            Err(_e) => { println!("Could not open {}", path_to_file);}
+        }
+    }
+}
+
+
+// -----------------------------------------------------------------------------
+// MISC FUNCTIONS
+// -----------------------------------------------------------------------------
+// Inverse transpose of the upper 3x3, so normals stay perpendicular under
+// non-uniform scales
+pub(super) fn normal_matrix(model_mat: &Matrix4<f32>) -> Matrix3<f32> {
+    match Matrix3::from_cols(model_mat.x.truncate(),
+                             model_mat.y.truncate(),
+                             model_mat.z.truncate()).invert() {
+        Some(inverse) => inverse.transpose(),
+        None => Matrix3::identity()
+    }
+}
+
+fn option_to_str(value: Option<usize>) -> String {
+    match value {
+        Some(index) => index.to_string(),
+        None => String::from("-")
+    }
+}
+
+fn str_to_option(value: &str) -> Option<usize> {
+    value.parse::<usize>().ok()
+}
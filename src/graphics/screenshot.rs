@@ -0,0 +1,122 @@
+extern crate gl;
+
+extern crate image;
+
+use std::fs;
+use std::os::raw::c_void;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+
+// ---- WHERE SCREENSHOTS GO ----
+pub const SCREENSHOT_DIR: &str = "screenshots";
+
+
+// -----------------------------------------------------------------------------
+// READ BACK FROM THE GPU
+// -----------------------------------------------------------------------------
+// The whole window as it is before swapping, so after everything is drawn
+pub fn read_back_buffer(size: (i32, i32)) -> image::RgbaImage {
+    let mut data = vec![0u8; (size.0 * size.1 * 4) as usize];
+
+    unsafe {
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+        gl::ReadBuffer(gl::BACK);
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::ReadPixels
+           (0, 0, size.0, size.1,
+            gl::RGBA, gl::UNSIGNED_BYTE,
+            data.as_mut_ptr() as *mut c_void);
+    }
+
+    to_image(size.0 as u32, size.1 as u32, data)
+}
+
+// Colour texture of a framebuffer, alpha comes back as 1 for RGB textures
+pub(super) fn read_texture(texture_id: u32, size: (u32, u32)) -> image::RgbaImage {
+    let mut data = vec![0u8; (size.0 * size.1 * 4) as usize];
+
+    unsafe {
+        gl::BindTexture(gl::TEXTURE_2D, texture_id);
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::GetTexImage
+           (gl::TEXTURE_2D, 0,
+            gl::RGBA, gl::UNSIGNED_BYTE,
+            data.as_mut_ptr() as *mut c_void);
+        gl::BindTexture(gl::TEXTURE_2D, 0);
+    }
+
+    to_image(size.0, size.1, data)
+}
+
+// GL rows start at the bottom
+fn to_image(width: u32, height: u32, data: Vec<u8>) -> image::RgbaImage {
+    let image = image::ImageBuffer::from_raw(width, height, data).unwrap();
+    image::imageops::flip_vertical(&image)
+}
+
+
+// -----------------------------------------------------------------------------
+// SAVING
+// -----------------------------------------------------------------------------
+// Every pixel becomes a square of factor x factor pixels, which keeps the
+// camera output sharp
+pub fn upscale(image: &image::RgbaImage, factor: u32) -> image::RgbaImage {
+    let factor = factor.max(1);
+    image::ImageBuffer::from_fn(image.width() * factor, image.height() * factor, |x, y| {
+        *image.get_pixel(x / factor, y / factor)
+    })
+}
+
+// Saved in the directory under a UTC timestamp, the path is returned so it
+// can be shown
+pub fn save_screenshot(image: &image::RgbaImage, dir: &str) -> Option<String> {
+    if let Err(e) = fs::create_dir_all(dir) {
+        println!("Could not create {}: {}", dir, e);
+        return None;
+    }
+
+    let path = screenshot_path(dir);
+    match image.save(&path) {
+        Ok(()) => {
+            println!("Saved {}", path);
+            Some(path)
+        },
+        Err(e) => {
+            println!("Could not write {}: {}", path, e);
+            None
+        }
+    }
+}
+
+// `dir/screenshot_YYYY-MM-DD_HH-MM-SS_mmm.png`, the milliseconds keep two
+// screenshots in the same second apart
+pub fn screenshot_path(dir: &str) -> String {
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let secs_of_day = secs % 86400;
+
+    format!("{}/screenshot_{:04}-{:02}-{:02}_{:02}-{:02}-{:02}_{:03}.png",
+            dir, year, month, day,
+            secs_of_day / 3600, (secs_of_day / 60) % 60, secs_of_day % 60,
+            since_epoch.subsec_millis())
+}
+
+
+// -----------------------------------------------------------------------------
+// MISC FUNCTIONS
+// -----------------------------------------------------------------------------
+// Date of a number of days since 1970-01-01, Howard Hinnant's algorithm
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = if z >= 0 { z } else { z - 146096 } / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
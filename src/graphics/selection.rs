@@ -0,0 +1,308 @@
+use cgmath::{Vector2};
+
+
+// ---- WHAT KIND OF ELEMENT IS BEING SELECTED ----
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SelectMode {
+    Vertex,
+    Edge,
+    Face
+}
+
+// ---- HOW A NEW SET OF HITS IS COMBINED WITH THE CURRENT ONE ----
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SelectOp {
+    Replace,
+    Add,
+    Subtract,
+    Toggle
+}
+
+// ---- SCREEN SPACE REGIONS ----
+pub enum SelectRegion {
+    Rect(Vector2<f32>, Vector2<f32>),
+    Lasso(Vec<Vector2<f32>>)
+}
+
+pub struct Selection {
+    mode: SelectMode,
+    verts: Vec<usize>,
+    lines: Vec<usize>,
+    faces: Vec<usize>
+}
+
+
+impl SelectMode {
+    pub fn next(self) -> SelectMode {
+        match self {
+            SelectMode::Vertex => SelectMode::Edge,
+            SelectMode::Edge => SelectMode::Face,
+            SelectMode::Face => SelectMode::Vertex
+        }
+    }
+}
+
+
+impl SelectRegion {
+    pub fn contains(&self, point: Vector2<f32>) -> bool {
+        match self {
+            SelectRegion::Rect(corner_a, corner_b) => {
+                let min_x = corner_a.x.min(corner_b.x);
+                let max_x = corner_a.x.max(corner_b.x);
+                let min_y = corner_a.y.min(corner_b.y);
+                let max_y = corner_a.y.max(corner_b.y);
+
+                point.x >= min_x && point.x <= max_x &&
+                point.y >= min_y && point.y <= max_y
+            },
+            SelectRegion::Lasso(points) => {
+                // Even-odd rule, the polygon is closed implicitly
+                if points.len() < 3 {
+                    return false;
+                }
+
+                let mut inside = false;
+                let mut prev = points[points.len() - 1];
+                for curr in points.iter() {
+                    if (curr.y > point.y) != (prev.y > point.y) {
+                        let x_cross = curr.x + (point.y - curr.y) *
+                                      (prev.x - curr.x) / (prev.y - curr.y);
+                        if point.x < x_cross {
+                            inside = !inside;
+                        }
+                    }
+                    prev = *curr;
+                }
+
+                inside
+            }
+        }
+    }
+}
+
+
+impl Selection {
+    pub fn new() -> Selection {
+        Selection {
+            mode: SelectMode::Vertex,
+            verts: Vec::new(),
+            lines: Vec::new(),
+            faces: Vec::new()
+        }
+    }
+
+    pub fn mode(&self) -> SelectMode {
+        self.mode
+    }
+
+    // Only changes the mode, converting the current selection is up to the
+    // model since it knows about the connectivity
+    pub fn set_mode(&mut self, mode: SelectMode) {
+        self.mode = mode;
+    }
+
+    pub fn verts(&self) -> &Vec<usize> {
+        &self.verts
+    }
+
+    pub fn lines(&self) -> &Vec<usize> {
+        &self.lines
+    }
+
+    pub fn faces(&self) -> &Vec<usize> {
+        &self.faces
+    }
+
+    pub fn clear(&mut self) {
+        self.verts = Vec::new();
+        self.lines = Vec::new();
+        self.faces = Vec::new();
+    }
+
+    // -------------------------------------------------------------------------
+    // COMBINE HITS WITH THE SET OF THE CURRENT MODE
+    // -------------------------------------------------------------------------
+    pub fn apply(&mut self, hits: &[usize], op: SelectOp) {
+        let set = match self.mode {
+            SelectMode::Vertex => &mut self.verts,
+            SelectMode::Edge => &mut self.lines,
+            SelectMode::Face => &mut self.faces
+        };
+
+        apply_op(set, hits, op);
+    }
+
+    pub fn apply_verts(&mut self, hits: &[usize], op: SelectOp) {
+        apply_op(&mut self.verts, hits, op);
+    }
+
+    pub fn apply_lines(&mut self, hits: &[usize], op: SelectOp) {
+        apply_op(&mut self.lines, hits, op);
+    }
+
+    pub fn apply_faces(&mut self, hits: &[usize], op: SelectOp) {
+        apply_op(&mut self.faces, hits, op);
+    }
+
+    pub fn contains_vert(&self, vert_index: usize) -> bool {
+        self.verts.contains(&vert_index)
+    }
+
+    // -------------------------------------------------------------------------
+    // KEEP INDICES VALID WHEN ELEMENTS ARE REMOVED
+    // -------------------------------------------------------------------------
+    pub fn vert_removed(&mut self, vert_index: usize) {
+        shift_after_removal(&mut self.verts, vert_index);
+    }
+
+    pub fn line_removed(&mut self, line_index: usize) {
+        shift_after_removal(&mut self.lines, line_index);
+    }
+
+    pub fn face_removed(&mut self, face_index: usize) {
+        shift_after_removal(&mut self.faces, face_index);
+    }
+}
+
+
+// -----------------------------------------------------------------------------
+// MISC FUNCTIONS
+// -----------------------------------------------------------------------------
+fn apply_op(set: &mut Vec<usize>, hits: &[usize], op: SelectOp) {
+    match op {
+        SelectOp::Replace => {
+            set.clear();
+            for hit in hits.iter() {
+                if !set.contains(hit) {
+                    set.push(*hit);
+                }
+            }
+        },
+        SelectOp::Add => {
+            for hit in hits.iter() {
+                if !set.contains(hit) {
+                    set.push(*hit);
+                }
+            }
+        },
+        SelectOp::Subtract => {
+            set.retain(|index| !hits.contains(index));
+        },
+        SelectOp::Toggle => {
+            for hit in hits.iter() {
+                match set.iter().position(|index| index == hit) {
+                    Some(position) => { set.remove(position); },
+                    None => set.push(*hit)
+                }
+            }
+        }
+    }
+}
+
+pub(super) fn shift_after_removal(set: &mut Vec<usize>, removed_index: usize) {
+    set.retain(|index| *index != removed_index);
+    for index in set.iter_mut() {
+        if *index > removed_index {
+            *index -= 1;
+        }
+    }
+}
+
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square() -> SelectRegion {
+        SelectRegion::Lasso(vec![Vector2::new(0.0, 0.0), Vector2::new(10.0, 0.0),
+                                 Vector2::new(10.0, 10.0), Vector2::new(0.0, 10.0)])
+    }
+
+    #[test]
+    fn lasso_closes_itself() {
+        let lasso = square();
+        assert!(lasso.contains(Vector2::new(5.0, 5.0)));
+        assert!(lasso.contains(Vector2::new(9.0, 1.0)));
+        assert!(!lasso.contains(Vector2::new(11.0, 5.0)));
+        assert!(!lasso.contains(Vector2::new(5.0, -1.0)));
+    }
+
+    #[test]
+    fn lasso_is_even_odd() {
+        // Five pointed star drawn in one stroke, the pentagon in the middle is
+        // crossed twice and is left out while the tips are in
+        let points = (0..5)
+            .map(|point| {
+                let angle = (90.0 + 144.0 * point as f32).to_radians();
+                Vector2::new(10.0 * angle.cos(), 10.0 * angle.sin())
+            })
+            .collect();
+        let star = SelectRegion::Lasso(points);
+
+        assert!(!star.contains(Vector2::new(0.0, 0.0)));
+        assert!(star.contains(Vector2::new(0.0, 8.0)));
+        assert!(!star.contains(Vector2::new(0.0, 11.0)));
+    }
+
+    #[test]
+    fn lasso_needs_three_points() {
+        let line = SelectRegion::Lasso(vec![Vector2::new(0.0, 0.0), Vector2::new(10.0, 10.0)]);
+        assert!(!line.contains(Vector2::new(5.0, 5.0)));
+    }
+
+    #[test]
+    fn rect_takes_corners_in_any_order() {
+        let rect = SelectRegion::Rect(Vector2::new(10.0, 0.0), Vector2::new(0.0, 10.0));
+        assert!(rect.contains(Vector2::new(5.0, 5.0)));
+        assert!(rect.contains(Vector2::new(10.0, 10.0)));
+        assert!(!rect.contains(Vector2::new(5.0, 10.5)));
+    }
+
+    #[test]
+    fn ops_combine_with_the_set_of_the_mode() {
+        let mut selection = Selection::new();
+        selection.apply(&[1, 2, 2, 3], SelectOp::Replace);
+        assert_eq!(*selection.verts(), vec![1, 2, 3]);
+
+        selection.apply(&[3, 4], SelectOp::Add);
+        assert_eq!(*selection.verts(), vec![1, 2, 3, 4]);
+
+        selection.apply(&[2, 5], SelectOp::Subtract);
+        assert_eq!(*selection.verts(), vec![1, 3, 4]);
+
+        selection.apply(&[1, 5], SelectOp::Toggle);
+        assert_eq!(*selection.verts(), vec![3, 4, 5]);
+
+        selection.apply(&[0], SelectOp::Replace);
+        assert_eq!(*selection.verts(), vec![0]);
+
+        selection.set_mode(SelectMode::Face);
+        selection.apply(&[7], SelectOp::Add);
+        assert_eq!(*selection.verts(), vec![0]);
+        assert_eq!(*selection.faces(), vec![7]);
+        assert!(selection.lines().is_empty());
+    }
+
+    #[test]
+    fn removals_shift_later_indices_down() {
+        let mut selection = Selection::new();
+        selection.apply_verts(&[0, 2, 5], SelectOp::Replace);
+        selection.apply_lines(&[1, 3], SelectOp::Replace);
+        selection.apply_faces(&[4], SelectOp::Replace);
+
+        selection.vert_removed(2);
+        assert_eq!(*selection.verts(), vec![0, 4]);
+
+        selection.vert_removed(1);
+        assert_eq!(*selection.verts(), vec![0, 3]);
+
+        selection.line_removed(0);
+        assert_eq!(*selection.lines(), vec![0, 2]);
+
+        selection.face_removed(4);
+        assert!(selection.faces().is_empty());
+    }
+}
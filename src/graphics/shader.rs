@@ -1,97 +1,472 @@
-extern crate gl;
-use self::gl::types::*;
+use cgmath::{Matrix3, Matrix4, Vector2, Vector3, Vector4};
 
-use cgmath::{Matrix4};
-use cgmath::prelude::*;
-
-use std::ffi::CString;
-use std::str;
+use std::cell::RefCell;
+use std::fmt;
 use std::fs;
-use std::ptr;
+use std::time::SystemTime;
+
+use super::backend::{with_backend, ProgramError, Uniform};
+use super::shader_source::ShaderSource;
 
+// Source files and the files they include are remembered with their
+// modification times so the program can be rebuilt when any of them changes.
+// Locations are looked up once at link time
 pub struct Shader {
     shader_id: u32,
+    vertex_file: String,
+    fragment_file: String,
+    defines: Vec<(String, String)>,
+    modified: Vec<(String, Option<SystemTime>)>,
+    uniforms: Vec<(String, i32)>,
+    attributes: Vec<(String, i32)>,
+    warned: RefCell<Vec<String>>
+}
+
+// Compile errors keep the whole log and the source, so the lines can be
+// pointed back into the files
+pub enum ShaderError {
+    Io { path: String, message: String },
+    Preprocess { path: String, line: usize, message: String },
+    Compile { path: String, log: String, source: ShaderSource },
+    Link { paths: (String, String), log: String }
+}
+
+// Intermediate result of building a program
+struct Built {
+    shader_id: u32,
+    files: Vec<String>,
+    uniforms: Vec<(String, i32)>,
+    attributes: Vec<(String, i32)>
 }
 
 impl Shader {
-    pub fn create(vertex_file: &str, fragment_file: &str) -> Shader {
-        unsafe {
-            // Vertex shader
-            let vertex_shader_source = fs::read_to_string(vertex_file).expect("Could not read vertex file");
-            let vertex_shader = gl::CreateShader(gl::VERTEX_SHADER);
-            let c_str_vert = CString::new(vertex_shader_source.as_bytes()).unwrap();
-            gl::ShaderSource(vertex_shader, 1, &c_str_vert.as_ptr(), ptr::null());
-            gl::CompileShader(vertex_shader);
-
-            let mut success = gl::FALSE as GLint;
-            let mut info_log = Vec::with_capacity(512);
-            info_log.set_len(512 - 1);
-            gl::GetShaderiv(vertex_shader, gl::COMPILE_STATUS, &mut success);
-            if success != gl::TRUE as GLint {
-                gl::GetShaderInfoLog(vertex_shader, 512, ptr::null_mut(), info_log.as_mut_ptr() as *mut GLchar);
-                println!("ERROR: Vertex shader compilation failed\n{}", str::from_utf8(&info_log).unwrap());
-            }
+    pub fn create(vertex_file: &str, fragment_file: &str) -> Result<Shader, ShaderError> {
+        Shader::create_variant(vertex_file, fragment_file, &[])
+    }
 
-            // Fragment shader
-            let fragment_shader_source = fs::read_to_string(fragment_file).expect("Could not read fragment file");
-            let fragment_shader = gl::CreateShader(gl::FRAGMENT_SHADER);
-            let c_str_frag = CString::new(fragment_shader_source.as_bytes()).unwrap();
-            gl::ShaderSource(fragment_shader, 1, &c_str_frag.as_ptr(), ptr::null());
-            gl::CompileShader(fragment_shader);
-
-            info_log.set_len(512 - 1);
-            gl::GetShaderiv(fragment_shader, gl::COMPILE_STATUS, &mut success);
-            if success != gl::TRUE as GLint {
-                gl::GetShaderInfoLog(fragment_shader, 512, ptr::null_mut(), info_log.as_mut_ptr() as *mut GLchar);
-                println!("ERROR: Fragment shader compilation failed\n{}", str::from_utf8(&info_log).unwrap());
-            }
+    // The same sources built with some `#define name value` lines, such as
+    // ("FLAT_SHADING", "") or ("UNLIT", "")
+    pub fn create_variant(vertex_file: &str, fragment_file: &str, defines: &[(&str, &str)]) -> Result<Shader, ShaderError> {
+        let defines: Vec<(String, String)> = defines.iter()
+            .map(|(name, value)| (String::from(*name), String::from(*value)))
+            .collect();
+        let built = build(vertex_file, fragment_file, &defines)?;
 
-            // Link shaders
-            let shader = Shader {
-                shader_id: gl::CreateProgram(),
-            };
-            gl::AttachShader(shader.shader_id, vertex_shader);
-            gl::AttachShader(shader.shader_id, fragment_shader);
-            gl::LinkProgram(shader.shader_id);
-
-            gl::GetProgramiv(shader.shader_id, gl::LINK_STATUS, &mut success);
-            if success != gl::TRUE as GLint {
-                gl::GetProgramInfoLog(shader.shader_id, 512, ptr::null_mut(), info_log.as_mut_ptr() as *mut GLchar);
-                println!("ERROR: Shader program linking failed\n{}", str::from_utf8(&info_log).unwrap());
-            }
+        Ok(Shader {
+            shader_id: built.shader_id,
+            vertex_file: String::from(vertex_file),
+            fragment_file: String::from(fragment_file),
+            defines,
+            modified: modified_times(&built.files),
+            uniforms: built.uniforms,
+            attributes: built.attributes,
+            warned: RefCell::new(Vec::new())
+        })
+    }
+
+    // -------------------------------------------------------------------------
+    // HOT RELOAD
+    // -------------------------------------------------------------------------
+    // Rebuild the program if a source file changed on disk. On any error the
+    // previous program stays in use, true when the new one took its place
+    pub fn reload_if_changed(&mut self) -> bool {
+        let files: Vec<String> = self.modified.iter().map(|(path, _time)| path.clone()).collect();
+        if modified_times(&files) == self.modified {
+            return false;
+        }
+
+        match build(&self.vertex_file, &self.fragment_file, &self.defines) {
+            Ok(built) => {
+                let old_id = self.shader_id;
+                with_backend(|backend| backend.delete_program(old_id));
+
+                self.shader_id = built.shader_id;
+                self.modified = modified_times(&built.files);
+                self.uniforms = built.uniforms;
+                self.attributes = built.attributes;
+                self.warned.borrow_mut().clear();
 
-            gl::DeleteShader(vertex_shader);
-            gl::DeleteShader(fragment_shader);
+                println!("Reloaded {} and {}", self.vertex_file, self.fragment_file);
+                true
+            },
+            Err(e) => {
+                // Not retried until a file changes again
+                self.modified = modified_times(&files);
 
-            shader
+                println!("{}", e);
+                println!("Keeping the previous program for {} and {}", self.vertex_file, self.fragment_file);
+                false
+            }
         }
     }
 
     pub fn bind(&self) {
-        unsafe { gl::UseProgram(self.shader_id); }
+        with_backend(|backend| backend.use_program(self.shader_id));
     }
 
-    pub fn pass_colour(&self, colour_name: &str, colour: (f32, f32, f32, f32)) {
-        unsafe {
-            let colour_name = CString::new(colour_name).unwrap();
-            let colour_location = gl::GetUniformLocation(self.shader_id, colour_name.as_ptr());
-            gl::Uniform4f(colour_location, colour.0, colour.1, colour.2, colour.3);
+    // -------------------------------------------------------------------------
+    // LOCATIONS
+    // -------------------------------------------------------------------------
+    // Names the program doesn't use get a warning the first time only
+    pub fn uniform_location(&self, name: &str) -> Option<i32> {
+        match self.uniforms.iter().find(|uniform| uniform.0 == name) {
+            Some(uniform) => Some(uniform.1),
+            None => {
+                let mut warned = self.warned.borrow_mut();
+                if !warned.iter().any(|warned_name| warned_name == name) {
+                    println!("WARNING: No uniform {} in {} and {}", name, self.vertex_file, self.fragment_file);
+                    warned.push(String::from(name));
+                }
+                None
+            }
         }
     }
 
+    pub fn attribute_location(&self, name: &str) -> Option<i32> {
+        self.attributes.iter().find(|attribute| attribute.0 == name).map(|attribute| attribute.1)
+    }
+
+    pub fn has_uniform(&self, name: &str) -> bool {
+        self.uniforms.iter().any(|uniform| uniform.0 == name)
+    }
+
+    // -------------------------------------------------------------------------
+    // UNIFORMS
+    // -------------------------------------------------------------------------
+    pub fn pass_int(&self, int_name: &str, int_value: i32) {
+        self.pass_uniform(int_name, &Uniform::Int(int_value));
+    }
+
+    pub fn pass_float(&self, float_name: &str, float_value: f32) {
+        self.pass_uniform(float_name, &Uniform::Float(float_value));
+    }
+
+    pub fn pass_vec2(&self, vector_name: &str, vector: Vector2<f32>) {
+        self.pass_uniform(vector_name, &Uniform::Vec2(vector));
+    }
+
+    pub fn pass_vec3(&self, vector_name: &str, vector: Vector3<f32>) {
+        self.pass_uniform(vector_name, &Uniform::Vec3(vector));
+    }
+
+    pub fn pass_vec4(&self, vector_name: &str, vector: Vector4<f32>) {
+        self.pass_uniform(vector_name, &Uniform::Vec4(vector));
+    }
+
+    pub fn pass_colour(&self, colour_name: &str, colour: (f32, f32, f32, f32)) {
+        self.pass_vec4(colour_name, Vector4::new(colour.0, colour.1, colour.2, colour.3));
+    }
+
+    pub fn pass_matrix3(&self, matrix_name: &str, matrix: &Matrix3<f32>) {
+        self.pass_uniform(matrix_name, &Uniform::Matrix3(*matrix));
+    }
+
     pub fn pass_matrix(&self, matrix_name: &str, matrix: &Matrix4<f32>) {
-        unsafe {
-            let matrix_name = CString::new(matrix_name).unwrap();
-            let matrix_location = gl::GetUniformLocation(self.shader_id, matrix_name.as_ptr());
-            gl::UniformMatrix4fv(matrix_location, 1, gl::FALSE, matrix.as_ptr());
+        self.pass_uniform(matrix_name, &Uniform::Matrix(*matrix));
+    }
+
+    // Texture unit the sampler reads from
+    pub fn pass_sampler(&self, sampler_name: &str, unit: u32) {
+        self.pass_uniform(sampler_name, &Uniform::Int(unit as i32));
+    }
+
+    pub fn pass_float_array(&self, array_name: &str, values: &[f32]) {
+        if values.is_empty() {
+            return;
         }
+
+        self.pass_uniform(array_name, &Uniform::FloatArray(values.to_vec()));
     }
 
-    pub fn pass_int(&self, int_name: &str, int_value: i32) {
-        unsafe {
-            let int_name = CString::new(int_name).unwrap();
-            let int_location = gl::GetUniformLocation(self.shader_id, int_name.as_ptr());
-            gl::Uniform1i(int_location, int_value);
+    pub fn pass_vec3_array(&self, array_name: &str, vectors: &[Vector3<f32>]) {
+        if vectors.is_empty() {
+            return;
+        }
+
+        self.pass_uniform(array_name, &Uniform::Vec3Array(vectors.to_vec()));
+    }
+
+    pub fn pass_colour_array(&self, array_name: &str, colours: &[(f32, f32, f32, f32)]) {
+        if colours.is_empty() {
+            return;
+        }
+
+        self.pass_uniform(array_name, &Uniform::ColourArray(colours.to_vec()));
+    }
+
+    fn pass_uniform(&self, name: &str, value: &Uniform) {
+        if let Some(location) = self.uniform_location(name) {
+            with_backend(|backend| backend.set_uniform(location, value));
+        }
+    }
+}
+
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShaderError::Io { path, message } => write!(f, "ERROR: Could not read {}: {}", path, message),
+            ShaderError::Preprocess { path, line, message } => write!(f, "ERROR: {}:{}: {}", path, line, message),
+            ShaderError::Compile { path, log, source } => {
+                writeln!(f, "ERROR: Compilation of {} failed", path)?;
+                write_log(f, log, source)
+            },
+            ShaderError::Link { paths, log } => {
+                write!(f, "ERROR: Linking {} and {} failed\n{}", paths.0, paths.1, log.trim_end())
+            }
+        }
+    }
+}
+
+
+// -----------------------------------------------------------------------------
+// MISC FUNCTIONS
+// -----------------------------------------------------------------------------
+// Program with its uniforms and attributes
+fn build(vertex_file: &str, fragment_file: &str, defines: &[(String, String)]) -> Result<Built, ShaderError> {
+    let vertex_source = ShaderSource::load(vertex_file, defines)?;
+    let fragment_source = ShaderSource::load(fragment_file, defines)?;
+
+    let shader_id = with_backend(|backend| backend.create_program(&vertex_source.text, &fragment_source.text));
+    let shader_id = match shader_id {
+        Ok(shader_id) => shader_id,
+        Err(ProgramError::Vertex(log)) => {
+            return Err(ShaderError::Compile { path: String::from(vertex_file), log, source: vertex_source });
+        },
+        Err(ProgramError::Fragment(log)) => {
+            return Err(ShaderError::Compile { path: String::from(fragment_file), log, source: fragment_source });
+        },
+        Err(ProgramError::Link(log)) => {
+            return Err(ShaderError::Link {
+                paths: (String::from(vertex_file), String::from(fragment_file)),
+                log
+            });
+        }
+    };
+
+    let mut files = vertex_source.files();
+    files.extend(fragment_source.files());
+
+    with_backend(|backend| {
+        Ok(Built {
+            shader_id,
+            files,
+            uniforms: backend.active_uniforms(shader_id),
+            attributes: backend.active_attributes(shader_id)
+        })
+    })
+}
+
+// Missing files count too, so deleting one is also a change
+fn modified_times(files: &[String]) -> Vec<(String, Option<SystemTime>)> {
+    files.iter().map(|path| (path.clone(), modified_time(path))).collect()
+}
+
+fn modified_time(path_to_file: &str) -> Option<SystemTime> {
+    fs::metadata(path_to_file).and_then(|metadata| metadata.modified()).ok()
+}
+
+// Every message of the log as `file:line: message` followed by the offending
+// source line, messages without a line number are written as they are
+fn write_log(f: &mut fmt::Formatter, log: &str, source: &ShaderSource) -> fmt::Result {
+    for message in log.lines().filter(|message| !message.trim().is_empty()) {
+        let line_number = log_line_number(message);
+        match line_number.and_then(|line_number| source.origin(line_number)) {
+            Some((path_to_file, line)) => {
+                writeln!(f, "{}:{}: {}", path_to_file, line, message.trim())?;
+                if let Some(text) = line_number.and_then(|line_number| source.line(line_number)) {
+                    writeln!(f, "    {} | {}", line, text.trim())?;
+                }
+            },
+            None => writeln!(f, "{}", message.trim())?
+        }
+    }
+
+    Ok(())
+}
+
+// Drivers write the line after the source string index, as `0:12(5):` on
+// Mesa, `ERROR: 0:12:` on AMD and Intel and `0(12) :` on NVIDIA
+fn log_line_number(message: &str) -> Option<usize> {
+    let message = message.trim_start().trim_start_matches("ERROR: ").trim_start_matches("WARNING: ");
+    let rest = message.trim_start_matches(|c: char| c.is_ascii_digit());
+    if rest.len() == message.len() {
+        return None;
+    }
+
+    let rest = rest.strip_prefix(':').or_else(|| rest.strip_prefix('('))?;
+    let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse::<usize>().ok()
+}
+
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::backend::{set_backend, BackendCall, RecordingBackend};
+
+    use std::path::PathBuf;
+    use std::rc::Rc;
+
+    const VERTEX: &str = "#version 330 core
+layout (location = 0) in vec3 aPos;
+layout (location = 2) in vec3 normal;
+layout (location = 7) in float model_vertex;
+uniform mat4 transMat;
+uniform mat3 normalMat;
+void main() {
+    gl_Position = transMat * vec4(aPos, 1.0);
+}
+";
+
+    const FRAGMENT: &str = "#version 330 core
+out vec4 FragColor;
+uniform float transparency;
+uniform vec4 tint;
+uniform sampler2D tex;
+uniform vec3 lightColours[5];
+void main() {
+    FragColor = vec4(1.0);
+}
+";
+
+    // Sources in a directory of their own, so tests can run side by side
+    fn write_sources(test_name: &str) -> (String, String) {
+        let dir: PathBuf = std::env::temp_dir().join(format!("editor_shader_{}_{}", test_name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let vertex_file = dir.join("test.vs");
+        let fragment_file = dir.join("test.fs");
+        fs::write(&vertex_file, VERTEX).unwrap();
+        fs::write(&fragment_file, FRAGMENT).unwrap();
+
+        (vertex_file.to_string_lossy().into_owned(), fragment_file.to_string_lossy().into_owned())
+    }
+
+    fn record() -> Rc<RefCell<Vec<BackendCall>>> {
+        let backend = RecordingBackend::new();
+        let calls = backend.calls();
+        set_backend(Box::new(backend));
+        calls
+    }
+
+    fn create(test_name: &str) -> Shader {
+        let (vertex_file, fragment_file) = write_sources(test_name);
+        match Shader::create(&vertex_file, &fragment_file) {
+            Ok(shader) => shader,
+            Err(e) => panic!("{}", e)
         }
     }
-}
\ No newline at end of file
+
+    fn set_uniform(program: u32, name: &str, value: Uniform) -> BackendCall {
+        BackendCall::SetUniform { program, name: String::from(name), value }
+    }
+
+    #[test]
+    fn create_links_one_program_and_reflects_it() {
+        let calls = record();
+        let shader = create("create");
+
+        assert_eq!(*calls.borrow(), vec![BackendCall::CreateProgram(shader.shader_id)]);
+        assert!(shader.has_uniform("transMat"));
+        assert!(shader.has_uniform("transparency"));
+        assert!(shader.has_uniform("lightColours"));
+        assert!(!shader.has_uniform("modelMat"));
+
+        calls.borrow_mut().clear();
+        shader.bind();
+        assert_eq!(*calls.borrow(), vec![BackendCall::UseProgram(shader.shader_id)]);
+    }
+
+    #[test]
+    fn missing_files_make_no_program() {
+        let calls = record();
+
+        let result = Shader::create("no/such/shader.vs", "no/such/shader.fs");
+
+        assert!(match result { Err(ShaderError::Io { path, .. }) => path == "no/such/shader.vs", _ => false });
+        assert!(calls.borrow().is_empty());
+    }
+
+    #[test]
+    fn uniforms_go_to_their_program() {
+        let calls = record();
+        let shader = create("uniforms");
+        calls.borrow_mut().clear();
+
+        let mat = Matrix4::from_scale(2.0);
+        shader.pass_matrix("transMat", &mat);
+        shader.pass_float("transparency", 0.5);
+        shader.pass_sampler("tex", 3);
+        shader.pass_vec3_array("lightColours", &[Vector3::new(1.0, 0.5, 0.0)]);
+        shader.pass_vec3_array("lightColours", &[]);
+
+        let program = shader.shader_id;
+        assert_eq!(*calls.borrow(), vec![
+            set_uniform(program, "transMat", Uniform::Matrix(mat)),
+            set_uniform(program, "transparency", Uniform::Float(0.5)),
+            set_uniform(program, "tex", Uniform::Int(3)),
+            set_uniform(program, "lightColours", Uniform::Vec3Array(vec![Vector3::new(1.0, 0.5, 0.0)]))
+        ]);
+    }
+
+    #[test]
+    fn vectors_and_matrices_keep_their_size() {
+        let calls = record();
+        let shader = create("sizes");
+        calls.borrow_mut().clear();
+
+        let normal_mat = Matrix3::new(0.5, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 2.0);
+        shader.pass_vec4("tint", Vector4::new(0.1, 0.2, 0.3, 0.4));
+        shader.pass_colour("tint", (1.0, 0.5, 0.25, 1.0));
+        shader.pass_matrix3("normalMat", &normal_mat);
+
+        let program = shader.shader_id;
+        assert_eq!(*calls.borrow(), vec![
+            set_uniform(program, "tint", Uniform::Vec4(Vector4::new(0.1, 0.2, 0.3, 0.4))),
+            set_uniform(program, "tint", Uniform::Vec4(Vector4::new(1.0, 0.5, 0.25, 1.0))),
+            set_uniform(program, "normalMat", Uniform::Matrix3(normal_mat))
+        ]);
+    }
+
+    #[test]
+    fn attributes_are_found_by_name() {
+        let _calls = record();
+        let shader = create("attributes");
+
+        assert_eq!(shader.attribute_location("aPos"), Some(0));
+        assert_eq!(shader.attribute_location("normal"), Some(2));
+        assert_eq!(shader.attribute_location("model_vertex"), Some(7));
+        assert_eq!(shader.attribute_location("uv"), None);
+    }
+
+    #[test]
+    fn unknown_uniforms_are_not_sent() {
+        let calls = record();
+        let shader = create("unknown");
+        calls.borrow_mut().clear();
+
+        shader.pass_float("fog", 1.0);
+        shader.pass_float("fog", 2.0);
+
+        assert!(calls.borrow().is_empty());
+        assert_eq!(*shader.warned.borrow(), vec![String::from("fog")]);
+    }
+
+    #[test]
+    fn log_lines_of_every_driver() {
+        // Mesa
+        assert_eq!(log_line_number("0:12(5): error: `colour' undeclared"), Some(12));
+        // AMD and Intel
+        assert_eq!(log_line_number("ERROR: 0:12: 'colour' : undeclared identifier"), Some(12));
+        assert_eq!(log_line_number("WARNING: 0:7: extension not supported"), Some(7));
+        // NVIDIA
+        assert_eq!(log_line_number("0(12) : error C1008: undefined variable \"colour\""), Some(12));
+
+        assert_eq!(log_line_number("ERROR: 2 compilation errors.  No code generated."), None);
+        assert_eq!(log_line_number("error: linking failed"), None);
+        assert_eq!(log_line_number(""), None);
+    }
+}
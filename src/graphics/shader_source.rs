@@ -0,0 +1,252 @@
+use std::fs;
+use std::path::Path;
+
+use super::shader::ShaderError;
+
+
+// ---- PREPROCESSED GLSL ----
+// Text handed to the compiler, with the file and line every one of its lines
+// came from so errors can point back at them
+pub struct ShaderSource {
+    pub text: String,
+    origins: Vec<(usize, usize)>,
+    files: Vec<String>
+}
+
+// Name used for the lines of the caller's defines
+const DEFINES_ORIGIN: &str = "<defines>";
+
+
+impl ShaderSource {
+    // `#include "file"` is replaced by the file, relative to the directory of
+    // the shader so includes read the same everywhere. A file is only ever
+    // included once. The defines go right after #version, which has to stay
+    // the first line
+    pub fn load(path_to_file: &str, defines: &[(String, String)]) -> Result<ShaderSource, ShaderError> {
+        let mut source = ShaderSource {
+            text: String::new(),
+            origins: Vec::new(),
+            files: vec![String::from(DEFINES_ORIGIN)]
+        };
+
+        let mut define_lines = Vec::<String>::new();
+        for (name, value) in defines.iter() {
+            define_lines.push(format!("#define {} {}", name, value).trim_end().to_string());
+        }
+
+        let shader_dir = Path::new(path_to_file).parent().unwrap_or(Path::new(""));
+        let mut pending_defines = Some(define_lines);
+        source.include(path_to_file, shader_dir, &mut Vec::new(), &mut pending_defines)?;
+
+        // No #version, the defines simply go first
+        if let Some(define_lines) = pending_defines {
+            let mut text = String::new();
+            let mut origins = Vec::new();
+            for (line_index, line) in define_lines.iter().enumerate() {
+                text.push_str(line);
+                text.push('\n');
+                origins.push((0, line_index + 1));
+            }
+            source.text = text + &source.text;
+            source.origins = origins.into_iter().chain(source.origins.drain(..)).collect();
+        }
+
+        Ok(source)
+    }
+
+    // Every file that went into the text, for watching them
+    pub fn files(&self) -> Vec<String> {
+        self.files[1..].to_vec()
+    }
+
+    // File and line of a line of the text, both counting from one like the
+    // compiler logs
+    pub fn origin(&self, line_number: usize) -> Option<(&str, usize)> {
+        if line_number == 0 {
+            return None;
+        }
+        self.origins.get(line_number - 1).map(|(file_index, line)| (self.files[*file_index].as_str(), *line))
+    }
+
+    pub fn line(&self, line_number: usize) -> Option<&str> {
+        if line_number == 0 {
+            return None;
+        }
+        self.text.lines().nth(line_number - 1)
+    }
+
+    fn include(&mut self,
+               path_to_file: &str,
+               shader_dir: &Path,
+               stack: &mut Vec<String>,
+               pending_defines: &mut Option<Vec<String>>) -> Result<(), ShaderError> {
+        if self.files.iter().any(|path| path == path_to_file) {
+            return Ok(());
+        }
+
+        let contents = fs::read_to_string(path_to_file).map_err(|e| ShaderError::Io {
+            path: String::from(path_to_file),
+            message: e.to_string()
+        })?;
+
+        self.files.push(String::from(path_to_file));
+        let file_index = self.files.len() - 1;
+        stack.push(String::from(path_to_file));
+
+        for (line_index, line) in contents.lines().enumerate() {
+            let trimmed = line.trim();
+
+            if let Some(name) = trimmed.strip_prefix("#include") {
+                let name = name.trim();
+                if name.len() < 2 || !name.starts_with('"') || !name.ends_with('"') {
+                    return Err(ShaderError::Preprocess {
+                        path: String::from(path_to_file),
+                        line: line_index + 1,
+                        message: String::from("expected #include \"file\"")
+                    });
+                }
+
+                let included = shader_dir.join(&name[1..name.len() - 1]).to_string_lossy().into_owned();
+                if stack.contains(&included) {
+                    return Err(ShaderError::Preprocess {
+                        path: String::from(path_to_file),
+                        line: line_index + 1,
+                        message: format!("{} ends up including itself", included)
+                    });
+                }
+
+                self.include(&included, shader_dir, stack, pending_defines)?;
+                continue;
+            }
+
+            self.text.push_str(line);
+            self.text.push('\n');
+            self.origins.push((file_index, line_index + 1));
+
+            if trimmed.starts_with("#version") {
+                if let Some(define_lines) = pending_defines.take() {
+                    for (define_index, define_line) in define_lines.iter().enumerate() {
+                        self.text.push_str(define_line);
+                        self.text.push('\n');
+                        self.origins.push((0, define_index + 1));
+                    }
+                }
+            }
+        }
+
+        stack.pop();
+        Ok(())
+    }
+}
+
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    // Files in a directory of their own, so tests can run side by side
+    fn write_files(test_name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("editor_source_{}_{}", test_name, std::process::id()));
+        for (name, contents) in files.iter() {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, contents).unwrap();
+        }
+        dir
+    }
+
+    fn path(dir: &Path, name: &str) -> String {
+        dir.join(name).to_string_lossy().into_owned()
+    }
+
+    fn load(path_to_file: &str, defines: &[(&str, &str)]) -> ShaderSource {
+        let defines: Vec<(String, String)> = defines.iter()
+            .map(|(name, value)| (String::from(*name), String::from(*value)))
+            .collect();
+        match ShaderSource::load(path_to_file, &defines) {
+            Ok(source) => source,
+            Err(e) => panic!("{}", e)
+        }
+    }
+
+    #[test]
+    fn includes_are_inlined_once_and_traced_back() {
+        let dir = write_files("include", &[
+            ("main.vs", "#version 330 core\n#include \"common/a.glsl\"\n#include \"common/b.glsl\"\nvoid main() {}\n"),
+            ("common/a.glsl", "// a\n#include \"common/b.glsl\"\nfloat a;\n"),
+            ("common/b.glsl", "float b;\n")
+        ]);
+        let main = path(&dir, "main.vs");
+        let a = path(&dir, "common/a.glsl");
+        let b = path(&dir, "common/b.glsl");
+
+        let source = load(&main, &[]);
+
+        assert_eq!(source.text, "#version 330 core\n// a\nfloat b;\nfloat a;\nvoid main() {}\n");
+        assert_eq!(source.files(), vec![main.clone(), a.clone(), b.clone()]);
+        assert_eq!(source.origin(1), Some((main.as_str(), 1)));
+        assert_eq!(source.origin(2), Some((a.as_str(), 1)));
+        assert_eq!(source.origin(3), Some((b.as_str(), 1)));
+        assert_eq!(source.origin(4), Some((a.as_str(), 3)));
+        assert_eq!(source.origin(5), Some((main.as_str(), 4)));
+        assert_eq!(source.origin(0), None);
+        assert_eq!(source.origin(6), None);
+        assert_eq!(source.line(4), Some("float a;"));
+    }
+
+    #[test]
+    fn defines_follow_the_version() {
+        let dir = write_files("defines", &[
+            ("main.fs", "// comment\n#version 330 core\nvoid main() {}\n"),
+            ("plain.fs", "void main() {}\n")
+        ]);
+        let main = path(&dir, "main.fs");
+
+        let source = load(&main, &[("SHADOWS", "1"), ("FLAT", "")]);
+
+        assert_eq!(source.text, "// comment\n#version 330 core\n#define SHADOWS 1\n#define FLAT\nvoid main() {}\n");
+        assert_eq!(source.origin(2), Some((main.as_str(), 2)));
+        assert_eq!(source.origin(3), Some((DEFINES_ORIGIN, 1)));
+        assert_eq!(source.origin(4), Some((DEFINES_ORIGIN, 2)));
+        assert_eq!(source.origin(5), Some((main.as_str(), 3)));
+        assert_eq!(source.files(), vec![main]);
+
+        // Without a #version they go first
+        let plain = path(&dir, "plain.fs");
+        let source = load(&plain, &[("FLAT", "")]);
+        assert_eq!(source.text, "#define FLAT\nvoid main() {}\n");
+        assert_eq!(source.origin(1), Some((DEFINES_ORIGIN, 1)));
+        assert_eq!(source.origin(2), Some((plain.as_str(), 1)));
+    }
+
+    #[test]
+    fn bad_includes_point_at_their_line() {
+        let dir = write_files("cycle", &[
+            ("main.vs", "#version 330 core\n#include \"a.glsl\"\n"),
+            ("a.glsl", "float a;\n#include \"main.vs\"\n"),
+            ("quotes.vs", "#include <a.glsl>\n"),
+            ("missing.vs", "#include \"none.glsl\"\n")
+        ]);
+
+        match ShaderSource::load(&path(&dir, "main.vs"), &[]) {
+            Err(ShaderError::Preprocess { path: file, line, .. }) => {
+                assert_eq!(file, path(&dir, "a.glsl"));
+                assert_eq!(line, 2);
+            },
+            _ => panic!("Expected the cycle to be an error")
+        }
+        match ShaderSource::load(&path(&dir, "quotes.vs"), &[]) {
+            Err(ShaderError::Preprocess { line, .. }) => assert_eq!(line, 1),
+            _ => panic!("Expected the include to need quotes")
+        }
+        match ShaderSource::load(&path(&dir, "missing.vs"), &[]) {
+            Err(ShaderError::Io { path: file, .. }) => assert_eq!(file, path(&dir, "none.glsl")),
+            _ => panic!("Expected the missing include to be an error")
+        }
+    }
+}
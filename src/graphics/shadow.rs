@@ -0,0 +1,137 @@
+use cgmath::{Matrix4, Point3, Vector3, ortho};
+use cgmath::prelude::*;
+
+use super::shader::Shader;
+use super::camera::create_screen_quad;
+use super::backend::{with_backend, Framebuffer, Primitive};
+
+
+// ---- WHERE THE SHADERS FIND THE MAP ----
+// Units 0 and 1 are the model texture and the morph deltas
+const SHADOW_TEXTURE_UNIT: u32 = 2;
+
+// Resolutions the editor cycles through
+pub const SHADOW_RESOLUTIONS: [u32; 4] = [512, 1024, 2048, 4096];
+
+// The debug view is this fraction of the shorter side of the target
+const DEBUG_VIEW_FRACTION: u32 = 3;
+
+// Depth of the map from a directional light. The light looks at a box around
+// the scene, so everything in it both casts and receives shadows
+pub struct ShadowMap {
+    framebuffer: Framebuffer,
+    quad_vao: u32,
+    light_mat: Matrix4<f32>,
+    // Taken off the depth of a surface before comparing so it doesn't shadow
+    // itself, in the depth range of the map
+    pub bias: f32,
+    // Texels on each side averaged by the percentage closer filter
+    pub pcf_radius: i32
+}
+
+
+impl ShadowMap {
+    // Square map, the framebuffer is made like the one of the camera
+    pub fn create(resolution: u32) -> ShadowMap {
+        // Quad the debug view is drawn with
+        let quad_vao = create_screen_quad();
+
+        with_backend(|backend| {
+            ShadowMap {
+                framebuffer: backend.create_depth_framebuffer((resolution, resolution)),
+                quad_vao,
+                light_mat: Matrix4::identity(),
+                bias: 0.005,
+                pcf_radius: 1
+            }
+        })
+    }
+
+    pub fn resolution(&self) -> u32 {
+        self.framebuffer.size.0
+    }
+
+    // The map is made again, so it is empty until the next pass
+    pub fn set_resolution(&mut self, resolution: u32) {
+        with_backend(|backend| {
+            backend.delete_framebuffer(&self.framebuffer);
+            self.framebuffer = backend.create_depth_framebuffer((resolution, resolution));
+        });
+    }
+
+    // Total matrix of the light, the same for every model in the pass
+    pub fn light_mat(&self) -> &Matrix4<f32> {
+        &self.light_mat
+    }
+
+    // -------------------------------------------------------------------------
+    // DEPTH PASS
+    // -------------------------------------------------------------------------
+    // Orthographic view along the light covering the sphere. The direction is
+    // towards the light like in Lighting
+    pub fn update_light(&mut self, direction: Vector3<f32>, centre: Vector3<f32>, radius: f32) {
+        let direction = if direction.magnitude() > 0.0 { direction.normalize() } else { Vector3::unit_z() };
+        let radius = radius.max(0.01);
+
+        // Any up works as long as it isn't along the light
+        let up = if direction.z.abs() > 0.99 { Vector3::unit_x() } else { Vector3::unit_z() };
+        let eye = Point3::from_vec(centre + direction * 2.0 * radius);
+        let view_mat = Matrix4::look_at(eye, Point3::from_vec(centre), up);
+        let proj_mat = ortho(-radius, radius, -radius, radius, radius, 3.0 * radius);
+
+        self.light_mat = proj_mat * view_mat;
+    }
+
+    // Faces drawn after this only go into the map
+    pub fn activate(&self) {
+        with_backend(|backend| {
+            backend.bind_framebuffer(self.framebuffer.id, self.framebuffer.size);
+            backend.clear((1.0, 1.0, 1.0, 1.0));
+        });
+    }
+
+    // -------------------------------------------------------------------------
+    // PASS TO SHADER
+    // -------------------------------------------------------------------------
+    // For the solid shaders, the shadow falls from the first directional light
+    pub fn pass_to_shader(&self, shader: &Shader) {
+        with_backend(|backend| backend.bind_texture(SHADOW_TEXTURE_UNIT, self.framebuffer.depth_texture));
+
+        shader.pass_sampler("shadowMap", SHADOW_TEXTURE_UNIT);
+        shader.pass_int("shadowsEnabled", 1);
+        shader.pass_matrix("lightMat", &self.light_mat);
+        shader.pass_float("shadowBias", self.bias);
+        shader.pass_int("shadowPcfRadius", self.pcf_radius);
+    }
+
+    // -------------------------------------------------------------------------
+    // DEBUG VIEW
+    // -------------------------------------------------------------------------
+    // The map in a square at the bottom left of the bound framebuffer, over
+    // whatever is there, with the shader that shows depth as grey
+    pub fn render_debug(&self, shader: &Shader, target_size: (u32, u32)) {
+        let size = target_size.0.min(target_size.1) / DEBUG_VIEW_FRACTION;
+
+        shader.pass_int("layer", 1);
+        shader.pass_sampler("textureSampler", 0);
+
+        with_backend(|backend| {
+            backend.set_viewport((0, 0), (size, size));
+            backend.set_depth_test(false);
+            backend.bind_texture(0, self.framebuffer.depth_texture);
+            backend.draw_arrays(self.quad_vao, Primitive::Triangles, 0, 6);
+            backend.bind_texture(0, 0);
+            backend.set_depth_test(true);
+            backend.set_viewport((0, 0), target_size);
+        });
+    }
+}
+
+
+// Shaders that sample shadows need this when there's no map. The sampler still
+// gets its own unit, left at zero it would share one with a sampler of
+// another type and the draw would fail
+pub fn disable_shadows(shader: &Shader) {
+    shader.pass_sampler("shadowMap", SHADOW_TEXTURE_UNIT);
+    shader.pass_int("shadowsEnabled", 0);
+}
@@ -0,0 +1,457 @@
+extern crate image;
+
+use cgmath::{Matrix4, Point3, Vector2, Vector3, Vector4};
+use cgmath::prelude::*;
+
+use super::model::{Model, SIZE_VERTEX_F32};
+use super::lighting::Lighting;
+use super::scene::normal_matrix;
+
+
+// ---- SAME VALUES AS THE SHADERS ----
+const HIGHLIGHT_COLOUR: (f32, f32, f32, f32) = (0.7, 0.7, 0.0, 1.0);
+const WIREFRAME_COLOUR: (f32, f32, f32, f32) = (0.0, 0.5, 0.0, 1.0);
+const POINT_SIZE: f32 = 10.0;
+
+// Lines and points drawn over faces of the same model would fight with them
+// for the depth, they are pulled towards the camera by this much
+const WIREFRAME_DEPTH_BIAS: f32 = 0.0001;
+
+
+// ---- RASTERIZER ON THE CPU ----
+// Draws models the way the simple and wireframe shaders do, into a colour and
+// a depth buffer. Rows start at the bottom like in GL
+pub struct SoftwareRaster {
+    size: (u32, u32),
+    colour: Vec<Vector4<f32>>,
+    depth: Vec<f32>,
+    pub clear_colour: (f32, f32, f32, f32)
+}
+
+// Vertex after the vertex shader, position in clip space
+#[derive(Clone, Copy)]
+struct ClipVertex {
+    pos: Vector4<f32>,
+    colour: Vector4<f32>
+}
+
+
+impl SoftwareRaster {
+    // Cleared to the background of the camera
+    pub fn new(size: (u32, u32)) -> SoftwareRaster {
+        let mut raster = SoftwareRaster {
+            size,
+            colour: Vec::new(),
+            depth: Vec::new(),
+            clear_colour: (0.1, 0.1, 0.1, 1.0)
+        };
+        raster.clear();
+
+        raster
+    }
+
+    pub fn clear(&mut self) {
+        let n_pixels = (self.size.0 * self.size.1) as usize;
+        let (r, g, b, a) = self.clear_colour;
+        self.colour = vec![Vector4::new(r, g, b, a); n_pixels];
+        self.depth = vec![1.0; n_pixels];
+    }
+
+    // -------------------------------------------------------------------------
+    // RENDER SOLID
+    // -------------------------------------------------------------------------
+    // Faces with the palette, vertex colours and light of simple.vs/simple.fs,
+    // the eye is where the camera is and where it looks. Back faces are culled
+    // like in the editor
+    pub fn draw_solid(&mut self,
+                      model: &Model,
+                      trans_mat: &Matrix4<f32>,
+                      model_mat: &Matrix4<f32>,
+                      lighting: &Lighting,
+                      eye: (Point3<f32>, Vector3<f32>)) {
+        let data = model.vertex_data();
+        let indices = model.index_data();
+        let (start, length) = model.solid_range();
+
+        let normal_mat = normal_matrix(model_mat);
+
+        let palette: Vec<Vector4<f32>> = model.palette().entries().iter()
+            .map(|entry| Vector4::new(entry.colour.0, entry.colour.1, entry.colour.2, entry.colour.3))
+            .collect();
+
+        // The light goes in the vertex colour and is interpolated over the
+        // face, alpha is left alone so the image stays opaque
+        let shade = |vert_index: usize| -> ClipVertex {
+            let vert = &data[vert_index * SIZE_VERTEX_F32 as usize..];
+            let pos = trans_mat * Vector4::new(vert[0], vert[1], vert[2], 1.0);
+
+            let colour = if vert[3] > 0.0 {
+                tuple_to_vec(HIGHLIGHT_COLOUR)
+            } else {
+                let entry = match palette.len() {
+                    0 => Vector4::zero(),
+                    n => palette[((vert[7] + 0.5) as usize).min(n - 1)]
+                };
+                entry.lerp(Vector4::new(vert[8], vert[9], vert[10], vert[11]), vert[12])
+            };
+
+            let world_pos = (model_mat * Vector4::new(vert[0], vert[1], vert[2], 1.0)).truncate();
+            let normal = normal_mat * Vector3::new(vert[4], vert[5], vert[6]);
+            let light = lighting.light_at(world_pos, normal, eye.0, eye.1);
+
+            ClipVertex {
+                pos,
+                colour: colour.truncate().mul_element_wise(light).extend(colour.w)
+            }
+        };
+
+        for triangle in indices[start..start + length].chunks(3) {
+            if triangle.len() == 3 {
+                let corners = [shade(triangle[0] as usize),
+                               shade(triangle[1] as usize),
+                               shade(triangle[2] as usize)];
+                self.draw_triangle(corners);
+            }
+        }
+    }
+
+    // -------------------------------------------------------------------------
+    // RENDER VERTICES AND LINES
+    // -------------------------------------------------------------------------
+    // Same as render_wf, every vertex as a square point and then the lines
+    pub fn draw_wireframe(&mut self, model: &Model, trans_mat: &Matrix4<f32>) {
+        let data = model.vertex_data();
+        let indices = model.index_data();
+        let (start, length) = model.wireframe_range();
+
+        let shade = |vert_index: usize| -> ClipVertex {
+            let vert = &data[vert_index * SIZE_VERTEX_F32 as usize..];
+            ClipVertex {
+                pos: trans_mat * Vector4::new(vert[0], vert[1], vert[2], 1.0),
+                colour: tuple_to_vec(if vert[3] > 0.0 { HIGHLIGHT_COLOUR } else { WIREFRAME_COLOUR })
+            }
+        };
+
+        for vert_index in 0..model.n_points() {
+            self.draw_point(shade(vert_index));
+        }
+
+        for line in indices[start..start + length].chunks(2) {
+            if line.len() == 2 {
+                self.draw_line(shade(line[0] as usize), shade(line[1] as usize));
+            }
+        }
+    }
+
+    // -------------------------------------------------------------------------
+    // OUTPUT
+    // -------------------------------------------------------------------------
+    pub fn to_image(&self) -> image::RgbaImage {
+        let (width, height) = self.size;
+        image::ImageBuffer::from_fn(width, height, |x, y| {
+            let colour = self.colour[((height - 1 - y) * width + x) as usize];
+            image::Rgba([to_byte(colour.x), to_byte(colour.y), to_byte(colour.z), to_byte(colour.w)])
+        })
+    }
+
+    pub fn write_to_file(&self, path_to_file: &str) {
+        if let Err(e) = self.to_image().save(path_to_file) {
+            println!("Could not write {}: {}", path_to_file, e);
+        }
+    }
+
+    // -------------------------------------------------------------------------
+    // TRIANGLES
+    // -------------------------------------------------------------------------
+    fn draw_triangle(&mut self, corners: [ClipVertex; 3]) {
+        let polygon = clip_near(&corners);
+        for fan_index in 1..polygon.len().saturating_sub(1) {
+            self.fill_triangle([polygon[0], polygon[fan_index], polygon[fan_index + 1]]);
+        }
+    }
+
+    // Barycentric fill over the bounding box, pixel centres on the edges are
+    // drawn. Colours are interpolated with perspective, depth is not
+    fn fill_triangle(&mut self, corners: [ClipVertex; 3]) {
+        let screen: Vec<Vector3<f32>> = corners.iter().map(|corner| self.to_screen(corner.pos)).collect();
+
+        let area = edge(screen[0], screen[1], screen[2]);
+        if area <= 0.0 {
+            return;
+        }
+
+        let min_x = screen.iter().fold(f32::MAX, |min, pos| min.min(pos.x)).max(0.0) as u32;
+        let min_y = screen.iter().fold(f32::MAX, |min, pos| min.min(pos.y)).max(0.0) as u32;
+        let max_x = screen.iter().fold(f32::MIN, |max, pos| max.max(pos.x)).min(self.size.0 as f32 - 1.0);
+        let max_y = screen.iter().fold(f32::MIN, |max, pos| max.max(pos.y)).min(self.size.1 as f32 - 1.0);
+        if max_x < 0.0 || max_y < 0.0 {
+            return;
+        }
+
+        let inv_w: Vec<f32> = corners.iter().map(|corner| 1.0 / corner.pos.w).collect();
+
+        for y in min_y..=(max_y as u32) {
+            for x in min_x..=(max_x as u32) {
+                let pixel = Vector3::new(x as f32 + 0.5, y as f32 + 0.5, 0.0);
+                let b0 = edge(screen[1], screen[2], pixel) / area;
+                let b1 = edge(screen[2], screen[0], pixel) / area;
+                let b2 = edge(screen[0], screen[1], pixel) / area;
+                if b0 < 0.0 || b1 < 0.0 || b2 < 0.0 {
+                    continue;
+                }
+
+                let depth = b0 * screen[0].z + b1 * screen[1].z + b2 * screen[2].z;
+
+                let (p0, p1, p2) = (b0 * inv_w[0], b1 * inv_w[1], b2 * inv_w[2]);
+                let colour = (corners[0].colour * p0 + corners[1].colour * p1 + corners[2].colour * p2)
+                           / (p0 + p1 + p2);
+
+                self.write_pixel(x as i32, y as i32, depth, colour);
+            }
+        }
+    }
+
+    // -------------------------------------------------------------------------
+    // LINES AND POINTS
+    // -------------------------------------------------------------------------
+    fn draw_line(&mut self, a: ClipVertex, b: ClipVertex) {
+        let (a, b) = match clip_line_near(a, b) {
+            Some(clipped) => clipped,
+            None => return
+        };
+
+        let start = self.to_screen(a.pos);
+        let end = self.to_screen(b.pos);
+        let n_steps = (end.x - start.x).abs().max((end.y - start.y).abs()).ceil().max(1.0) as usize;
+
+        for step in 0..=n_steps {
+            let fraction = step as f32 / n_steps as f32;
+            let pos = start.lerp(end, fraction);
+            let colour = a.colour.lerp(b.colour, fraction);
+            self.write_pixel(pos.x.floor() as i32, pos.y.floor() as i32,
+                             pos.z - WIREFRAME_DEPTH_BIAS, colour);
+        }
+    }
+
+    fn draw_point(&mut self, point: ClipVertex) {
+        if point.pos.z < -point.pos.w || point.pos.w <= 0.0 {
+            return;
+        }
+
+        let centre = self.to_screen(point.pos);
+        let half = POINT_SIZE / 2.0;
+        let min = Vector2::new((centre.x - half).round() as i32, (centre.y - half).round() as i32);
+        for y in min.y..min.y + POINT_SIZE as i32 {
+            for x in min.x..min.x + POINT_SIZE as i32 {
+                self.write_pixel(x, y, centre.z - WIREFRAME_DEPTH_BIAS, point.colour);
+            }
+        }
+    }
+
+    // -------------------------------------------------------------------------
+    // PIXELS
+    // -------------------------------------------------------------------------
+    // Clip space to pixels, with the depth mapped to 0..1 like glDepthRange
+    fn to_screen(&self, pos: Vector4<f32>) -> Vector3<f32> {
+        let ndc = pos.truncate() / pos.w;
+        Vector3::new((ndc.x + 1.0) * 0.5 * self.size.0 as f32,
+                     (ndc.y + 1.0) * 0.5 * self.size.1 as f32,
+                     (ndc.z + 1.0) * 0.5)
+    }
+
+    // Depth test is GL_LESS, anything past the far plane is dropped
+    fn write_pixel(&mut self, x: i32, y: i32, depth: f32, colour: Vector4<f32>) {
+        if x < 0 || y < 0 || x >= self.size.0 as i32 || y >= self.size.1 as i32 || depth > 1.0 {
+            return;
+        }
+
+        let pixel_index = (y as u32 * self.size.0 + x as u32) as usize;
+        if depth < self.depth[pixel_index] {
+            self.depth[pixel_index] = depth;
+            self.colour[pixel_index] = colour;
+        }
+    }
+}
+
+
+// -----------------------------------------------------------------------------
+// MISC FUNCTIONS
+// -----------------------------------------------------------------------------
+// Twice the signed area of abp, positive when counter-clockwise
+fn edge(a: Vector3<f32>, b: Vector3<f32>, p: Vector3<f32>) -> f32 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+// Only the near plane needs clipping, the rest is done by the bounding box
+fn clip_near(corners: &[ClipVertex; 3]) -> Vec<ClipVertex> {
+    let mut polygon = Vec::<ClipVertex>::new();
+    for corner_index in 0..3 {
+        let a = corners[corner_index];
+        let b = corners[(corner_index + 1) % 3];
+        let a_dist = a.pos.z + a.pos.w;
+        let b_dist = b.pos.z + b.pos.w;
+
+        if a_dist >= 0.0 {
+            polygon.push(a);
+        }
+        if (a_dist >= 0.0) != (b_dist >= 0.0) {
+            polygon.push(lerp_vertex(a, b, a_dist / (a_dist - b_dist)));
+        }
+    }
+    polygon
+}
+
+fn clip_line_near(a: ClipVertex, b: ClipVertex) -> Option<(ClipVertex, ClipVertex)> {
+    let a_dist = a.pos.z + a.pos.w;
+    let b_dist = b.pos.z + b.pos.w;
+
+    match (a_dist >= 0.0, b_dist >= 0.0) {
+        (true, true) => Some((a, b)),
+        (true, false) => Some((a, lerp_vertex(a, b, a_dist / (a_dist - b_dist)))),
+        (false, true) => Some((lerp_vertex(a, b, a_dist / (a_dist - b_dist)), b)),
+        (false, false) => None
+    }
+}
+
+fn lerp_vertex(a: ClipVertex, b: ClipVertex, fraction: f32) -> ClipVertex {
+    ClipVertex {
+        pos: a.pos.lerp(b.pos, fraction),
+        colour: a.colour.lerp(b.colour, fraction)
+    }
+}
+
+fn tuple_to_vec(colour: (f32, f32, f32, f32)) -> Vector4<f32> {
+    Vector4::new(colour.0, colour.1, colour.2, colour.3)
+}
+
+fn to_byte(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::backend::{set_backend, RecordingBackend};
+
+    use cgmath::{perspective, Deg};
+
+    const SIZE: u32 = 32;
+    const RED: (f32, f32, f32, f32) = (1.0, 0.0, 0.0, 1.0);
+    const NEAR: f32 = 1.0;
+    const FAR: f32 = 10.0;
+
+    // Square image looking down -Z from the eye with a right angle field of
+    // view, so a point at (x, y, -d) lands at x / d, y / d
+    fn total_mat(eye: Point3<f32>) -> Matrix4<f32> {
+        perspective(Deg(90.0), 1.0, NEAR, FAR) *
+            Matrix4::look_at(eye, eye - Vector3::unit_z(), Vector3::unit_y())
+    }
+
+    // Only ambient light so faces keep their palette colour
+    fn flat_light() -> Lighting {
+        let mut lighting = Lighting::create_empty();
+        lighting.ambient = Vector3::new(1.0, 1.0, 1.0);
+        lighting
+    }
+
+    fn draw(model: &mut Model, eye: Point3<f32>) -> SoftwareRaster {
+        model.palette_mut().set_colour(0, RED);
+        model.update_gpu_data();
+
+        let mut raster = SoftwareRaster::new((SIZE, SIZE));
+        raster.draw_solid(model, &total_mat(eye), &Matrix4::identity(), &flat_light(),
+                          (eye, -Vector3::unit_z()));
+        raster
+    }
+
+    fn triangle(corners: [Vector3<f32>; 3]) -> Model {
+        let mut model = Model::create_headless();
+        for corner in corners.iter() {
+            model.add_vert(*corner);
+        }
+        model.add_face(&vec![0, 1, 2], 0.0);
+        model
+    }
+
+    // Pixels are addressed from the bottom left like the buffers
+    fn pixel(raster: &SoftwareRaster, x: u32, y: u32) -> (Vector4<f32>, f32) {
+        let index = (y * SIZE + x) as usize;
+        (raster.colour[index], raster.depth[index])
+    }
+
+    fn ndc_depth(distance: f32) -> f32 {
+        let ndc = (FAR + NEAR) / (FAR - NEAR) - 2.0 * FAR * NEAR / ((FAR - NEAR) * distance);
+        (ndc + 1.0) * 0.5
+    }
+
+    #[test]
+    fn cube_front_face_covers_the_centre() {
+        // The cube needs GPU objects, nothing is drawn with them
+        set_backend(Box::new(RecordingBackend::new()));
+        let mut cube = Model::create_cube();
+        let raster = draw(&mut cube, Point3::new(0.0, 0.0, 3.0));
+
+        // The front face is 2.5 away and a unit wide, a fifth of the view
+        let (colour, depth) = pixel(&raster, SIZE / 2, SIZE / 2);
+        assert_eq!(colour, tuple_to_vec(RED));
+        assert!((depth - ndc_depth(2.5)).abs() < 1e-4, "depth {}", depth);
+
+        for (x, y) in [(13, 16), (18, 16), (16, 13), (16, 18)].iter() {
+            assert_eq!(pixel(&raster, *x, *y).0, tuple_to_vec(RED));
+        }
+        for (x, y) in [(0, 0), (SIZE - 1, SIZE - 1), (12, 16), (16, 19)].iter() {
+            let (colour, depth) = pixel(&raster, *x, *y);
+            assert_eq!(colour, tuple_to_vec(raster.clear_colour));
+            assert_eq!(depth, 1.0);
+        }
+
+        let image = raster.to_image();
+        assert_eq!(image.get_pixel(SIZE / 2, SIZE / 2).data, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(0, 0).data, [26, 26, 26, 255]);
+    }
+
+    #[test]
+    fn back_faces_are_culled() {
+        let eye = Point3::new(0.0, 0.0, 0.0);
+        let corners = [Vector3::new(-1.0, -1.0, -2.0), Vector3::new(1.0, -1.0, -2.0), Vector3::new(0.0, 1.0, -2.0)];
+
+        let raster = draw(&mut triangle(corners), eye);
+        let (colour, depth) = pixel(&raster, SIZE / 2, SIZE / 2);
+        assert_eq!(colour, tuple_to_vec(RED));
+        assert!((depth - ndc_depth(2.0)).abs() < 1e-4, "depth {}", depth);
+
+        let raster = draw(&mut triangle([corners[0], corners[2], corners[1]]), eye);
+        assert!(raster.depth.iter().all(|depth| *depth == 1.0));
+    }
+
+    #[test]
+    fn near_plane_clips_what_is_behind_the_eye() {
+        // The tip is behind the eye, the near plane cuts the triangle along
+        // the middle of the view at y = 0
+        let eye = Point3::new(0.0, 0.0, 0.0);
+        let corners = [Vector3::new(-1.0, -1.0, -3.0), Vector3::new(1.0, -1.0, -3.0), Vector3::new(0.0, 1.0, 1.0)];
+        let raster = draw(&mut triangle(corners), eye);
+
+        assert!(raster.depth.iter().all(|depth| (0.0..=1.0).contains(depth)));
+
+        // The plane goes through y = (1 - d) / 2 at a distance d, so the pixel
+        // just under the cut at y / d = -1 / 32 is barely past the near plane
+        let (colour, depth) = pixel(&raster, SIZE / 2, SIZE / 2 - 1);
+        assert_eq!(colour, tuple_to_vec(RED));
+        assert!((depth - ndc_depth(1.0 / (1.0 - 2.0 / 32.0))).abs() < 1e-4, "depth {}", depth);
+
+        // Further down at y / d = -7 / 32
+        let (colour, depth) = pixel(&raster, SIZE / 2, SIZE / 4 + 4);
+        assert_eq!(colour, tuple_to_vec(RED));
+        assert!((depth - ndc_depth(1.0 / (1.0 - 14.0 / 32.0))).abs() < 1e-4, "depth {}", depth);
+
+        // Nothing over the cut nor under the bottom edge
+        for y in [SIZE / 2, SIZE - 1, SIZE / 4].iter() {
+            assert_eq!(pixel(&raster, SIZE / 2, *y).1, 1.0);
+        }
+    }
+}
@@ -0,0 +1,110 @@
+extern crate gl;
+use self::gl::types::*;
+
+extern crate image;
+
+use std::os::raw::c_void;
+
+use super::backend::with_backend;
+
+
+pub struct Texture {
+    id: u32
+}
+
+// Textures are loaded once per path, like models in a scene. Failed loads are
+// remembered too so they are not retried every frame
+pub struct TextureCache {
+    textures: Vec<Option<Texture>>,
+    paths: Vec<String>
+}
+
+
+impl Texture {
+    // PNG and JPEG go through the image crate, the rows are flipped since GL
+    // starts at the bottom
+    pub fn load_from_file(path_to_file: &str) -> Option<Texture> {
+        let image = match image::open(path_to_file) {
+            Ok(image) => image.flipv().to_rgba(),
+            Err(e) => {
+                println!("Could not load texture {}: {}", path_to_file, e);
+                return None;
+            }
+        };
+
+        let size = image.dimensions();
+        let data = image.into_raw();
+
+        let mut texture = Texture {
+            id: 0
+        };
+
+        unsafe {
+            gl::GenTextures(1, &mut texture.id);
+            gl::BindTexture(gl::TEXTURE_2D, texture.id);
+            gl::TexImage2D
+               (gl::TEXTURE_2D, 0, gl::RGBA as i32,
+                size.0 as i32, size.1 as i32, 0,
+                gl::RGBA, gl::UNSIGNED_BYTE,
+                &data[0] as *const u8 as *const c_void);
+            gl::GenerateMipmap(gl::TEXTURE_2D);
+
+            // Nearest on magnification keeps the pixelated look of the camera
+            gl::TexParameteri
+               (gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
+            gl::TexParameteri
+               (gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
+            gl::TexParameteri
+               (gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST_MIPMAP_LINEAR as i32);
+            gl::TexParameteri
+               (gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+
+        Some(texture)
+    }
+
+    pub fn bind(&self, unit: u32) {
+        with_backend(|backend| backend.bind_texture(unit, self.id));
+    }
+}
+
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.id as *const GLuint);
+        }
+    }
+}
+
+
+impl TextureCache {
+    pub fn new() -> TextureCache {
+        TextureCache {
+            textures: Vec::new(),
+            paths: Vec::new()
+        }
+    }
+
+    pub fn load(&mut self, path_to_file: &str) -> Option<&Texture> {
+        let texture_index = match self.paths.iter().position(|path| path == path_to_file) {
+            Some(texture_index) => texture_index,
+            None => {
+                self.textures.push(Texture::load_from_file(path_to_file));
+                self.paths.push(String::from(path_to_file));
+                self.textures.len() - 1
+            }
+        };
+
+        self.textures[texture_index].as_ref()
+    }
+
+    // Forget a path so it is loaded again from disk next time
+    pub fn forget(&mut self, path_to_file: &str) {
+        if let Some(texture_index) = self.paths.iter().position(|path| path == path_to_file) {
+            self.textures.remove(texture_index);
+            self.paths.remove(texture_index);
+        }
+    }
+}
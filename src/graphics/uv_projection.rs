@@ -0,0 +1,113 @@
+use cgmath::{Vector2, Vector3};
+use cgmath::prelude::*;
+
+use std::f32::consts::PI;
+
+
+// ---- WAYS OF GENERATING UVS FROM POSITIONS ----
+// Planar maps along two axes whose length is one texture repeat. Box picks the
+// pair of axes facing each face the most, scaled by the size of the box.
+// Cylindrical wraps U around the axis and runs V along it over the height
+#[derive(Clone, Copy)]
+pub enum UvProjection {
+    Planar {
+        origin: Vector3<f32>,
+        u_axis: Vector3<f32>,
+        v_axis: Vector3<f32>
+    },
+    Box {
+        origin: Vector3<f32>,
+        size: Vector3<f32>
+    },
+    Cylindrical {
+        origin: Vector3<f32>,
+        axis: Vector3<f32>,
+        height: f32
+    }
+}
+
+
+impl UvProjection {
+    // -------------------------------------------------------------------------
+    // FIT TO BOUNDS
+    // -------------------------------------------------------------------------
+    // Seen from above, X goes to U and Y to V
+    pub fn planar_fit(min: Vector3<f32>, max: Vector3<f32>) -> UvProjection {
+        let size = max - min;
+        UvProjection::Planar {
+            origin: min,
+            u_axis: Vector3::unit_x() / size.x.max(1e-6),
+            v_axis: Vector3::unit_y() / size.y.max(1e-6)
+        }
+    }
+
+    pub fn box_fit(min: Vector3<f32>, max: Vector3<f32>) -> UvProjection {
+        UvProjection::Box {
+            origin: min,
+            size: max - min
+        }
+    }
+
+    // Around the vertical line through the centre of the bounds
+    pub fn cylindrical_fit(min: Vector3<f32>, max: Vector3<f32>) -> UvProjection {
+        let centre = (min + max) / 2.0;
+        UvProjection::Cylindrical {
+            origin: Vector3::new(centre.x, centre.y, min.z),
+            axis: Vector3::unit_z(),
+            height: max.z - min.z
+        }
+    }
+
+    // -------------------------------------------------------------------------
+    // PROJECT ONE FACE
+    // -------------------------------------------------------------------------
+    pub fn project_face(&self, corners: [Vector3<f32>; 3]) -> [Vector2<f32>; 3] {
+        match *self {
+            UvProjection::Planar { origin, u_axis, v_axis } => {
+                let project = |pos: Vector3<f32>| {
+                    Vector2::new((pos - origin).dot(u_axis), (pos - origin).dot(v_axis))
+                };
+                [project(corners[0]), project(corners[1]), project(corners[2])]
+            },
+            UvProjection::Box { origin, size } => {
+                let normal = (corners[1] - corners[0]).cross(corners[2] - corners[0]);
+                let size = Vector3::new(size.x.max(1e-6), size.y.max(1e-6), size.z.max(1e-6));
+
+                // Drop the axis the face looks along
+                let project = |pos: Vector3<f32>| {
+                    let local = pos - origin;
+                    if normal.x.abs() >= normal.y.abs() && normal.x.abs() >= normal.z.abs() {
+                        Vector2::new(local.y / size.y, local.z / size.z)
+                    } else if normal.y.abs() >= normal.z.abs() {
+                        Vector2::new(local.x / size.x, local.z / size.z)
+                    } else {
+                        Vector2::new(local.x / size.x, local.y / size.y)
+                    }
+                };
+                [project(corners[0]), project(corners[1]), project(corners[2])]
+            },
+            UvProjection::Cylindrical { origin, axis, height } => {
+                let axis = axis.normalize();
+                let side = if axis.x.abs() < 0.9 { Vector3::unit_x() } else { Vector3::unit_y() };
+                let side_u = (side - axis * side.dot(axis)).normalize();
+                let side_v = axis.cross(side_u);
+
+                let project = |pos: Vector3<f32>| {
+                    let local = pos - origin;
+                    let angle = local.dot(side_v).atan2(local.dot(side_u));
+                    Vector2::new(angle / (2.0 * PI) + 0.5, local.dot(axis) / height.max(1e-6))
+                };
+                let mut uvs = [project(corners[0]), project(corners[1]), project(corners[2])];
+
+                // Faces across the seam would wrap the whole texture
+                let max_u = uvs.iter().fold(0.0f32, |max_u, uv| max_u.max(uv.x));
+                for uv in uvs.iter_mut() {
+                    if max_u - uv.x > 0.5 {
+                        uv.x += 1.0;
+                    }
+                }
+                uvs
+            }
+        }
+    }
+}
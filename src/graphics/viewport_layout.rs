@@ -0,0 +1,243 @@
+use cgmath::Vector2;
+
+use super::shader::Shader;
+use super::camera::create_screen_quad;
+use super::backend::{with_backend, Primitive};
+
+
+// ---- PLAYERS ON ONE SCREEN ----
+pub const MAX_VIEWS: usize = 4;
+
+// One colour per player, in the order of the views
+const PLAYER_COLOURS: [(f32, f32, f32, f32); MAX_VIEWS] = [(0.0, 0.9, 1.0, 1.0),
+                                                          (1.0, 0.5, 0.0, 1.0),
+                                                          (0.9, 0.9, 0.1, 1.0),
+                                                          (0.9, 0.1, 0.8, 1.0)];
+
+// Height in pixels of the bar along the top of each view, doubled for the
+// view that has the input
+const OVERLAY_THICKNESS: u32 = 3;
+
+// Part of the screen in pixels, from the bottom left corner like in GL
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ScreenRect {
+    pub origin: (u32, u32),
+    pub size: (u32, u32)
+}
+
+// Views go from the top left, one per camera. Two views are stacked, three
+// have the first one across the top and four are two by two. The separators
+// are the gaps between views, where the clear colour shows
+pub struct ViewportLayout {
+    screen_size: (u32, u32),
+    view_count: usize,
+    rects: Vec<ScreenRect>,
+    quad_vao: u32,
+    pub separator_width: u32,
+    pub separator_colour: (f32, f32, f32, f32)
+}
+
+
+impl ScreenRect {
+    pub fn contains(&self, point: (u32, u32)) -> bool {
+        point.0 >= self.origin.0 && point.0 < self.origin.0 + self.size.0 &&
+        point.1 >= self.origin.1 && point.1 < self.origin.1 + self.size.1
+    }
+}
+
+
+impl ViewportLayout {
+    pub fn create(view_count: usize, screen_size: (u32, u32)) -> ViewportLayout {
+        let mut layout = ViewportLayout {
+            screen_size,
+            view_count: view_count.clamp(1, MAX_VIEWS),
+            rects: Vec::new(),
+            quad_vao: create_screen_quad(),
+            separator_width: 4,
+            separator_colour: (0.6, 0.6, 0.6, 1.0)
+        };
+        layout.arrange();
+
+        layout
+    }
+
+    pub fn view_count(&self) -> usize {
+        self.view_count
+    }
+
+    pub fn screen_size(&self) -> (u32, u32) {
+        self.screen_size
+    }
+
+    pub fn rects(&self) -> &Vec<ScreenRect> {
+        &self.rects
+    }
+
+    // Between one and MAX_VIEWS
+    pub fn set_view_count(&mut self, view_count: usize) {
+        self.view_count = view_count.clamp(1, MAX_VIEWS);
+        self.arrange();
+    }
+
+    // For when the window changes size
+    pub fn resize(&mut self, screen_size: (u32, u32)) {
+        self.screen_size = screen_size;
+        self.arrange();
+    }
+
+    // -------------------------------------------------------------------------
+    // ARRANGEMENT
+    // -------------------------------------------------------------------------
+    fn arrange(&mut self) {
+        let columns_per_row: &[u32] = match self.view_count {
+            1 => &[1],
+            2 => &[1, 1],
+            3 => &[1, 2],
+            _ => &[2, 2]
+        };
+
+        let (width, height) = self.screen_size;
+        let n_rows = columns_per_row.len() as u32;
+        let half_gap = self.separator_width / 2;
+
+        self.rects = Vec::new();
+        for (row, n_columns) in columns_per_row.iter().enumerate() {
+            let row = row as u32;
+
+            // Rows are counted from the top, GL counts from the bottom
+            let top = height - height * row / n_rows;
+            let bottom = height - height * (row + 1) / n_rows;
+            let bottom = if row + 1 < n_rows { bottom + half_gap } else { bottom };
+            let top = if row > 0 { top.saturating_sub(half_gap) } else { top };
+
+            for column in 0..*n_columns {
+                let left = width * column / n_columns;
+                let right = width * (column + 1) / n_columns;
+                let left = if column > 0 { left + half_gap } else { left };
+                let right = if column + 1 < *n_columns { right.saturating_sub(half_gap) } else { right };
+
+                self.rects.push(ScreenRect {
+                    origin: (left, bottom),
+                    size: (right.saturating_sub(left).max(1), top.saturating_sub(bottom).max(1))
+                });
+            }
+        }
+    }
+
+    // Render size of the camera of a view, so that a view half the height of
+    // the screen is half_height pixels tall and pixels stay square
+    pub fn render_size(&self, view_index: usize, half_height: u32) -> (u32, u32) {
+        let rect = &self.rects[view_index];
+        let scale = (self.screen_size.1 as f32 / 2.0 / half_height as f32).max(1.0);
+
+        (((rect.size.0 as f32 / scale).round() as u32).max(1),
+         ((rect.size.1 as f32 / scale).round() as u32).max(1))
+    }
+
+    // -------------------------------------------------------------------------
+    // MOUSE
+    // -------------------------------------------------------------------------
+    // View under a point in pixels from the top left of the screen, like the
+    // mouse
+    pub fn view_at(&self, point: Vector2<f32>) -> Option<usize> {
+        if point.x < 0.0 || point.y < 0.0 {
+            return None;
+        }
+
+        let point_gl = (point.x as u32, (self.screen_size.1 as f32 - point.y).max(0.0) as u32);
+        self.rects.iter().position(|rect| rect.contains(point_gl))
+    }
+
+    // Same point from the top left of the view
+    pub fn to_view(&self, view_index: usize, point: Vector2<f32>) -> Vector2<f32> {
+        let rect = &self.rects[view_index];
+        // Rects keep at least a pixel, so they stick out of a minimised window
+        let view_top = self.screen_size.1.saturating_sub(rect.origin.1 + rect.size.1) as f32;
+
+        Vector2::new(point.x - rect.origin.0 as f32, point.y - view_top)
+    }
+
+    // -------------------------------------------------------------------------
+    // COMPOSITING
+    // -------------------------------------------------------------------------
+    // The whole window cleared to the separator colour, views are drawn over it
+    pub fn begin_frame(&self) {
+        with_backend(|backend| {
+            backend.bind_framebuffer(0, self.screen_size);
+            backend.clear(self.separator_colour);
+        });
+    }
+
+    // Whatever is drawn next fills the view
+    pub fn activate_view(&self, view_index: usize) {
+        let rect = self.rects[view_index];
+        with_backend(|backend| backend.set_viewport(rect.origin, rect.size));
+    }
+
+    // Back to the whole window
+    pub fn end_frame(&self) {
+        with_backend(|backend| backend.set_viewport((0, 0), self.screen_size));
+    }
+
+    // A bar of the colour of the player along the top of each view, with the
+    // shader that fills the viewport with overlayColour
+    pub fn render_overlays(&self, shader: &Shader, active_view: usize) {
+        shader.pass_int("layer", 1);
+
+        for (view_index, rect) in self.rects.iter().enumerate() {
+            let thickness = if view_index == active_view { 2 * OVERLAY_THICKNESS } else { OVERLAY_THICKNESS };
+            let thickness = thickness.min(rect.size.1);
+
+            shader.pass_colour("overlayColour", PLAYER_COLOURS[view_index]);
+            with_backend(|backend| {
+                backend.set_viewport((rect.origin.0, rect.origin.1 + rect.size.1 - thickness),
+                                     (rect.size.0, thickness));
+                backend.set_depth_test(false);
+                backend.draw_arrays(self.quad_vao, Primitive::Triangles, 0, 6);
+                backend.set_depth_test(true);
+            });
+        }
+
+        self.end_frame();
+    }
+}
+
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::backend::{set_backend, RecordingBackend};
+
+    #[test]
+    fn points_map_into_their_view() {
+        set_backend(Box::new(RecordingBackend::new()));
+        let layout = ViewportLayout::create(3, (200, 100));
+
+        let point = Vector2::new(150.0, 75.0);
+        let view_index = layout.view_at(point).unwrap();
+        let rect = layout.rects[view_index];
+        let in_view = layout.to_view(view_index, point);
+
+        assert_eq!(rect.origin.0 as f32 + in_view.x, 150.0);
+        assert!(in_view.x >= 0.0 && in_view.x < rect.size.0 as f32);
+        assert!(in_view.y >= 0.0 && in_view.y < rect.size.1 as f32);
+    }
+
+    #[test]
+    fn minimised_windows_map_without_overflowing() {
+        set_backend(Box::new(RecordingBackend::new()));
+        for view_count in 1..5 {
+            let mut layout = ViewportLayout::create(view_count, (200, 100));
+            layout.resize((0, 0));
+
+            for view_index in 0..view_count {
+                let in_view = layout.to_view(view_index, Vector2::new(0.0, 0.0));
+                assert!(in_view.x <= 0.0 && in_view.y <= 0.0);
+            }
+        }
+    }
+}
+
@@ -7,7 +7,7 @@ use std::mem;
 
 pub enum InputAction {
     Close,
-    ToggleWF,
+    CycleDisplayMode,
     MoveForward,
     MoveBack,
     MoveLeft,
@@ -45,7 +45,74 @@ pub enum InputAction {
     EnterFace,
     DeleteVertex,
 
-    Select
+    Select,
+    CycleSelectMode,
+    BoxSelect,
+    LassoSelect,
+    ClearSelection,
+    SelectAdd,
+    SelectSubtract,
+    SelectToggle,
+
+    GroupFromSelection,
+    NextGroup,
+    SelectGroup,
+    ToggleGroupVisible,
+    ToggleGroupLocked,
+    IsolateGroup,
+
+    NextPrimitive,
+    AddPrimitive,
+
+    AddInstance,
+    RemoveInstance,
+    ToggleLodFade,
+    ToggleLodTint,
+
+    NextColour,
+    LinearGradient,
+    RadialGradient,
+    ClearColours,
+    EnterPaletteColour,
+    SavePalette,
+    LoadPalette,
+
+    ProjectPlanarUvs,
+    ProjectBoxUvs,
+    ProjectCylindricalUvs,
+    ToggleTexture,
+
+    ToggleCollisionMesh,
+    ProbeCollision,
+
+    RecordMorph,
+    PlayMorphs,
+    ToggleGpuMorphs,
+
+    PartFromGroup,
+    PlayClip,
+    KeyPart,
+
+    Screenshot,
+    ScreenshotUpscaled,
+    ScreenshotWindow,
+
+    ToggleHeadlight,
+    ToggleSpecular,
+    AmbientUp,
+    AmbientDown,
+    EnterLightDirection,
+
+    ToggleCelShading,
+    ToggleOutlines,
+    FewerCelBands,
+    MoreCelBands,
+
+    ToggleShadows,
+    ToggleShadowDebug,
+    NextShadowResolution,
+
+    CycleViewCount
 }
 
 struct Command {
@@ -104,7 +171,7 @@ impl Window {
         });
         window.commands.push(Command {
             key_id: Key::Tab,
-            action: InputAction::ToggleWF,
+            action: InputAction::CycleDisplayMode,
             is_down: false,
             was_just_pressed: false
         });
@@ -316,6 +383,334 @@ impl Window {
             is_down: false,
             was_just_pressed: false
         });
+        window.commands.push(Command {
+            key_id: Key::M,
+            action: InputAction::CycleSelectMode,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::B,
+            action: InputAction::BoxSelect,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::K,
+            action: InputAction::LassoSelect,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::C,
+            action: InputAction::ClearSelection,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::LeftShift,
+            action: InputAction::SelectAdd,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::LeftControl,
+            action: InputAction::SelectSubtract,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::LeftAlt,
+            action: InputAction::SelectToggle,
+            is_down: false,
+            was_just_pressed: false
+        });
+
+        window.commands.push(Command {
+            key_id: Key::G,
+            action: InputAction::GroupFromSelection,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::N,
+            action: InputAction::NextGroup,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::T,
+            action: InputAction::SelectGroup,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::H,
+            action: InputAction::ToggleGroupVisible,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::O,
+            action: InputAction::ToggleGroupLocked,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::KpDivide,
+            action: InputAction::IsolateGroup,
+            is_down: false,
+            was_just_pressed: false
+        });
+
+        window.commands.push(Command {
+            key_id: Key::KpMultiply,
+            action: InputAction::NextPrimitive,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::KpAdd,
+            action: InputAction::AddPrimitive,
+            is_down: false,
+            was_just_pressed: false
+        });
+
+        window.commands.push(Command {
+            key_id: Key::Kp1,
+            action: InputAction::AddInstance,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::Kp0,
+            action: InputAction::RemoveInstance,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::Kp5,
+            action: InputAction::ToggleLodFade,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::KpDecimal,
+            action: InputAction::ToggleLodTint,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::P,
+            action: InputAction::NextColour,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::Y,
+            action: InputAction::LinearGradient,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::U,
+            action: InputAction::RadialGradient,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::I,
+            action: InputAction::ClearColours,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::Kp7,
+            action: InputAction::EnterPaletteColour,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::Kp8,
+            action: InputAction::SavePalette,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::Kp9,
+            action: InputAction::LoadPalette,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::F7,
+            action: InputAction::ProjectPlanarUvs,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::F8,
+            action: InputAction::ProjectBoxUvs,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::F9,
+            action: InputAction::ProjectCylindricalUvs,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::Kp4,
+            action: InputAction::ToggleTexture,
+            is_down: false,
+            was_just_pressed: false
+        });
+
+        window.commands.push(Command {
+            key_id: Key::Kp6,
+            action: InputAction::ToggleCollisionMesh,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::Kp3,
+            action: InputAction::ProbeCollision,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::R,
+            action: InputAction::RecordMorph,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::E,
+            action: InputAction::PlayMorphs,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::KpSubtract,
+            action: InputAction::ToggleGpuMorphs,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::J,
+            action: InputAction::PartFromGroup,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::Z,
+            action: InputAction::PlayClip,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::Kp2,
+            action: InputAction::KeyPart,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::F10,
+            action: InputAction::Screenshot,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::F11,
+            action: InputAction::ScreenshotUpscaled,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::F12,
+            action: InputAction::ScreenshotWindow,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::Home,
+            action: InputAction::ToggleHeadlight,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::End,
+            action: InputAction::ToggleSpecular,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::PageUp,
+            action: InputAction::AmbientUp,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::PageDown,
+            action: InputAction::AmbientDown,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::Insert,
+            action: InputAction::EnterLightDirection,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::Backslash,
+            action: InputAction::ToggleCelShading,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::Apostrophe,
+            action: InputAction::ToggleOutlines,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::LeftBracket,
+            action: InputAction::FewerCelBands,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::RightBracket,
+            action: InputAction::MoreCelBands,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::Semicolon,
+            action: InputAction::ToggleShadows,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::Slash,
+            action: InputAction::ToggleShadowDebug,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::Equal,
+            action: InputAction::NextShadowResolution,
+            is_down: false,
+            was_just_pressed: false
+        });
+        window.commands.push(Command {
+            key_id: Key::GraveAccent,
+            action: InputAction::CycleViewCount,
+            is_down: false,
+            was_just_pressed: false
+        });
 
         window
     }
@@ -3,9 +3,11 @@ use self::glfw::{Context};
 
 extern crate gl;
 
-use cgmath::{Point3, Vector3};
+use cgmath::{Matrix4, Point3, Vector2, Vector3, Vector4};
 use cgmath::prelude::*;
 
+use std::path::Path;
+
 mod graphics;
 use graphics::*;
 
@@ -15,10 +17,22 @@ const SCREEN_WIDTH: u32 = 1600;
 const SCREEN_HEIGHT: u32 = 900;
 
 // ---- CAMERA SIZE ----
-// This should be fixed to 480x270 so that with 4 players it's 1920x1080
+// This should be fixed to 480x270 so that with 4 players it's 1920x1080. Views
+// of other shapes keep the same pixel size, CAM_HEIGHT for half the screen
 const CAM_WIDTH: u32 = 480;
 const CAM_HEIGHT: u32 = 270;
 
+// ---- SPLIT SCREEN ----
+// Where the camera of each view starts, the first one as the single camera
+// used to and the others from the top, left and front preset views
+const VIEW_STARTS: [([f32; 3], [f32; 3]); MAX_VIEWS] = [([-2.0, 0.0, 0.0], [0.0, 0.0, 0.0]),
+                                                       ([0.0, 0.0, 3.0], [0.0, MATH_PI / 2.0 - 0.01, 0.0]),
+                                                       ([0.0, 3.0, 0.0], [0.0, 0.0, -MATH_PI / 2.0]),
+                                                       ([3.0, 0.0, 0.0], [0.0, 0.0, MATH_PI])];
+
+// Upscaled screenshots of the camera come out at 1920x1080
+const SCREENSHOT_SCALE: u32 = 4;
+
 
 // ---- STUFF ----
 const INPUT_MODE_NOMINAL: i32 = 0;
@@ -26,11 +40,67 @@ const INPUT_MODE_ENTER_VERTEX: i32 = 1;
 const INPUT_MODE_ENTER_LINE: i32 = 2;
 const INPUT_MODE_ENTER_FACE: i32 = 3;
 const INPUT_MODE_DELETE_VERTEX: i32 = 4;
+const INPUT_MODE_BOX_SELECT: i32 = 5;
+const INPUT_MODE_LASSO_SELECT: i32 = 6;
+const INPUT_MODE_GRADIENT: i32 = 7;
+const INPUT_MODE_RECORD_MORPH: i32 = 8;
+const INPUT_MODE_LIGHT_DIRECTION: i32 = 9;
+const INPUT_MODE_PALETTE_COLOUR: i32 = 10;
+const INPUT_MODE_PART_KEY: i32 = 11;
+
+// Seconds between checks of the shader files for changes
+const SHADER_CHECK_INTERVAL: f32 = 0.5;
+
+// ---- SCENE ----
+// Kept between sessions with the last model, edited through its own node
+const SCENE_FILE: &str = "models/last_scene.scn";
+const LAST_MODEL_FILE: &str = "models/last_model.mdl";
+const EDITED_NODE: &str = "edited";
+// Instances of the edited model are lined up behind it, and switch to a
+// coarse stand-in below LOD_MIN_SIZE pixels across
+const INSTANCE_SPACING: f32 = 3.0;
+const LOD_MODEL_FILE: &str = "models/last_model.lod1.mdl";
+const LOD_MIN_SIZE: f32 = 40.0;
+const LOD_FADE_TIME: f32 = 0.3;
+
+// Size of the ball rolled along the probe ray, like a character would be
+const PROBE_RADIUS: f32 = 0.1;
+
+// Image given to the edited model, read again from disk every time it is
+// turned on
+const TEXTURE_FILE: &str = "models/last_texture.png";
+
+// Palettes can be shared between models through their own file
+const PALETTE_FILE: &str = "models/last_palette.pal";
+
+// ---- LIGHTING ----
+// Kept between sessions like the last model
+const LIGHTING_FILE: &str = "models/last_lighting.lgt";
+const HEADLIGHT_INTENSITY: f32 = 0.6;
+const SPECULAR_STRENGTH: f32 = 0.5;
+const SPECULAR_SHININESS: f32 = 32.0;
+const AMBIENT_STEP: f32 = 0.05;
+
+// Minimum mouse travel in pixels before a new lasso point is recorded
+const LASSO_POINT_SPACING: f32 = 5.0;
 
 const MATH_PI: f32 = std::f32::consts::PI;
 
 
 fn main() {
+    // ---- SUBCOMMANDS ----
+    // `editor diff old.mdl new.mdl [tolerance]` and
+    // `editor render model.mdl out.png [wireframe]` run without opening a window
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() > 1 && args[1] == "diff" {
+        diff_models(&args[2..]);
+        return;
+    }
+    if args.len() > 1 && args[1] == "render" {
+        render_headless(&args[2..]);
+        return;
+    }
+
     // -------------------------------------------------------------------------
     // INITIALISATION
     // -------------------------------------------------------------------------
@@ -42,6 +112,7 @@ fn main() {
        (&mut glfw, (SCREEN_WIDTH, SCREEN_HEIGHT), "TronWarp");
 
     let (scr_width, scr_height) = window.glfw_window.get_framebuffer_size();
+    let mut layout = ViewportLayout::create(1, (scr_width as u32, scr_height as u32));
 
     // ---- OPENGL INITIALISATION ----
     gl::load_with(|symbol| window.glfw_window.get_proc_address(symbol) as *const _);
@@ -59,39 +130,116 @@ fn main() {
         gl::Enable(gl::MULTISAMPLE);
     }
 
-    let normal_shader = Shader::create("shaders/simple.vs", "shaders/simple.fs");
-    let wf_shader = Shader::create("shaders/wireframe.vs", "shaders/wireframe.fs");
-    let cam_shader = Shader::create("shaders/camera.vs", "shaders/camera.fs");
+    let mut normal_shader = required_shader(Shader::create("shaders/simple.vs", "shaders/simple.fs"));
+    let mut textured_shader = required_shader(Shader::create("shaders/textured.vs", "shaders/textured.fs"));
+    let mut wf_shader = required_shader(Shader::create("shaders/wireframe.vs", "shaders/wireframe.fs"));
+    let mut cam_shader = required_shader(Shader::create("shaders/camera.vs", "shaders/camera.fs"));
+
+    // Cel shading variants of the solid shaders and the outlines around them
+    let mut cel_shader = required_shader(Shader::create_variant("shaders/simple.vs", "shaders/simple.fs", &[("CEL_SHADING", "")]));
+    let mut textured_cel_shader = required_shader(Shader::create_variant("shaders/textured.vs", "shaders/textured.fs", &[("CEL_SHADING", "")]));
+    let mut outline_shader = required_shader(Shader::create("shaders/outline.vs", "shaders/outline.fs"));
+
+    // Depth from the light and the view of it over the window
+    let mut shadow_shader = required_shader(Shader::create("shaders/shadow_depth.vs", "shaders/shadow_depth.fs"));
+    let mut shadow_debug_shader = required_shader(Shader::create("shaders/camera.vs", "shaders/shadow_debug.fs"));
+
+    // Player bars over the views
+    let mut overlay_shader = required_shader(Shader::create("shaders/camera.vs", "shaders/overlay.fs"));
 
     // ---- OBJECT CREATION ----
     let mut model = Model::create_empty();
 
     create_phat_ship(&mut model);
     model.write_to_file("models/phat_ship.mdl");
+    let swivel_path = clip_path("models/phat_ship.mdl", "swivel");
+    if !Path::new(&swivel_path).exists() {
+        create_pusher_swivel().write_to_file(&swivel_path);
+    }
+
+    // ---- SCENE ----
+    // The last model is loaded unless the scene already has it
+    let mut scene = Scene::new();
+    scene.load_from_file(SCENE_FILE);
+    let edited_model = match scene.find_model(LAST_MODEL_FILE) {
+        Some(model_index) => model_index,
+        None => {
+            // Load last model
+            model.load_from_file(LAST_MODEL_FILE);
+            scene.add_model(model, LAST_MODEL_FILE)
+        }
+    };
+    let mut edited_node = match scene.find_node(EDITED_NODE) {
+        Some(node_index) => node_index,
+        None => scene.add_node(EDITED_NODE, None, Some(edited_model), graphics::Transform::identity())
+    };
+
+    scene.model_mut(edited_model).clean();
+    scene.model_mut(edited_model).update_gpu_data();
+
+    if scene.lod_sets().is_empty() {
+        let stand_in = create_lod_stand_in(scene.model(edited_model));
+        stand_in.write_to_file(LOD_MODEL_FILE);
+        let stand_in_model = scene.add_model(stand_in, LOD_MODEL_FILE);
+
+        let mut lod_set = LodSet::new();
+        lod_set.add_level(edited_model, LOD_MIN_SIZE);
+        lod_set.add_level(stand_in_model, 0.0);
+        lod_set.fade_time = LOD_FADE_TIME;
+        scene.add_lod_set(lod_set);
+    }
+    let instance_lod = 0;
 
-    // Load last model
-    model.load_from_file("models/last_model.mdl");
+    let mut textures = TextureCache::new();
 
-    model.clean();
-    model.update_gpu_data();
+    let mut lighting = Lighting::load_from_file(LIGHTING_FILE).unwrap_or(Lighting::create_default());
+
+    let mut cel_shading = CelShading::create_default();
+    let mut cel_enabled = false;
+
+    let mut shadow_resolution_index = 1;
+    let mut shadow_map = ShadowMap::create(SHADOW_RESOLUTIONS[shadow_resolution_index]);
+    let mut shadows_enabled = false;
+    let mut shadow_debug = false;
+
+    // Clip previewed in the editor, keyed there and kept beside the model
+    let preview_path = clip_path(LAST_MODEL_FILE, "preview");
+    let mut clip = Clip::load_from_file(&preview_path).unwrap_or(Clip::new("preview"));
+    let mut clip_clock = AnimationClock::new();
 
     // ---- CAMERA CREATION ----
-    let mut camera = Camera::create
-       (60.0, (CAM_WIDTH, CAM_HEIGHT),
-        Point3::new(-2.0, 0.0, 0.0),
-        Vector3::new(0.0, 0.0, 0.0));
+    // One per view, only the active one takes the input
+    let mut cameras: Vec<Camera> = VIEW_STARTS.iter().map(|(pos, att)| {
+        Camera::create
+           (60.0, (CAM_WIDTH, CAM_HEIGHT),
+            Point3::new(pos[0], pos[1], pos[2]),
+            Vector3::new(att[0], att[1], att[2]))
+    }).collect();
+    fit_cameras(&mut cameras, &layout);
+    let mut active_view = 0;
 
     // ---- MISC VARIABLES ----
     let mut last_frame = 0.0;
+    let mut last_shader_check = 0.0;
     let mut delta_time;
 
-    let mut is_wf = true;
+    let mut display_mode = DisplayMode::Wireframe;
 
     let mut quit_flag = false;
 
     let mut input_mode = INPUT_MODE_NOMINAL;
     let mut input_string = String::new();
     let mut vert_indices = Vec::<usize>::new();
+    let mut select_anchor = Vector2::<f32>::zero();
+    let mut lasso_points = Vec::<Vector2<f32>>::new();
+    let mut active_group = 0;
+    let mut isolated_group: Option<usize> = None;
+    let mut active_colour = 0;
+    let mut active_primitive = 0;
+    let mut gradient_radial = false;
+    let mut morph_base = Vec::<Vector3<f32>>::new();
+    let mut morph_playing = false;
+    let mut morph_time = 0.0;
 
     // -------------------------------------------------------------------------
     // MAIN LOOP
@@ -102,18 +250,140 @@ fn main() {
         delta_time = curr_frame - last_frame;
         last_frame = curr_frame;
 
+        // ---- SHADER HOT RELOAD ----
+        if curr_frame - last_shader_check > SHADER_CHECK_INTERVAL {
+            last_shader_check = curr_frame;
+            normal_shader.reload_if_changed();
+            textured_shader.reload_if_changed();
+            wf_shader.reload_if_changed();
+            cam_shader.reload_if_changed();
+            cel_shader.reload_if_changed();
+            textured_cel_shader.reload_if_changed();
+            outline_shader.reload_if_changed();
+            shadow_shader.reload_if_changed();
+            shadow_debug_shader.reload_if_changed();
+            overlay_shader.reload_if_changed();
+        }
+
         // ---- PROCESS INPUT ----
         // Start by storing it all in the window
         window.process_input();
 
+        // ---- VIEWS ----
+        // The layout follows the size of the window
+        let (fb_width, fb_height) = window.glfw_window.get_framebuffer_size();
+        if (fb_width as u32, fb_height as u32) != layout.screen_size() {
+            layout.resize((fb_width as u32, fb_height as u32));
+            fit_cameras(&mut cameras, &layout);
+        }
+        if window.was_input_pressed(InputAction::CycleViewCount) {
+            layout.set_view_count(layout.view_count() % MAX_VIEWS + 1);
+            fit_cameras(&mut cameras, &layout);
+            println!("Views: {}", layout.view_count());
+        }
+
+        // The mouse works in the view it is over, which keeps the input until
+        // a command is done. Its position is in pixels from the top left of
+        // that view
+        let (win_width, _win_height) = window.glfw_window.get_size();
+        let mouse_screen = window.last_mouse_pos * (fb_width as f32 / win_width.max(1) as f32);
+        if input_mode == INPUT_MODE_NOMINAL {
+            if let Some(view_index) = layout.view_at(mouse_screen) {
+                active_view = view_index;
+            }
+        }
+        if active_view >= layout.view_count() {
+            active_view = 0;
+        }
+        let mouse_pos = layout.to_view(active_view, mouse_screen);
+
         // Various flag setting
         quit_flag = window.was_input_pressed(InputAction::Close);
         if window.glfw_window.should_close() {
             quit_flag = true;
         }
-        if window.was_input_pressed(InputAction::ToggleWF) {
-            is_wf = !is_wf;
+        if window.was_input_pressed(InputAction::CycleDisplayMode) {
+            display_mode = display_mode.next();
+            println!("Display mode: {}", display_mode.name());
+        }
+        if window.was_input_pressed(InputAction::ToggleCelShading) {
+            cel_enabled = !cel_enabled;
+            println!("Cel shading: {}", cel_enabled);
+        }
+        if window.was_input_pressed(InputAction::ToggleOutlines) {
+            cel_shading.outlines = !cel_shading.outlines;
+            println!("Outlines: {}", cel_shading.outlines);
         }
+        if window.was_input_pressed(InputAction::FewerCelBands) {
+            cel_shading.set_even_bands(cel_shading.bands().len().saturating_sub(1));
+            println!("Cel bands: {}", cel_shading.bands().len());
+        }
+        if window.was_input_pressed(InputAction::MoreCelBands) {
+            cel_shading.set_even_bands(cel_shading.bands().len() + 1);
+            println!("Cel bands: {}", cel_shading.bands().len());
+        }
+        if window.was_input_pressed(InputAction::ToggleShadows) {
+            shadows_enabled = !shadows_enabled;
+            println!("Shadows: {}", shadows_enabled);
+        }
+        if window.was_input_pressed(InputAction::ToggleShadowDebug) {
+            shadow_debug = !shadow_debug;
+        }
+        if window.was_input_pressed(InputAction::NextShadowResolution) {
+            shadow_resolution_index = (shadow_resolution_index + 1) % SHADOW_RESOLUTIONS.len();
+            shadow_map.set_resolution(SHADOW_RESOLUTIONS[shadow_resolution_index]);
+            println!("Shadow map: {}x{}", shadow_map.resolution(), shadow_map.resolution());
+        }
+
+        // Instances share the edited model and show every change made to it
+        if input_mode == INPUT_MODE_NOMINAL {
+            if window.was_input_pressed(InputAction::AddInstance) {
+                let n_instances = scene.nodes().iter()
+                    .filter(|node| node.model == Some(edited_model) && node.name != EDITED_NODE)
+                    .count();
+                let name = format!("instance_{}", n_instances);
+                let transform = graphics::Transform {
+                    translation: Vector3::new(INSTANCE_SPACING * (n_instances + 1) as f32, 0.0, 0.0),
+                    ..graphics::Transform::identity()
+                };
+                let node_index = scene.add_node(&name, None, Some(edited_model), transform);
+                scene.set_lod(node_index, Some(instance_lod));
+                println!("Added {}", name);
+
+                // Big models don't fit the spacing, say which instances touch
+                let mesh = scene.model(edited_model).collision_mesh();
+                let node_mat = scene.world_matrix(node_index);
+                for (other_index, other) in scene.nodes().iter().enumerate() {
+                    if other_index != node_index && other.model == Some(edited_model) &&
+                       mesh.overlaps(&node_mat, &mesh, &scene.world_matrix(other_index)) {
+                        println!("{} overlaps {}", name, other.name);
+                    }
+                }
+            }
+            if window.was_input_pressed(InputAction::RemoveInstance) {
+                if let Some(node_index) = scene.nodes().iter().rposition(|node| node.name != EDITED_NODE) {
+                    println!("Removed {}", scene.nodes()[node_index].name);
+                    scene.remove_node(node_index);
+                    edited_node = scene.find_node(EDITED_NODE).unwrap();
+                }
+            }
+
+            // Levels either switch at once or cross-fade, the tint shows
+            // which one every instance is at
+            if window.was_input_pressed(InputAction::ToggleLodFade) {
+                let fade_time = if scene.lod_sets()[instance_lod].fade_time > 0.0 { 0.0 } else { LOD_FADE_TIME };
+                scene.lod_set_mut(instance_lod).fade_time = fade_time;
+                println!("LOD fade time: {}", fade_time);
+            }
+            if window.was_input_pressed(InputAction::ToggleLodTint) {
+                scene.lod_debug_tint = !scene.lod_debug_tint;
+                println!("LOD tint: {}", scene.lod_debug_tint);
+            }
+        }
+
+        // The edited model is picked in the space of its node
+        let edited_mat = scene.world_matrix(edited_node);
+        let model = scene.model_mut(edited_model);
 
         if input_mode == INPUT_MODE_NOMINAL {
             if window.was_input_pressed(InputAction::EnterVertex) {
@@ -128,49 +398,247 @@ fn main() {
             if window.was_input_pressed(InputAction::DeleteVertex) {
                 input_mode = INPUT_MODE_DELETE_VERTEX;
             }
-        }
+            if window.was_input_pressed(InputAction::BoxSelect) {
+                select_anchor = mouse_pos;
+                input_mode = INPUT_MODE_BOX_SELECT;
+            }
+            if window.was_input_pressed(InputAction::LassoSelect) {
+                lasso_points = vec![mouse_pos];
+                input_mode = INPUT_MODE_LASSO_SELECT;
+            }
+            if window.was_input_pressed(InputAction::CycleSelectMode) {
+                let mode = model.selection().mode().next();
+                model.set_select_mode(mode);
+                println!("Selection mode: {:?}", mode);
+            }
+            if window.was_input_pressed(InputAction::ClearSelection) {
+                model.clear_selection();
+            }
 
-        // Process inputting a vertex
-        if input_mode == INPUT_MODE_ENTER_VERTEX {
-            if window.was_input_pressed(InputAction::Num0) {
-                input_string.push_str("0");
+            // Palette entry given to new faces
+            if window.was_input_pressed(InputAction::NextColour) && model.palette().len() > 0 {
+                active_colour = (active_colour + 1) % model.palette().len();
+                println!("Active colour: {}", model.palette().entries()[active_colour].name);
+            }
+
+            // Primitives go in at the origin as a group of their own
+            if window.was_input_pressed(InputAction::NextPrimitive) {
+                active_primitive = (active_primitive + 1) % PRIMITIVE_NAMES.len();
+                println!("Active primitive: {}", PRIMITIVE_NAMES[active_primitive]);
+            }
+            if window.was_input_pressed(InputAction::AddPrimitive) {
+                let counts = (model.n_verts(), model.n_lines(), model.n_faces());
+                add_primitive(model, active_primitive, active_colour as f32);
+
+                let name = format!("{}_{}", PRIMITIVE_NAMES[active_primitive], model.groups().len());
+                active_group = model.add_group_since(&name, counts);
+                println!("Added {}", name);
+            }
+
+            // Gradients over the selection, picked between two vertices
+            if window.was_input_pressed(InputAction::LinearGradient) {
+                gradient_radial = false;
+                input_mode = INPUT_MODE_GRADIENT;
+            }
+            if window.was_input_pressed(InputAction::RadialGradient) {
+                gradient_radial = true;
+                input_mode = INPUT_MODE_GRADIENT;
+            }
+            if window.was_input_pressed(InputAction::ClearColours) {
+                model.clear_selected_colours();
             }
-            if window.was_input_pressed(InputAction::Num1) {
-                input_string.push_str("1");
+
+            // The active palette entry can be retyped, the whole palette saved
+            // and given to another model
+            if window.was_input_pressed(InputAction::EnterPaletteColour) && model.palette().len() > 0 {
+                println!("Type r,g,b for {}", model.palette().entries()[active_colour].name);
+                input_mode = INPUT_MODE_PALETTE_COLOUR;
+            }
+            if window.was_input_pressed(InputAction::SavePalette) {
+                model.palette().write_to_file(PALETTE_FILE);
+            }
+            if window.was_input_pressed(InputAction::LoadPalette) {
+                let mut palette = Palette::create_empty();
+                palette.load_from_file(PALETTE_FILE);
+                if palette.len() > 0 {
+                    model.set_palette(palette);
+                    active_colour = 0;
+                }
+            }
+
+            // Lighting, the direction typed goes to the first directional light
+            if window.was_input_pressed(InputAction::ToggleHeadlight) {
+                lighting.headlight = match lighting.headlight {
+                    Some(_colour) => None,
+                    None => Some(Vector3::new(HEADLIGHT_INTENSITY, HEADLIGHT_INTENSITY, HEADLIGHT_INTENSITY))
+                };
+                println!("Headlight: {}", lighting.headlight.is_some());
+            }
+            if window.was_input_pressed(InputAction::ToggleSpecular) {
+                lighting.specular = match lighting.specular {
+                    Some(_specular) => None,
+                    None => Some(Specular { strength: SPECULAR_STRENGTH, shininess: SPECULAR_SHININESS })
+                };
+                println!("Specular: {}", lighting.specular.is_some());
+            }
+            if window.was_input_pressed(InputAction::AmbientUp) {
+                lighting.ambient += Vector3::new(AMBIENT_STEP, AMBIENT_STEP, AMBIENT_STEP);
             }
-            if window.was_input_pressed(InputAction::Num2) {
-                input_string.push_str("2");
+            if window.was_input_pressed(InputAction::AmbientDown) {
+                let ambient = lighting.ambient - Vector3::new(AMBIENT_STEP, AMBIENT_STEP, AMBIENT_STEP);
+                lighting.ambient = Vector3::new(ambient.x.max(0.0), ambient.y.max(0.0), ambient.z.max(0.0));
             }
-            if window.was_input_pressed(InputAction::Num3) {
-                input_string.push_str("3");
+            if window.was_input_pressed(InputAction::EnterLightDirection) {
+                println!("Type dx,dy,dz towards the light");
+                input_mode = INPUT_MODE_LIGHT_DIRECTION;
             }
-            if window.was_input_pressed(InputAction::Num4) {
-                input_string.push_str("4");
+
+            // Morph targets, recorded from the selected vertices being moved
+            if window.was_input_pressed(InputAction::RecordMorph) {
+                morph_base = model.vert_positions();
+                println!("Recording morph target, type dx,dy,dz to move the selection");
+                input_mode = INPUT_MODE_RECORD_MORPH;
             }
-            if window.was_input_pressed(InputAction::Num5) {
-                input_string.push_str("5");
+            if window.was_input_pressed(InputAction::PlayMorphs) {
+                morph_playing = !morph_playing;
+                morph_time = 0.0;
+                if !morph_playing {
+                    for target_index in 0..model.morphs().targets().len() {
+                        model.morphs_mut().set_weight(target_index, 0.0);
+                    }
+                }
             }
-            if window.was_input_pressed(InputAction::Num6) {
-                input_string.push_str("6");
+            // Blending falls back to the CPU with more targets than the
+            // shaders take
+            if window.was_input_pressed(InputAction::ToggleGpuMorphs) {
+                let gpu_blending = !model.morphs().gpu_blending();
+                model.morphs_mut().set_gpu_blending(gpu_blending);
+                println!("GPU morphs: {}", model.morphs().uses_gpu());
             }
-            if window.was_input_pressed(InputAction::Num7) {
-                input_string.push_str("7");
+
+            // Parts are made from the active group and previewed with the clip
+            if window.was_input_pressed(InputAction::PartFromGroup) && !model.groups().is_empty() {
+                model.part_from_group(active_group % model.groups().len(), Vector3::unit_z());
             }
-            if window.was_input_pressed(InputAction::Num8) {
-                input_string.push_str("8");
+            if window.was_input_pressed(InputAction::KeyPart) && !model.groups().is_empty() {
+                let name = model.groups()[active_group % model.groups().len()].name();
+                if model.find_part(name).is_some() {
+                    println!("Type time,angle or time,angle,tx,ty,tz to key {}", name);
+                    input_mode = INPUT_MODE_PART_KEY;
+                } else {
+                    println!("Make a part from {} first", name);
+                }
             }
-            if window.was_input_pressed(InputAction::Num9) {
-                input_string.push_str("9");
+            if window.was_input_pressed(InputAction::PlayClip) {
+                clip_clock.playing = !clip_clock.playing;
+                clip_clock.time = 0.0;
+                if !clip_clock.playing {
+                    model.clear_pose();
+                }
             }
-            if window.was_input_pressed(InputAction::Dot) {
-                input_string.push_str(".");
+
+            // UV projections fitted to the bounds of the selection
+            if let Some((min, max)) = model.selected_bounds() {
+                if window.was_input_pressed(InputAction::ProjectPlanarUvs) {
+                    model.project_uvs(&UvProjection::planar_fit(min, max));
+                }
+                if window.was_input_pressed(InputAction::ProjectBoxUvs) {
+                    model.project_uvs(&UvProjection::box_fit(min, max));
+                }
+                if window.was_input_pressed(InputAction::ProjectCylindricalUvs) {
+                    model.project_uvs(&UvProjection::cylindrical_fit(min, max));
+                }
+            }
+            if window.was_input_pressed(InputAction::ToggleTexture) {
+                if model.texture_path().is_some() {
+                    model.set_texture_path(None);
+                } else {
+                    textures.forget(TEXTURE_FILE);
+                    model.set_texture_path(Some(TEXTURE_FILE));
+                }
+                println!("Texture: {}", model.texture_path().is_some());
+            }
+
+            // Collisions can use the LOD stand-in instead of the faces, the
+            // probe casts a ray and a ball through the mouse
+            if window.was_input_pressed(InputAction::ToggleCollisionMesh) {
+                if model.collision_path().is_some() {
+                    model.set_collision_path(None);
+                } else {
+                    model.set_collision_path(Some(LOD_MODEL_FILE));
+                }
+                let mesh = model.collision_mesh();
+                println!("Collision mesh: {} triangles", mesh.n_triangles());
+                if let Some((min, max)) = mesh.bounds() {
+                    println!("Bounds: {:?} to {:?}", min, max);
+                }
+            }
+            if window.was_input_pressed(InputAction::ProbeCollision) {
+                let (origin, dir) = mouse_ray(&(cameras[active_view].total_mat * edited_mat),
+                                              layout.rects()[active_view].size,
+                                              mouse_pos);
+                let mesh = model.collision_mesh();
+                match mesh.ray_cast(origin, dir, f32::MAX) {
+                    Some(hit) => println!("Hit face {} at {:.3}, point {:?}, normal {:?}",
+                                          hit.face, hit.distance, hit.point, hit.normal),
+                    None => match mesh.closest_point(origin) {
+                        Some(closest) => println!("No hit, face {} is closest at {:.3}, point {:?}",
+                                                  closest.face, closest.distance, closest.point),
+                        None => println!("No hit")
+                    }
+                }
+                if let Some(hit) = mesh.sphere_cast(origin, dir, PROBE_RADIUS, f32::MAX) {
+                    println!("Ball stops against face {} at {:.3}, touching {:?}",
+                             hit.face, hit.distance, hit.point);
+                }
+            }
+
+            // Groups, the active one is cycled through and acted upon
+            if window.was_input_pressed(InputAction::GroupFromSelection) {
+                let name = format!("group_{}", model.groups().len());
+                active_group = model.group_from_selection(&name);
+                println!("Created group {}", name);
             }
-            if window.was_input_pressed(InputAction::Comma) {
-                input_string.push_str(",");
+            if !model.groups().is_empty() {
+                if active_group >= model.groups().len() {
+                    active_group = 0;
+                }
+                if window.was_input_pressed(InputAction::NextGroup) {
+                    active_group = (active_group + 1) % model.groups().len();
+                    println!("Active group: {}", model.groups()[active_group].name());
+                }
+                if window.was_input_pressed(InputAction::SelectGroup) {
+                    model.select_group(active_group, select_op(&window));
+                }
+                if window.was_input_pressed(InputAction::ToggleGroupVisible) {
+                    let visible = !model.groups()[active_group].is_visible();
+                    model.set_group_visible(active_group, visible);
+                }
+                if window.was_input_pressed(InputAction::ToggleGroupLocked) {
+                    let locked = !model.groups()[active_group].is_locked();
+                    model.set_group_locked(active_group, locked);
+                }
+                // Only the isolated group is drawn, the rest stays editable
+                if window.was_input_pressed(InputAction::IsolateGroup) {
+                    isolated_group = match isolated_group {
+                        Some(_) => None,
+                        None => {
+                            println!("Isolated group: {}", model.groups()[active_group].name());
+                            Some(active_group)
+                        }
+                    };
+                }
             }
-            if window.was_input_pressed(InputAction::Minus) {
-                input_string.push_str("-");
+            if let Some(group_index) = isolated_group {
+                if group_index >= model.groups().len() {
+                    isolated_group = None;
+                }
             }
+        }
+
+        // Process inputting a vertex
+        if input_mode == INPUT_MODE_ENTER_VERTEX {
+            type_number(&window, &mut input_string);
             if window.was_input_pressed(InputAction::EndCommand) {
                 // Process input string
                 let str_coords: Vec<&str> = input_string.split(',').collect();
@@ -199,9 +667,8 @@ fn main() {
 
         if input_mode == INPUT_MODE_ENTER_LINE {
             if window.was_input_pressed(InputAction::Select) {
-                match model.select_vert(window.last_mouse_pos) {
-                    Some(index) => vert_indices.push(index),
-                    None => ()
+                if let Some(index) = model.select_vert(mouse_pos) {
+                    vert_indices.push(index);
                 }
             }
             if window.was_input_pressed(InputAction::AbortCommand) {
@@ -219,9 +686,8 @@ fn main() {
 
         if input_mode == INPUT_MODE_ENTER_FACE {
             if window.was_input_pressed(InputAction::Select) {
-                match model.select_vert(window.last_mouse_pos) {
-                    Some(index) => vert_indices.push(index),
-                    None => ()
+                if let Some(index) = model.select_vert(mouse_pos) {
+                    vert_indices.push(index);
                 }
             }
             if window.was_input_pressed(InputAction::AbortCommand) {
@@ -231,7 +697,7 @@ fn main() {
             }
 
             if vert_indices.len() == 3 {
-                model.add_face(&vert_indices, 0.0);
+                model.add_face(&vert_indices, active_colour as f32);
                 vert_indices = Vec::<usize>::new();
                 input_mode = INPUT_MODE_NOMINAL;
             }
@@ -239,63 +705,639 @@ fn main() {
 
         if input_mode == INPUT_MODE_DELETE_VERTEX {
             if window.was_input_pressed(InputAction::Select) {
-                match model.select_vert(window.last_mouse_pos) {
-                    Some(index) => {
-                        model.remove_vert(index);
-                        input_mode = INPUT_MODE_NOMINAL;
-                    },
-                    None => ()
+                if let Some(index) = model.select_vert(mouse_pos) {
+                    model.remove_vert(index);
+                    input_mode = INPUT_MODE_NOMINAL;
                 }
             }
         }
 
+        // Process box selection, from the anchor to the current mouse position
+        if input_mode == INPUT_MODE_BOX_SELECT {
+            if window.was_input_pressed(InputAction::Select) {
+                let region = SelectRegion::Rect(select_anchor, mouse_pos);
+                model.select_region(&region, select_op(&window));
+                input_mode = INPUT_MODE_NOMINAL;
+            }
+            if window.was_input_pressed(InputAction::AbortCommand) {
+                input_mode = INPUT_MODE_NOMINAL;
+            }
+        }
+
+        // Process lasso selection, the path follows the mouse until closed
+        if input_mode == INPUT_MODE_LASSO_SELECT {
+            let last_point = lasso_points[lasso_points.len() - 1];
+            if (mouse_pos - last_point).magnitude() > LASSO_POINT_SPACING {
+                lasso_points.push(mouse_pos);
+            }
+
+            if window.was_input_pressed(InputAction::Select) {
+                let region = SelectRegion::Lasso(lasso_points);
+                model.select_region(&region, select_op(&window));
+                lasso_points = Vec::new();
+                input_mode = INPUT_MODE_NOMINAL;
+            }
+            if window.was_input_pressed(InputAction::AbortCommand) {
+                lasso_points = Vec::new();
+                input_mode = INPUT_MODE_NOMINAL;
+            }
+        }
+
+        // Process gradient, from the active palette colour at the first vertex
+        // to the next palette colour at the second one
+        if input_mode == INPUT_MODE_GRADIENT {
+            if window.was_input_pressed(InputAction::Select) {
+                if let Some(index) = model.vert_at(mouse_pos) {
+                    vert_indices.push(index);
+                }
+            }
+            if window.was_input_pressed(InputAction::AbortCommand) {
+                vert_indices = Vec::<usize>::new();
+                input_mode = INPUT_MODE_NOMINAL;
+            }
+
+            if vert_indices.len() == 2 {
+                let entries = model.palette().entries();
+                if !entries.is_empty() {
+                    let start_colour = entries[active_colour % entries.len()].colour;
+                    let end_colour = entries[(active_colour + 1) % entries.len()].colour;
+                    let start = model.vert_pos(vert_indices[0]);
+                    let end = model.vert_pos(vert_indices[1]);
+
+                    let gradient = if gradient_radial {
+                        Gradient::radial(start, (end - start).magnitude(), start_colour, end_colour)
+                    } else {
+                        Gradient::linear(start, end, start_colour, end_colour)
+                    };
+                    model.paint_gradient(&gradient);
+                }
+
+                vert_indices = Vec::<usize>::new();
+                input_mode = INPUT_MODE_NOMINAL;
+            }
+        }
+
+        // Process recording a morph target, the selection is moved by typed
+        // offsets and the target is stored when recording is pressed again
+        if input_mode == INPUT_MODE_RECORD_MORPH {
+            type_number(&window, &mut input_string);
+            if window.was_input_pressed(InputAction::EndCommand) {
+                let str_coords: Vec<&str> = input_string.split(',').collect();
+                if str_coords.len() == 3 {
+                    let mut offset: Vector3<f32> = Vector3::zero();
+                    offset.x = str_coords[0].parse::<f32>().unwrap_or(0.0);
+                    offset.y = str_coords[1].parse::<f32>().unwrap_or(0.0);
+                    offset.z = str_coords[2].parse::<f32>().unwrap_or(0.0);
+
+                    model.translate_selected(offset);
+                } else {
+                    println!("You must enter three coords separated by commas");
+                }
+
+                input_string = String::new();
+            }
+            if window.was_input_pressed(InputAction::RecordMorph) {
+                let name = format!("morph_{}", model.morphs().targets().len());
+                let target_index = model.record_morph_target(&name, &morph_base);
+
+                // Give it a track going there and back so it can be previewed
+                model.morphs_mut().add_key(target_index, 0.0, 0.0);
+                model.morphs_mut().add_key(target_index, 1.0, 1.0);
+                model.morphs_mut().add_key(target_index, 2.0, 0.0);
+                println!("Recorded morph target {}", name);
+
+                input_string = String::new();
+                input_mode = INPUT_MODE_NOMINAL;
+            }
+            if window.was_input_pressed(InputAction::AbortCommand) {
+                // Put the vertices back where they were
+                model.set_vert_positions(&morph_base);
+
+                input_string = String::new();
+                input_mode = INPUT_MODE_NOMINAL;
+            }
+        }
+
+        // Process typing the colour of the active palette entry
+        if input_mode == INPUT_MODE_PALETTE_COLOUR {
+            type_number(&window, &mut input_string);
+            if window.was_input_pressed(InputAction::EndCommand) {
+                let str_coords: Vec<&str> = input_string.split(',').collect();
+                if str_coords.len() == 3 {
+                    let red = str_coords[0].parse::<f32>().unwrap_or(0.0);
+                    let green = str_coords[1].parse::<f32>().unwrap_or(0.0);
+                    let blue = str_coords[2].parse::<f32>().unwrap_or(0.0);
+
+                    model.palette_mut().set_colour(active_colour, (red, green, blue, 1.0));
+                } else {
+                    println!("You must enter three values separated by commas");
+                }
+
+                input_string = String::new();
+                input_mode = INPUT_MODE_NOMINAL;
+            }
+            if window.was_input_pressed(InputAction::AbortCommand) {
+                input_string = String::new();
+                input_mode = INPUT_MODE_NOMINAL;
+            }
+        }
+
+        // Process typing a key of the part of the active group, the angle is
+        // in degrees
+        if input_mode == INPUT_MODE_PART_KEY {
+            type_number(&window, &mut input_string);
+            if window.was_input_pressed(InputAction::EndCommand) {
+                let values: Vec<f32> = input_string.split(',')
+                    .map(|value| value.parse::<f32>().unwrap_or(0.0))
+                    .collect();
+                if values.len() == 2 || values.len() == 5 {
+                    let translation = if values.len() == 5 {
+                        Vector3::new(values[2], values[3], values[4])
+                    } else {
+                        Vector3::zero()
+                    };
+                    let name = model.groups()[active_group % model.groups().len()].name();
+                    clip.add_key(name, PartKey {
+                        time: values[0],
+                        angle: values[1].to_radians(),
+                        translation,
+                        interpolation: Interpolation::Bezier
+                    });
+
+                    if let Some(track) = clip.track(name) {
+                        println!("{} has {} keys", track.part(), track.keys().len());
+                    }
+                } else {
+                    println!("You must enter two or five values separated by commas");
+                }
+
+                input_string = String::new();
+                input_mode = INPUT_MODE_NOMINAL;
+            }
+            if window.was_input_pressed(InputAction::AbortCommand) {
+                input_string = String::new();
+                input_mode = INPUT_MODE_NOMINAL;
+            }
+        }
+
+        // Process typing the direction of the light
+        if input_mode == INPUT_MODE_LIGHT_DIRECTION {
+            type_number(&window, &mut input_string);
+            if window.was_input_pressed(InputAction::EndCommand) {
+                let str_coords: Vec<&str> = input_string.split(',').collect();
+                if str_coords.len() == 3 {
+                    let mut direction: Vector3<f32> = Vector3::zero();
+                    direction.x = str_coords[0].parse::<f32>().unwrap_or(0.0);
+                    direction.y = str_coords[1].parse::<f32>().unwrap_or(0.0);
+                    direction.z = str_coords[2].parse::<f32>().unwrap_or(0.0);
+
+                    match lighting.directional.first_mut() {
+                        Some(light) => light.direction = direction,
+                        None => { lighting.add_directional(direction, Vector3::new(1.0, 1.0, 1.0)); }
+                    }
+                } else {
+                    println!("You must enter three coords separated by commas");
+                }
+
+                input_string = String::new();
+                input_mode = INPUT_MODE_NOMINAL;
+            }
+            if window.was_input_pressed(InputAction::AbortCommand) {
+                input_string = String::new();
+                input_mode = INPUT_MODE_NOMINAL;
+            }
+        }
+
         // Process input for objects
-        camera.process_input(&window);
+        cameras[active_view].process_input(&window);
 
         // ---- UPDATE ----
-        camera.update(delta_time);
+        for camera in cameras.iter_mut() {
+            camera.update(delta_time);
+        }
+
+        // Clip preview
+        if clip_clock.playing {
+            clip_clock.advance(delta_time, &clip);
+            model.set_pose(&clip, clip_clock.time);
+        }
 
-        // Process vertices of model
+        // Morph preview loops over the longest track
+        if morph_playing {
+            morph_time += delta_time;
+            let duration = model.morphs().duration();
+            if duration > 0.0 && morph_time > duration {
+                morph_time -= duration;
+            }
+            model.animate_morphs(morph_time);
+        }
+
+        // Process vertices of model, as seen in the active view
         model.process_vertices
-           (&camera.total_mat,
-            (SCREEN_WIDTH, SCREEN_HEIGHT),
-            window.last_mouse_pos);
+           (&(cameras[active_view].total_mat * edited_mat),
+            layout.rects()[active_view].size,
+            mouse_pos);
+
+        if display_mode.shows_faces() {
+            model.process_faces(mouse_pos);
+        }
 
-        if !is_wf {
-            model.process_faces(window.last_mouse_pos);
+        // Levels of detail as seen in every view
+        for (view_index, camera) in cameras.iter().enumerate().take(layout.view_count()) {
+            scene.update_lods(view_index, camera, delta_time);
         }
 
         // ---- RENDER ----
-        camera.activate();
-        unsafe {
-            // Render wireframe or solid color
-            if is_wf {
-                wf_shader.bind();
-                wf_shader.pass_matrix("transMat", &camera.total_mat);
-                model.render_wf();
-            } else {
-                normal_shader.bind();
-                normal_shader.pass_matrix("transMat", &camera.total_mat);
-                model.render_solid();
-            }
-
-            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
-            gl::Viewport(0, 0, scr_width, scr_height);
-            gl::ClearColor(0.6, 0.6, 0.6, 1.0);
-            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
-
-            cam_shader.bind();
-            cam_shader.pass_int("layer", 1);
-            cam_shader.pass_int("textureSampler", 0);
+        let model = scene.model(edited_model);
+
+        // Isolating a group only applies to the edited model
+        let isolated = |model_index: usize| {
+            if model_index == edited_model { isolated_group } else { None }
+        };
+
+        // Depth from the first directional light, around the whole edited model
+        let casts_shadows = shadows_enabled && display_mode.draws_faces() && !lighting.directional.is_empty();
+        if casts_shadows {
+            let (centre, radius) = model.bounding_sphere();
+            let centre = (edited_mat * centre.extend(1.0)).truncate();
+            shadow_map.update_light(lighting.directional[0].direction, centre, radius);
+
+            shadow_map.activate();
+            shadow_shader.bind();
+            shadow_shader.pass_matrix("lightMat", shadow_map.light_mat());
+            scene.render_solid(&shadow_shader, shadow_map.light_mat(), active_view);
+        }
+
+        // Every view draws into the framebuffer of its camera
+        for (view_index, camera) in cameras.iter().enumerate().take(layout.view_count()) {
+            camera.activate();
+            // Faces first so the lines and points can be tested against them
+            if display_mode.draws_faces() {
+                // Models with a texture that loads go through the textured shader
+                let texture = match model.texture_path() {
+                    Some(path) => textures.load(path),
+                    None => None
+                };
+                let solid_shader = match texture {
+                    Some(texture) => {
+                        texture.bind(0);
+                        let shader = if cel_enabled { &textured_cel_shader } else { &textured_shader };
+                        shader.bind();
+                        shader.pass_sampler("tex", 0);
+                        shader
+                    },
+                    None => {
+                        let shader = if cel_enabled { &cel_shader } else { &normal_shader };
+                        shader.bind();
+                        shader
+                    }
+                };
+
+                lighting.pass_to_shader(solid_shader, camera.position(), camera.look_direction());
+                solid_shader.pass_float("transparency", display_mode.transparency());
+                if cel_enabled {
+                    cel_shading.pass_to_shader(solid_shader);
+                }
+                if casts_shadows {
+                    shadow_map.pass_to_shader(solid_shader);
+                } else {
+                    disable_shadows(solid_shader);
+                }
+
+                display_mode.begin_faces();
+                scene.render(solid_shader, &camera.total_mat, view_index, |model_index, model| {
+                    render_faces(model, isolated(model_index));
+                });
+                display_mode.end_faces();
+
+                if cel_enabled && cel_shading.outlines && display_mode.shows_faces() {
+                    outline_shader.bind();
+                    cel_shading.pass_outline_to_shader(&outline_shader, camera.size());
+
+                    cel_shading.begin_outline();
+                    scene.render(&outline_shader, &camera.total_mat, view_index, |model_index, model| {
+                        render_faces(model, isolated(model_index));
+                    });
+                    cel_shading.end_outline();
+                }
+            }
+
+            // Only the edited model shows its vertices
+            wf_shader.bind();
+            scene.render(&wf_shader, &camera.total_mat, view_index, |model_index, model| {
+                if display_mode.draws_lines() {
+                    render_lines(model, isolated(model_index));
+                }
+                if model_index == edited_model {
+                    model.render_points();
+                }
+            });
+        }
+
+        // ---- COMPOSITE ----
+        // Each camera stretched over its view, with the player bars on top
+        layout.begin_frame();
+
+        cam_shader.bind();
+        cam_shader.pass_int("layer", 1);
+        cam_shader.pass_sampler("textureSampler", 0);
+        for (view_index, camera) in cameras.iter().enumerate().take(layout.view_count()) {
+            layout.activate_view(view_index);
             camera.render();
         }
+        layout.end_frame();
+
+        if layout.view_count() > 1 {
+            overlay_shader.bind();
+            layout.render_overlays(&overlay_shader, active_view);
+        }
+
+        if shadow_debug {
+            shadow_debug_shader.bind();
+            shadow_map.render_debug(&shadow_debug_shader, layout.screen_size());
+        }
+
+        // ---- SCREENSHOTS ----
+        // Read back once everything is drawn and before the buffers swap. The
+        // camera ones are of the active view
+        if window.was_input_pressed(InputAction::Screenshot) {
+            save_screenshot(&cameras[active_view].read_colour_buffer(), SCREENSHOT_DIR);
+        }
+        if window.was_input_pressed(InputAction::ScreenshotUpscaled) {
+            save_screenshot(&upscale(&cameras[active_view].read_colour_buffer(), SCREENSHOT_SCALE), SCREENSHOT_DIR);
+        }
+        if window.was_input_pressed(InputAction::ScreenshotWindow) {
+            let (width, height) = layout.screen_size();
+            save_screenshot(&read_back_buffer((width as i32, height as i32)), SCREENSHOT_DIR);
+        }
 
         // GLFW: swap buffers and poll IO events
         window.glfw_window.swap_buffers();
         glfw.poll_events();
     }
 
-    model.write_to_file("models/last_model.mdl");
+    // The scene saves its models as well
+    lighting.write_to_file(LIGHTING_FILE);
+    scene.lighting = lighting;
+    scene.write_to_file(SCENE_FILE);
+    if !clip.tracks().is_empty() {
+        clip.write_to_file(&preview_path);
+    }
+}
+
+
+// -----------------------------------------------------------------------------
+// SHADERS THE EDITOR CAN'T RUN WITHOUT
+// -----------------------------------------------------------------------------
+fn required_shader(result: Result<Shader, ShaderError>) -> Shader {
+    match result {
+        Ok(shader) => shader,
+        Err(e) => {
+            println!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+
+// -----------------------------------------------------------------------------
+// THE WHOLE MODEL OR ONLY ITS ISOLATED GROUP
+// -----------------------------------------------------------------------------
+fn render_faces(model: &Model, isolated_group: Option<usize>) {
+    match isolated_group {
+        Some(group_index) => model.render_group_solid(group_index),
+        None => model.render_solid()
+    }
+}
+
+fn render_lines(model: &Model, isolated_group: Option<usize>) {
+    match isolated_group {
+        Some(group_index) => model.render_group_wf(group_index),
+        None => model.render_lines()
+    }
+}
+
+// -----------------------------------------------------------------------------
+// COARSE STAND-IN FOR INSTANCES FAR AWAY
+// -----------------------------------------------------------------------------
+// An ellipsoid over the bounds of the model, around its origin
+fn create_lod_stand_in(source: &Model) -> Model {
+    let mut model = Model::create_empty();
+
+    let positions = source.vert_positions();
+    if let Some(first) = positions.first() {
+        let mut min = *first;
+        let mut max = min;
+        for pos in positions.iter() {
+            min = Vector3::new(min.x.min(pos.x), min.y.min(pos.y), min.z.min(pos.z));
+            max = Vector3::new(max.x.max(pos.x), max.y.max(pos.y), max.z.max(pos.z));
+        }
+
+        primitives::ellipsoid(&mut model,
+                              Vector3::new(max.x.max(0.01), max.y.max(0.01), max.z.max(0.01)),
+                              Vector3::new((-min.x).max(0.01), (-min.y).max(0.01), (-min.z).max(0.01)),
+                              4, 6,
+                              PrimitiveSettings::default());
+    }
+
+    model.update_gpu_data();
+    model
+}
+
+// -----------------------------------------------------------------------------
+// RAY THROUGH THE MOUSE
+// -----------------------------------------------------------------------------
+// From the near plane towards the far one, in the space the matrix goes from
+fn mouse_ray(proj_view_mat: &Matrix4<f32>,
+             size: (u32, u32),
+             mouse_pos: Vector2<f32>) -> (Vector3<f32>, Vector3<f32>) {
+    let inverse = proj_view_mat.invert().unwrap_or(Matrix4::identity());
+    let x = mouse_pos.x * 2.0 / size.0 as f32 - 1.0;
+    let y = 1.0 - mouse_pos.y * 2.0 / size.1 as f32;
+
+    let near = inverse * Vector4::new(x, y, -1.0, 1.0);
+    let far = inverse * Vector4::new(x, y, 1.0, 1.0);
+    let near = near.truncate() / near.w;
+    let far = far.truncate() / far.w;
+
+    (near, far - near)
+}
+
+// -----------------------------------------------------------------------------
+// CAMERAS SIZED TO THEIR VIEWS
+// -----------------------------------------------------------------------------
+// Cameras without a view are left as they are until they get one again
+fn fit_cameras(cameras: &mut [Camera], layout: &ViewportLayout) {
+    for (view_index, camera) in cameras.iter_mut().enumerate().take(layout.view_count()) {
+        camera.resize(layout.render_size(view_index, CAM_HEIGHT));
+    }
+}
+
+
+// -----------------------------------------------------------------------------
+// SELECTION OPERATION FROM THE MODIFIER KEYS
+// -----------------------------------------------------------------------------
+fn select_op(window: &Window) -> SelectOp {
+    if window.is_input_down(InputAction::SelectAdd) {
+        SelectOp::Add
+    } else if window.is_input_down(InputAction::SelectSubtract) {
+        SelectOp::Subtract
+    } else if window.is_input_down(InputAction::SelectToggle) {
+        SelectOp::Toggle
+    } else {
+        SelectOp::Replace
+    }
+}
+
+
+// -----------------------------------------------------------------------------
+// TYPING NUMBERS INTO THE INPUT STRING
+// -----------------------------------------------------------------------------
+fn type_number(window: &Window, input_string: &mut String) {
+    if window.was_input_pressed(InputAction::Num0) {
+        input_string.push('0');
+    }
+    if window.was_input_pressed(InputAction::Num1) {
+        input_string.push('1');
+    }
+    if window.was_input_pressed(InputAction::Num2) {
+        input_string.push('2');
+    }
+    if window.was_input_pressed(InputAction::Num3) {
+        input_string.push('3');
+    }
+    if window.was_input_pressed(InputAction::Num4) {
+        input_string.push('4');
+    }
+    if window.was_input_pressed(InputAction::Num5) {
+        input_string.push('5');
+    }
+    if window.was_input_pressed(InputAction::Num6) {
+        input_string.push('6');
+    }
+    if window.was_input_pressed(InputAction::Num7) {
+        input_string.push('7');
+    }
+    if window.was_input_pressed(InputAction::Num8) {
+        input_string.push('8');
+    }
+    if window.was_input_pressed(InputAction::Num9) {
+        input_string.push('9');
+    }
+    if window.was_input_pressed(InputAction::Dot) {
+        input_string.push('.');
+    }
+    if window.was_input_pressed(InputAction::Comma) {
+        input_string.push(',');
+    }
+    if window.was_input_pressed(InputAction::Minus) {
+        input_string.push('-');
+    }
+}
+
+
+// -----------------------------------------------------------------------------
+// DIFF OF TWO MODEL FILES
+// -----------------------------------------------------------------------------
+fn diff_models(args: &[String]) {
+    if args.len() < 2 {
+        println!("Usage: editor diff old.mdl new.mdl [tolerance]");
+        return;
+    }
+
+    let tolerance = match args.get(2) {
+        Some(tolerance) => match tolerance.parse::<f32>() {
+            Ok(tolerance) => tolerance,
+            Err(_e) => {
+                println!("Wrong tolerance: {}", tolerance);
+                return;
+            }
+        },
+        None => 0.0001
+    };
+
+    let mut old = Model::create_headless();
+    old.load_from_file(&args[0]);
+    let mut new = Model::create_headless();
+    new.load_from_file(&args[1]);
+
+    for line in old.diff(&new, tolerance).summary(&old, &new) {
+        println!("{}", line);
+    }
+}
+
+
+// -----------------------------------------------------------------------------
+// RENDER A MODEL FILE TO PNG ON THE CPU
+// -----------------------------------------------------------------------------
+// Seen from where the editor camera starts, for comparing against golden images
+fn render_headless(args: &[String]) {
+    if args.len() < 2 {
+        println!("Usage: editor render model.mdl out.png [wireframe]");
+        return;
+    }
+
+    let mut model = Model::create_headless();
+    model.load_from_file(&args[0]);
+
+    // Zero attitude looks along X
+    let eye_pos = Point3::new(-2.0, 0.0, 0.0);
+    let total_mat = Camera::headless_total_mat
+       (60.0, (CAM_WIDTH, CAM_HEIGHT),
+        eye_pos,
+        Vector3::new(0.0, 0.0, 0.0));
+
+    let mut raster = SoftwareRaster::new((CAM_WIDTH, CAM_HEIGHT));
+    if args.get(2).map(|mode| mode == "wireframe").unwrap_or(false) {
+        raster.draw_wireframe(&model, &total_mat);
+    } else {
+        raster.draw_solid(&model, &total_mat, &Matrix4::identity(),
+                          &Lighting::create_default(), (eye_pos, Vector3::unit_x()));
+    }
+    raster.write_to_file(&args[1]);
+}
+
+
+// -----------------------------------------------------------------------------
+// PRIMITIVES THE EDITOR CAN ADD
+// -----------------------------------------------------------------------------
+// Sized to fit in the view from where the camera starts
+const PRIMITIVE_NAMES: [&str; 10] = ["plane", "box", "sphere", "ellipsoid", "cylinder",
+                                     "cone", "torus", "capsule", "icosphere", "pipe"];
+
+fn add_primitive(model: &mut Model, primitive_index: usize, colour: f32) {
+    let settings = PrimitiveSettings { colour, ..PrimitiveSettings::default() };
+
+    match primitive_index {
+        0 => primitives::plane_grid(model, (1.0, 1.0), (4, 4), settings),
+        1 => primitives::box_mesh(model, Vector3::new(1.0, 1.0, 1.0), (1, 1, 1), settings),
+        2 => primitives::uv_sphere(model, 0.5, 8, 12, settings),
+        3 => primitives::ellipsoid(model, Vector3::new(0.75, 0.5, 0.4), Vector3::new(0.25, 0.5, 0.15), 8, 12, settings),
+        4 => primitives::cylinder(model, 0.5, 1.0, 12, 1, true, settings),
+        5 => primitives::cone(model, 0.5, 1.0, 12, 1, true, settings),
+        6 => primitives::torus(model, 0.5, 0.15, 16, 8, settings),
+        7 => primitives::capsule(model, 0.3, 0.6, 12, 3, settings),
+        8 => primitives::icosphere(model, 0.5, 1, settings),
+        _ => add_pipe(model, settings)
+    }
+}
+
+// Round profile swept along a quarter turn, closed at both ends
+fn add_pipe(model: &mut Model, settings: PrimitiveSettings) {
+    let profile: Vec<Vector2<f32>> = (0..12).map(|index| {
+        let angle = 2.0 * MATH_PI * index as f32 / 12.0;
+        Vector2::new(0.15 * angle.cos(), 0.15 * angle.sin())
+    }).collect();
+    let path: Vec<Vector3<f32>> = (0..8).map(|index| {
+        let angle = MATH_PI / 2.0 * index as f32 / 7.0;
+        Vector3::new(0.5 * angle.cos(), 0.5 * angle.sin(), 0.0)
+    }).collect();
+
+    let mut loft = Loft::sweep(&profile, &path, Vector3::unit_z());
+    loft.close_sections = true;
+    loft.cap_start = true;
+    loft.cap_end = true;
+    loft.build(model, settings);
 }
 
 
@@ -303,6 +1345,8 @@ fn main() {
 // CODE FOR CREATING THE PHATSHIP
 // -----------------------------------------------------------------------------
 fn create_phat_ship(model: &mut Model) {
+    let counts = (model.n_verts(), model.n_lines(), model.n_faces());
+
     // ---- BODY ELLIPSOID ----
     // Define semi axis
     let a1 = 1.5;
@@ -389,214 +1433,70 @@ fn create_phat_ship(model: &mut Model) {
         }
     }
 
-    let mut index_offset = n_lambda * n_theta + 2;
-
-    // ---- LEFT PUSHER OUTER SURFACE----
-    // Define guides
-    let x_max = 2.0;
-    let x_min = -0.5;
-    let y1_top = 1.6;
-    let y1_bot = 2.4;
-    let y2_top = 1.8;
-    let y2_bot = 3.0;
-
-    let z_top = 1.5;
-    let z_bot = -0.5;
-
-    let a_top = (y2_top - y1_top) / ((x_min - x_max) * (x_min - x_max));
-    let b_top = -2.0 * a_top * x_max;
-    let c_top = y1_top + a_top * x_max * x_max;
-
-    let a_bot = (y2_bot - y1_bot) / ((x_min - x_max) * (x_min - x_max));
-    let b_bot = -2.0 * a_bot * x_max;
-    let c_bot = y1_bot + a_bot * x_max * x_max;
+    model.add_group_since("body", counts);
 
-    // Number of points along x and y
-    let n_x = 5;
-    let n_z = 5;
+    // ---- LEFT PUSHER ----
+    let counts = (model.n_verts(), model.n_lines(), model.n_faces());
+    let outer = add_pusher_surface(model, (1.6, 1.8), (2.4, 3.0), 1.0, false);
+    let inner = add_pusher_surface(model, (1.3, 1.3), (2.1, 2.0), 1.0, true);
 
-    for x_index in 0..n_x {
-        // Get position in X and top and bottom Y position
-        let x_pos = x_min + (x_max - x_min) * x_index as f32 / (n_x as f32 - 1.0);
+    // Stitch them together
+    model.bridge_loops(&outer.boundary(), &inner.boundary(), BridgeSettings::default());
 
-        let y_pos_top = a_top * x_pos * x_pos + b_top * x_pos + c_top;
-        let y_pos_bot = a_bot * x_pos * x_pos + b_bot * x_pos + c_bot;
+    let group_index = model.add_group_since("left_pusher", counts);
+    model.part_from_group(group_index, Vector3::unit_y());
 
-        // Generate coefficients for YZ parabola
-        let a_yz = (y_pos_bot - y_pos_top) / ((z_bot - z_top) * (z_bot - z_top));
-        let b_yz = -2.0 * a_yz * z_top;
-        let c_yz = y_pos_top + a_yz * z_top * z_top;
+    // ---- RIGHT PUSHER ----
+    let counts = (model.n_verts(), model.n_lines(), model.n_faces());
+    let outer = add_pusher_surface(model, (1.6, 1.8), (2.4, 3.0), -1.0, true);
+    let inner = add_pusher_surface(model, (1.3, 1.3), (2.1, 2.0), -1.0, false);
 
-        for z_index in 0..n_z {
-            let z_pos = z_bot + (z_top - z_bot) * z_index as f32 / (n_z as f32 - 1.0);
-            let y_pos = a_yz * z_pos * z_pos + b_yz * z_pos + c_yz;
-
-            model.add_vert(Vector3::new(x_pos, y_pos, z_pos));
-
-            // Add the line that joins to the previous one at the same x
-            // slice
-            if z_index != 0 {
-                model.add_line
-                (&vec![z_index - 1 + x_index * n_z + index_offset,
-                        z_index + x_index * n_z + index_offset]);
-            }
+    // Stitch them together
+    model.bridge_loops(&outer.boundary(), &inner.boundary(), BridgeSettings::default());
 
-            // Add the line that joins to the previous one at the same z
-            // slice
-            if x_index != 0 {
-                model.add_line
-                (&vec![z_index + (x_index - 1) * n_z + index_offset,
-                        z_index + x_index * n_z + index_offset]);
-            }
-
-            // Add the faces corresponding to that vertex
-            if z_index != 0 && x_index != 0 {
-                model.add_face
-                (&vec![z_index - 1 + x_index * n_z + index_offset,
-                        z_index - 1 + (x_index - 1) * n_z + index_offset,
-                        z_index + x_index * n_z + index_offset], 0.0);
-                model.add_face
-                (&vec![z_index - 1 + (x_index - 1) * n_z + index_offset,
-                        z_index + (x_index - 1) * n_z + index_offset,
-                        z_index + x_index * n_z + index_offset], 0.0);
-            }
-        }
-    }
-
-    index_offset = index_offset + n_x * n_z;
-
-    // ---- LEFT PUSHER INNER SURFACE----
-    // Define guides
-    let x_max = 2.0;
-    let x_min = -0.5;
-    let y1_top = 1.3;
-    let y1_bot = 2.1;
-    let y2_top = 1.3;
-    let y2_bot = 2.0;
-
-    let z_top = 1.5;
-    let z_bot = -0.5;
-
-    let a_top = (y2_top - y1_top) / ((x_min - x_max) * (x_min - x_max));
-    let b_top = -2.0 * a_top * x_max;
-    let c_top = y1_top + a_top * x_max * x_max;
-
-    let a_bot = (y2_bot - y1_bot) / ((x_min - x_max) * (x_min - x_max));
-    let b_bot = -2.0 * a_bot * x_max;
-    let c_bot = y1_bot + a_bot * x_max * x_max;
-
-    for x_index in 0..n_x {
-        // Get position in X and top and bottom Y position
-        let x_pos = x_min + (x_max - x_min) * x_index as f32 / (n_x as f32 - 1.0);
-
-        let y_pos_top = a_top * x_pos * x_pos + b_top * x_pos + c_top;
-        let y_pos_bot = a_bot * x_pos * x_pos + b_bot * x_pos + c_bot;
-
-        // Generate coefficients for YZ parabola
-        let a_yz = (y_pos_bot - y_pos_top) / ((z_bot - z_top) * (z_bot - z_top));
-        let b_yz = -2.0 * a_yz * z_top;
-        let c_yz = y_pos_top + a_yz * z_top * z_top;
-
-        for z_index in 0..n_z {
-            let z_pos = z_bot + (z_top - z_bot) * z_index as f32 / (n_z as f32 - 1.0);
-            let y_pos = a_yz * z_pos * z_pos + b_yz * z_pos + c_yz;
-
-            model.add_vert(Vector3::new(x_pos, y_pos, z_pos));
-
-            // Add the line that joins to the previous one at the same x
-            // slice
-            if z_index != 0 {
-                model.add_line
-                (&vec![z_index - 1 + x_index * n_z + index_offset,
-                       z_index + x_index * n_z + index_offset]);
-            }
+    let group_index = model.add_group_since("right_pusher", counts);
+    model.part_from_group(group_index, Vector3::unit_y());
+}
 
-            // Add the line that joins to the previous one at the same z
-            // slice
-            if x_index != 0 {
-                model.add_line
-                (&vec![z_index + (x_index - 1) * n_z + index_offset,
-                       z_index + x_index * n_z + index_offset]);
-            }
 
-            // Add the faces corresponding to that vertex
-            if z_index != 0 && x_index != 0 {
-                model.add_face
-                (&vec![z_index - 1 + x_index * n_z + index_offset,
-                       z_index + x_index * n_z + index_offset,
-                       z_index - 1 + (x_index - 1) * n_z + index_offset], 0.0);
-                model.add_face
-                (&vec![z_index - 1 + (x_index - 1) * n_z + index_offset,
-                       z_index + x_index * n_z + index_offset,
-                       z_index + (x_index - 1) * n_z + index_offset], 0.0);
-            }
+// -----------------------------------------------------------------------------
+// CLIP SWIVELLING THE PUSHERS OF THE PHATSHIP
+// -----------------------------------------------------------------------------
+fn create_pusher_swivel() -> Clip {
+    let mut clip = Clip::new("swivel");
+
+    for part_name in ["left_pusher", "right_pusher"].iter() {
+        for (time, angle) in [(0.0, 0.0), (1.0, 0.4), (3.0, -0.4), (4.0, 0.0)].iter() {
+            clip.add_key(part_name, PartKey {
+                time: *time,
+                angle: *angle,
+                translation: Vector3::zero(),
+                interpolation: Interpolation::Bezier
+            });
         }
     }
 
-    // ---- STITCH THEM TOGETHER ----
-    for x_index in 0..n_x {
-        for z_index in 0..n_z {
-            // Add lines
-            if x_index == 0 || x_index == n_x - 1 ||
-               z_index == 0 || z_index == n_z - 1 {
-                model.add_line(&vec![z_index + x_index * n_z + index_offset,
-                                     z_index + x_index * n_z + index_offset - n_x * n_z]);
-            }
-
-            // Add faces
-            if x_index == 0 {
-                if z_index != 0 {
-                    model.add_face(&vec![z_index + x_index * n_z + index_offset,
-                                         z_index + x_index * n_z + index_offset - n_x * n_z,
-                                         z_index - 1 + x_index * n_z + index_offset], 0.0);
-                    model.add_face(&vec![z_index - 1 + x_index * n_z + index_offset - n_x * n_z,
-                                         z_index - 1 + x_index * n_z + index_offset,
-                                         z_index + x_index * n_z + index_offset - n_x * n_z], 0.0);
-                }
-            }
-            if x_index == n_x - 1 {
-                if z_index != 0 {
-                    model.add_face(&vec![z_index + x_index * n_z + index_offset,
-                                         z_index - 1 + x_index * n_z + index_offset,
-                                         z_index + x_index * n_z + index_offset - n_x * n_z], 0.0);
-                    model.add_face(&vec![z_index - 1 + x_index * n_z + index_offset - n_x * n_z,
-                                         z_index + x_index * n_z + index_offset - n_x * n_z,
-                                         z_index - 1 + x_index * n_z + index_offset], 0.0);
-                }
-            }
-            if z_index == 0 {
-                if x_index != 0 {
-                    model.add_face(&vec![z_index + x_index * n_z + index_offset,
-                                         z_index + (x_index - 1) * n_z + index_offset,
-                                         z_index + x_index * n_z + index_offset - n_x * n_z], 0.0);
-                    model.add_face(&vec![z_index + (x_index - 1) * n_z + index_offset - n_x * n_z,
-                                         z_index + x_index * n_z + index_offset - n_x * n_z,
-                                         z_index + (x_index - 1) * n_z + index_offset], 0.0);
-                }
-            }
-            if z_index == n_z - 1 {
-                if x_index != 0 {
-                    model.add_face(&vec![z_index + x_index * n_z + index_offset,
-                                         z_index + x_index * n_z + index_offset - n_x * n_z,
-                                         z_index + (x_index - 1) * n_z + index_offset], 0.0);
-                    model.add_face(&vec![z_index + (x_index - 1) * n_z + index_offset - n_x * n_z,
-                                         z_index + (x_index - 1) * n_z + index_offset,
-                                         z_index + x_index * n_z + index_offset - n_x * n_z], 0.0);
-                }
-            }
-        }
-    }
+    clip
+}
 
-    index_offset = index_offset + n_x * n_z;
 
-    // ---- RIGHT PUSHER OUTER SURFACE----
+// -----------------------------------------------------------------------------
+// PUSHER SURFACE LOFTED FROM PARABOLIC SECTIONS
+// -----------------------------------------------------------------------------
+// The Y of the surface follows a parabola along X for the top and bottom
+// guides, given as (Y at x_max, Y at x_min), and each section between them is
+// another parabola along Z. Side is 1.0 for the left pusher and -1.0 for the
+// right one
+fn add_pusher_surface(model: &mut Model,
+                      y_top: (f32, f32),
+                      y_bot: (f32, f32),
+                      side: f32,
+                      flip: bool) -> LoftGrid {
     // Define guides
     let x_max = 2.0;
     let x_min = -0.5;
-    let y1_top = 1.6;
-    let y1_bot = 2.4;
-    let y2_top = 1.8;
-    let y2_bot = 3.0;
+    let (y1_top, y2_top) = y_top;
+    let (y1_bot, y2_bot) = y_bot;
 
     let z_top = 1.5;
     let z_bot = -0.5;
@@ -609,79 +1509,12 @@ fn create_phat_ship(model: &mut Model) {
     let b_bot = -2.0 * a_bot * x_max;
     let c_bot = y1_bot + a_bot * x_max * x_max;
 
-    // Number of points along x and y
+    // Number of points along x and z
     let n_x = 5;
     let n_z = 5;
 
-    for x_index in 0..n_x {
-        // Get position in X and top and bottom Y position
-        let x_pos = x_min + (x_max - x_min) * x_index as f32 / (n_x as f32 - 1.0);
-
-        let y_pos_top = a_top * x_pos * x_pos + b_top * x_pos + c_top;
-        let y_pos_bot = a_bot * x_pos * x_pos + b_bot * x_pos + c_bot;
-
-        // Generate coefficients for YZ parabola
-        let a_yz = (y_pos_bot - y_pos_top) / ((z_bot - z_top) * (z_bot - z_top));
-        let b_yz = -2.0 * a_yz * z_top;
-        let c_yz = y_pos_top + a_yz * z_top * z_top;
-
-        for z_index in 0..n_z {
-            let z_pos = z_bot + (z_top - z_bot) * z_index as f32 / (n_z as f32 - 1.0);
-            let y_pos = a_yz * z_pos * z_pos + b_yz * z_pos + c_yz;
-
-            model.add_vert(Vector3::new(x_pos, -y_pos, z_pos));
-
-            // Add the line that joins to the previous one at the same x
-            // slice
-            if z_index != 0 {
-                model.add_line
-                (&vec![z_index - 1 + x_index * n_z + index_offset,
-                       z_index + x_index * n_z + index_offset]);
-            }
-
-            // Add the line that joins to the previous one at the same z
-            // slice
-            if x_index != 0 {
-                model.add_line
-                (&vec![z_index + (x_index - 1) * n_z + index_offset,
-                       z_index + x_index * n_z + index_offset]);
-            }
-
-            // Add the faces corresponding to that vertex
-            if z_index != 0 && x_index != 0 {
-                model.add_face
-                (&vec![z_index - 1 + x_index * n_z + index_offset,
-                       z_index + x_index * n_z + index_offset,
-                       z_index - 1 + (x_index - 1) * n_z + index_offset], 0.0);
-                model.add_face
-                (&vec![z_index - 1 + (x_index - 1) * n_z + index_offset,
-                       z_index + x_index * n_z + index_offset,
-                       z_index + (x_index - 1) * n_z + index_offset], 0.0);
-            }
-        }
-    }
-
-    index_offset = index_offset + n_x * n_z;
-
-    // ---- RIGHT PUSHER INNER SURFACE----
-    // Define guides
-    let x_max = 2.0;
-    let x_min = -0.5;
-    let y1_top = 1.3;
-    let y1_bot = 2.1;
-    let y2_top = 1.3;
-    let y2_bot = 2.0;
-
-    let z_top = 1.5;
-    let z_bot = -0.5;
-
-    let a_top = (y2_top - y1_top) / ((x_min - x_max) * (x_min - x_max));
-    let b_top = -2.0 * a_top * x_max;
-    let c_top = y1_top + a_top * x_max * x_max;
-
-    let a_bot = (y2_bot - y1_bot) / ((x_min - x_max) * (x_min - x_max));
-    let b_bot = -2.0 * a_bot * x_max;
-    let c_bot = y1_bot + a_bot * x_max * x_max;
+    let mut loft = Loft::new(n_z);
+    loft.flip = flip;
 
     for x_index in 0..n_x {
         // Get position in X and top and bottom Y position
@@ -695,93 +1528,13 @@ fn create_phat_ship(model: &mut Model) {
         let b_yz = -2.0 * a_yz * z_top;
         let c_yz = y_pos_top + a_yz * z_top * z_top;
 
-        for z_index in 0..n_z {
-            let z_pos = z_bot + (z_top - z_bot) * z_index as f32 / (n_z as f32 - 1.0);
+        loft.add_section(CrossSection::Curve(Box::new(move |t: f32| {
+            let z_pos = z_bot + (z_top - z_bot) * t;
             let y_pos = a_yz * z_pos * z_pos + b_yz * z_pos + c_yz;
 
-            model.add_vert(Vector3::new(x_pos, -y_pos, z_pos));
-
-            // Add the line that joins to the previous one at the same x
-            // slice
-            if z_index != 0 {
-                model.add_line
-                (&vec![z_index - 1 + x_index * n_z + index_offset,
-                       z_index + x_index * n_z + index_offset]);
-            }
-
-            // Add the line that joins to the previous one at the same z
-            // slice
-            if x_index != 0 {
-                model.add_line
-                (&vec![z_index + (x_index - 1) * n_z + index_offset,
-                       z_index + x_index * n_z + index_offset]);
-            }
-
-            // Add the faces corresponding to that vertex
-            if z_index != 0 && x_index != 0 {
-                model.add_face
-                (&vec![z_index - 1 + x_index * n_z + index_offset,
-                       z_index - 1 + (x_index - 1) * n_z + index_offset,
-                       z_index + x_index * n_z + index_offset], 0.0);
-                model.add_face
-                (&vec![z_index - 1 + (x_index - 1) * n_z + index_offset,
-                       z_index + (x_index - 1) * n_z + index_offset,
-                       z_index + x_index * n_z + index_offset], 0.0);
-            }
-        }
+            Vector3::new(x_pos, side * y_pos, z_pos)
+        })));
     }
 
-    // ---- STITCH THEM TOGETHER ----
-    for x_index in 0..n_x {
-        for z_index in 0..n_z {
-            // Add lines
-            if x_index == 0 || x_index == n_x - 1 ||
-               z_index == 0 || z_index == n_z - 1 {
-                model.add_line(&vec![z_index + x_index * n_z + index_offset,
-                                     z_index + x_index * n_z + index_offset - n_x * n_z]);
-            }
-
-            // Add faces
-            if x_index == 0 {
-                if z_index != 0 {
-                    model.add_face(&vec![z_index + x_index * n_z + index_offset,
-                                         z_index - 1 + x_index * n_z + index_offset,
-                                         z_index + x_index * n_z + index_offset - n_x * n_z], 0.0);
-                    model.add_face(&vec![z_index - 1 + x_index * n_z + index_offset - n_x * n_z,
-                                         z_index + x_index * n_z + index_offset - n_x * n_z,
-                                         z_index - 1 + x_index * n_z + index_offset], 0.0);
-                }
-            }
-            if x_index == n_x - 1 {
-                if z_index != 0 {
-                    model.add_face(&vec![z_index + x_index * n_z + index_offset,
-                                         z_index + x_index * n_z + index_offset - n_x * n_z,
-                                         z_index - 1 + x_index * n_z + index_offset], 0.0);
-                    model.add_face(&vec![z_index - 1 + x_index * n_z + index_offset - n_x * n_z,
-                                         z_index - 1 + x_index * n_z + index_offset,
-                                         z_index + x_index * n_z + index_offset - n_x * n_z], 0.0);
-                }
-            }
-            if z_index == 0 {
-                if x_index != 0 {
-                    model.add_face(&vec![z_index + x_index * n_z + index_offset,
-                                         z_index + x_index * n_z + index_offset - n_x * n_z,
-                                         z_index + (x_index - 1) * n_z + index_offset], 0.0);
-                    model.add_face(&vec![z_index + (x_index - 1) * n_z + index_offset - n_x * n_z,
-                                         z_index + (x_index - 1) * n_z + index_offset,
-                                         z_index + x_index * n_z + index_offset - n_x * n_z], 0.0);
-                }
-            }
-            if z_index == n_z - 1 {
-                if x_index != 0 {
-                    model.add_face(&vec![z_index + x_index * n_z + index_offset,
-                                         z_index + (x_index - 1) * n_z + index_offset,
-                                         z_index + x_index * n_z + index_offset - n_x * n_z], 0.0);
-                    model.add_face(&vec![z_index + (x_index - 1) * n_z + index_offset - n_x * n_z,
-                                         z_index + x_index * n_z + index_offset - n_x * n_z,
-                                         z_index + (x_index - 1) * n_z + index_offset], 0.0);
-                }
-            }
-        }
-    }
-}
\ No newline at end of file
+    loft.build(model, PrimitiveSettings { normals: false, lines: true, colour: 0.0 })
+}